@@ -4,6 +4,7 @@ use crate::ReadFrom;
 
 #[derive(Debug)]
 pub enum AttrInfo {
+    BootstrapMethods(Vec<BootstrapMethod>),
     Code(Code),
     // refers to constant pool constant long, constant float, constant double, constant integer, constant string
     ConstantValue(u16),
@@ -22,14 +23,14 @@ pub enum AttrInfo {
     },
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct LineNumber {
-    start_pc: u16,
-    line_number: u16,
+    pub start_pc: u16,
+    pub line_number: u16,
 }
 
 impl ReadFrom for LineNumber {
-    fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> Self {
+    fn read_from(p: &mut ClassFileParser, _cp: &ConstantPool) -> Self {
         Self {
             start_pc: p.u16(),
             line_number: p.u16(),
@@ -62,16 +63,34 @@ impl ReadFrom for LocalVariable {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
+pub struct BootstrapMethod {
+    // refers to constant pool method handle
+    pub method_ref: u16,
+    // refers to constant pool loadable constants
+    pub args: Vec<u16>,
+}
+
+impl ReadFrom for BootstrapMethod {
+    fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> Self {
+        BootstrapMethod {
+            method_ref: p.u16(),
+            args: u16::read_vec_from(p, cp),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Exception {
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    catch_type: u16,
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    // refers to constant pool class info, 0 catches any throwable
+    pub catch_type: u16,
 }
 
 impl ReadFrom for Exception {
-    fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> Self {
+    fn read_from(p: &mut ClassFileParser, _cp: &ConstantPool) -> Self {
         Exception {
             start_pc: p.u16(),
             end_pc: p.u16(),
@@ -121,6 +140,7 @@ impl ReadFrom for AttrInfo {
             }
             "ConstantValue" => Self::ConstantValue(p.u16()),
             "Code" => Self::Code(Code::read_from(p, cp)),
+            "BootstrapMethods" => Self::BootstrapMethods(BootstrapMethod::read_vec_from(p, cp)),
             "LineNumberTable" => Self::LineNumberTable(LineNumber::read_vec_from(p, cp)),
             "LocalVariableTable" => Self::LocalVariableTable(LocalVariable::read_vec_from(p, cp)),
             // constant pool index refers to class info
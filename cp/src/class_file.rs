@@ -1,5 +1,4 @@
-use crate::{AttrInfo, ConstantInfo, ConstantPool, ReadFrom};
-use rp::Rp;
+use crate::{AttrInfo, ConstantInfo, ConstantPool, ReadFrom, Symbol};
 
 #[derive(Default, Debug)]
 // java 类文件
@@ -38,8 +37,8 @@ pub struct MemberInfo {
     // refers to constant pool utf8
     pub name_i: u16,
     pub desc_i: u16,
-    pub name: String,
-    pub desc: String,
+    pub name: Symbol,
+    pub desc: Symbol,
     pub attrs: Vec<AttrInfo>,
 }
 
@@ -53,8 +52,8 @@ impl ReadFrom for MemberInfo {
             name_i: name_i,
             desc_i: desc_i,
             attrs: Vec::new(),
-            name: cp.utf8(name_i as usize).to_string(),
-            desc: cp.utf8(desc_i as usize).to_string(),
+            name: cp.symbol(name_i as usize),
+            desc: cp.symbol(desc_i as usize),
         };
         // parse attributes
         m.attrs = AttrInfo::read_vec_from(p, cp);
@@ -86,7 +85,7 @@ impl ReadFrom for ConstantPool {
 }
 
 impl ReadFrom for ConstantInfo {
-    fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> ConstantInfo {
+    fn read_from(p: &mut ClassFileParser, _cp: &ConstantPool) -> ConstantInfo {
         let tag = p.u8();
         use ct_info_tag::*;
         match tag {
@@ -98,8 +97,8 @@ impl ReadFrom for ConstantInfo {
                 let str_len = p.u16() as usize;
                 let bytes = p.bytes(str_len);
                 let utf8 = mutf8::mutf8_to_utf8(bytes).unwrap();
-                let s = String::from_utf8(utf8.into_owned()).unwrap();
-                ConstantInfo::Utf8(s)
+                let s = std::str::from_utf8(&utf8).unwrap();
+                ConstantInfo::Utf8(Symbol::intern(s))
             }
             STRING => ConstantInfo::String { utf8_i: p.u16() },
             CLASS => ConstantInfo::Class { name_i: p.u16() },
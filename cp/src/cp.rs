@@ -1,3 +1,5 @@
+use crate::Symbol;
+
 macro_rules! cp_member {
     ($n: ident, $t: path) => {
         // field index -> (class, name, desc)
@@ -36,6 +38,10 @@ pub enum Constant<'a> {
     // class reference
     ClassRef(u16),
     String(&'a str),
+    // reference kind, index of field/method/interface method ref
+    MethodHandle(u8, u16),
+    // method descriptor
+    MethodType(&'a str),
 }
 
 // 常量池
@@ -50,7 +56,7 @@ pub enum ConstantInfo {
     Float(f32),
     Long(u64),
     Double(f64),
-    Utf8(String),
+    Utf8(Symbol),
     String {
         // index refers to utf8
         utf8_i: u16,
@@ -110,9 +116,13 @@ impl ConstantPool {
     pub fn new(infos: Vec<ConstantInfo>) -> Self {
         Self { infos }
     }
-    pub fn utf8(&self, i: usize) -> &str {
+    pub fn utf8(&self, i: usize) -> &'static str {
+        self.symbol(i).as_str()
+    }
+
+    pub fn symbol(&self, i: usize) -> Symbol {
         match self.infos[i as usize] {
-            ConstantInfo::Utf8(ref a) => a,
+            ConstantInfo::Utf8(s) => s,
             _ => panic!("invalid utf8 index"),
         }
     }
@@ -133,6 +143,10 @@ impl ConstantPool {
             ConstantInfo::Double(j) => Constant::Primitive(j.to_bits(), true),
             ConstantInfo::Class { name_i } => Constant::ClassRef(name_i),
             ConstantInfo::String { utf8_i } => Constant::String(self.utf8(utf8_i as usize)),
+            ConstantInfo::MethodHandle { ref_kind, ref_i } => {
+                Constant::MethodHandle(ref_kind, ref_i)
+            }
+            ConstantInfo::MethodType { desc_i } => Constant::MethodType(self.utf8(desc_i as usize)),
             _ => panic!("invalid constant {} {:?}", i, self.infos[i]),
         }
     }
@@ -145,6 +159,56 @@ impl ConstantPool {
         self.utf8(j as usize)
     }
 
+    // name and type index -> (name, desc)
+    pub fn name_and_type(&self, i: usize) -> (&str, &str) {
+        match self.infos[i] {
+            ConstantInfo::NameAndType { name_i, desc_i } => {
+                (self.utf8(name_i as usize), self.utf8(desc_i as usize))
+            }
+            _ => panic!("invalid name type index {}", i),
+        }
+    }
+
+    // method handle index -> (reference kind, index of field/method/interface method ref)
+    pub fn method_handle(&self, i: usize) -> (u8, u16) {
+        match self.infos[i] {
+            ConstantInfo::MethodHandle { ref_kind, ref_i } => (ref_kind, ref_i),
+            _ => panic!("invalid method handle index {}", i),
+        }
+    }
+
+    // method type index -> method descriptor
+    pub fn method_type(&self, i: usize) -> &str {
+        match self.infos[i] {
+            ConstantInfo::MethodType { desc_i } => self.utf8(desc_i as usize),
+            _ => panic!("invalid method type index {}", i),
+        }
+    }
+
+    // invoke dynamic index -> (bootstrap method index, name, desc)
+    pub fn invoke_dynamic(&self, i: usize) -> (u16, &str, &str) {
+        match self.infos[i] {
+            ConstantInfo::InvokeDynamic {
+                boot_i,
+                name_type_i,
+            } => {
+                let (name, desc) = self.name_and_type(name_type_i as usize);
+                (boot_i, name, desc)
+            }
+            _ => panic!("invalid invoke dynamic index {}", i),
+        }
+    }
+
+    // field, method or interface method index -> (class, name, desc)
+    pub fn member_ref(&self, i: usize) -> (&str, &str, &str) {
+        match self.infos[i] {
+            ConstantInfo::FieldRef { .. } => self.field_ref(i),
+            ConstantInfo::MethodRef { .. } => self.method_ref(i),
+            ConstantInfo::IFaceMethodRef { .. } => self.iface_ref(i),
+            _ => panic!("invalid member index {}", i),
+        }
+    }
+
     pub fn infos(&self) -> &[ConstantInfo] {
         &self.infos
     }
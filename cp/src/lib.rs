@@ -3,6 +3,7 @@ mod class_file;
 mod cp;
 mod entry;
 mod io_utils;
+mod symbol;
 
 #[macro_use]
 extern crate err;
@@ -11,6 +12,7 @@ pub use crate::attr::*;
 pub use crate::class_file::*;
 pub use crate::cp::*;
 pub use crate::entry::{new_entry, Entry};
+pub use crate::symbol::Symbol;
 
 trait ReadFrom: Sized {
     fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> Self;
@@ -26,7 +28,7 @@ trait ReadFrom: Sized {
 }
 
 impl ReadFrom for u16 {
-    fn read_from(p: &mut ClassFileParser, cp: &ConstantPool) -> Self {
+    fn read_from(p: &mut ClassFileParser, _cp: &ConstantPool) -> Self {
         p.u16()
     }
 }
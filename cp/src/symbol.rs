@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::ops::Deref;
+use std::sync::{OnceLock, RwLock};
+
+// interned name or descriptor, equal strings have equal symbols
+// strings are never freed, so a symbol is valid for the whole process
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Table {
+    ids: HashMap<&'static str, u32>,
+    strs: Vec<&'static str>,
+}
+
+fn table() -> &'static RwLock<Table> {
+    static TABLE: OnceLock<RwLock<Table>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        // empty string is the default symbol
+        let mut t = Table::default();
+        t.ids.insert("", 0);
+        t.strs.push("");
+        RwLock::new(t)
+    })
+}
+
+impl Symbol {
+    pub fn intern(s: &str) -> Symbol {
+        if let Some(&i) = table().read().unwrap().ids.get(s) {
+            return Symbol(i);
+        }
+
+        let mut t = table().write().unwrap();
+        if let Some(&i) = t.ids.get(s) {
+            return Symbol(i);
+        }
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        let i = t.strs.len() as u32;
+        t.strs.push(s);
+        t.ids.insert(s, i);
+        Symbol(i)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        table().read().unwrap().strs[self.0 as usize]
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    // number of interned symbols
+    pub fn count() -> usize {
+        table().read().unwrap().strs.len()
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &'static str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Symbol::intern(&s)
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(s: &Symbol) -> Self {
+        *s
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Symbol;
+
+    #[test]
+    fn intern_test() {
+        let a = Symbol::intern("java/lang/Object");
+        let b: Symbol = "java/lang/Object".to_string().into();
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("java/lang/String"));
+        assert_eq!(a, "java/lang/Object");
+        assert_eq!(a.len(), 16);
+        assert_eq!(Symbol::default(), "");
+        assert_eq!(format!("{}", a), "java/lang/Object");
+    }
+}
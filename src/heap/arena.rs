@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+// bytes of a chunk that small objects are bumped from
+pub const CHUNK: usize = 1024 * 1024;
+// objects larger than this get a chunk of their own, released once they die
+const LARGE: usize = CHUNK / 4;
+
+struct Chunk {
+    mem: Box<[u64]>,
+    // bytes bumped so far
+    top: usize,
+    // bytes of blocks not freed
+    live: usize,
+}
+
+impl Chunk {
+    fn new(bytes: usize) -> Self {
+        Chunk {
+            mem: vec![0u64; bytes / 8].into_boxed_slice(),
+            top: 0,
+            live: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.mem.len() * 8
+    }
+}
+
+// vm managed memory of java objects, blocks are bumped from chunks and reused after free
+// freed blocks are kept by size, a chunk is released once all of its blocks are freed
+#[derive(Default)]
+pub struct Arena {
+    // chunks by start address
+    chunks: BTreeMap<usize, Chunk>,
+    // chunk small objects are bumped from
+    cur: usize,
+    // addresses of freed blocks by size
+    free: BTreeMap<usize, Vec<usize>>,
+    capacity: usize,
+}
+
+// blocks are word aligned
+fn align(bytes: usize) -> usize {
+    (bytes.max(8) + 7) & !7
+}
+
+impl Arena {
+    // bytes of all chunks
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // zeroed block of at least bytes
+    pub fn alloc(&mut self, bytes: usize) -> *mut u8 {
+        let bytes = align(bytes);
+
+        if let Some(p) = self.free.get_mut(&bytes).and_then(|v| v.pop()) {
+            unsafe { std::ptr::write_bytes(p as *mut u8, 0, bytes) };
+            self.chunk_of(p).live += bytes;
+            return p as *mut u8;
+        }
+
+        if bytes > LARGE {
+            let p = self.add_chunk(bytes);
+            let c = self.chunks.get_mut(&p).unwrap();
+            c.top = bytes;
+            c.live = bytes;
+            return p as *mut u8;
+        }
+
+        let fits = match self.chunks.get(&self.cur) {
+            Some(c) => c.top + bytes <= c.len(),
+            None => false,
+        };
+        if !fits {
+            self.cur = self.add_chunk(CHUNK);
+        }
+
+        let start = self.cur;
+        let c = self.chunks.get_mut(&start).unwrap();
+        let p = start + c.top;
+        c.top += bytes;
+        c.live += bytes;
+        p as *mut u8
+    }
+
+    // give back a block of bytes returned by alloc
+    pub fn free(&mut self, p: *mut u8, bytes: usize) {
+        let bytes = align(bytes);
+        self.chunk_of(p as usize).live -= bytes;
+        self.free.entry(bytes).or_default().push(p as usize);
+    }
+
+    // release chunks without live blocks, called after a collection
+    pub fn trim(&mut self) {
+        let cur = self.cur;
+        let empty: Vec<usize> = self
+            .chunks
+            .iter()
+            .filter(|&(&p, c)| c.live == 0 && p != cur)
+            .map(|(&p, _)| p)
+            .collect();
+        if empty.is_empty() {
+            return;
+        }
+
+        for p in empty.iter() {
+            let c = self.chunks.remove(p).unwrap();
+            self.capacity -= c.len();
+        }
+
+        let chunks = &self.chunks;
+        for v in self.free.values_mut() {
+            v.retain(|&p| {
+                chunks
+                    .range(..=p)
+                    .next_back()
+                    .map_or(false, |(&s, c)| p < s + c.len())
+            });
+        }
+        self.free.retain(|_, v| !v.is_empty());
+    }
+
+    fn add_chunk(&mut self, bytes: usize) -> usize {
+        let c = Chunk::new(bytes);
+        let p = c.mem.as_ptr() as usize;
+        self.capacity += c.len();
+        self.chunks.insert(p, c);
+        p
+    }
+
+    fn chunk_of(&mut self, p: usize) -> &mut Chunk {
+        self.chunks
+            .range_mut(..=p)
+            .next_back()
+            .map(|(_, c)| c)
+            .expect("block outside of arena")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Arena, CHUNK};
+
+    #[test]
+    fn arena_test() {
+        let mut a = Arena::default();
+        let p = a.alloc(40);
+        let q = a.alloc(13);
+        assert_eq!(q as usize - p as usize, 40);
+        assert_eq!(a.capacity(), CHUNK);
+
+        // freed block of the same size is reused zeroed
+        unsafe { *(q as *mut u64) = 7 };
+        a.free(q, 13);
+        assert_eq!(a.alloc(16), q);
+        assert_eq!(unsafe { *(q as *mut u64) }, 0);
+
+        // large blocks have their own chunk, released after free
+        let l = a.alloc(CHUNK);
+        assert_eq!(a.capacity(), 2 * CHUNK);
+        a.free(l, CHUNK);
+        a.trim();
+        assert_eq!(a.capacity(), CHUNK);
+    }
+}
@@ -1,30 +1,41 @@
-use crate::heap::misc::{AccessFlags, SymRef};
-use crate::runtime::vm::JThread;
+use crate::heap::misc::{AccessFlags, SymRef, PRIMITIVES};
 use cp::AttrInfo;
-use cp::{ClassFile, ConstantPool, MemberInfo};
+use cp::{BootstrapMethod, ClassFile, ConstantPool, Exception, LineNumber, MemberInfo, Symbol};
 use rp::Rp;
 use std::fmt::Debug;
-use std::ops::Deref;
 
 use super::desc::MethodDescriptor;
+use super::icache::InlineCache;
+use super::refmap::{self, RefMap};
 use crate::heap::misc::Heap;
+use crate::ins::decode::{self, Insn};
+use crate::jit::Code;
+use crate::natives::NativeMethodW;
 
 impl From<ClassFile> for Class {
     fn from(mut c: ClassFile) -> Self {
         let mut r = Class::default();
         r.access_flags = AccessFlags(c.access_flags);
-        r.name = c.this_class().to_string();
-        r.desc = format!("L{};", r.name);
-        r.super_name = c.super_class().to_string();
+        r.name = c.this_class().into();
+        r.desc = format!("L{};", r.name).into();
+        r.super_name = c.super_class().into();
         r.iface_names = c
             .interfaces_i
             .iter()
-            .map(|&x| c.cp.class(x as usize).to_string())
+            .map(|&x| c.cp.class(x as usize).into())
             .collect();
         r.fields = c.fields.iter_mut().map(|x| x.into()).collect();
         r.methods = c.methods.iter_mut().map(|x| x.into()).collect();
         r.sym_refs = vec![Rp::null(); c.cp.len()];
 
+        for attr in c.attrs.iter_mut() {
+            match attr {
+                AttrInfo::SourceFile(f) => r.source_file = f.to_string(),
+                AttrInfo::BootstrapMethods(b) => std::mem::swap(&mut r.bootstrap_methods, b),
+                _ => {}
+            }
+        }
+
         core::mem::swap(&mut c.cp, &mut r.cp);
         r
     }
@@ -33,16 +44,23 @@ impl From<ClassFile> for Class {
 impl From<&mut MemberInfo> for ClassMember {
     fn from(m: &mut MemberInfo) -> Self {
         let mut r = ClassMember::default();
-        r.name = m.name.to_string();
+        r.name = m.name.into();
         r.access_flags = AccessFlags(m.access_flags);
-        r.desc = m.desc.to_string();
+        r.desc = m.desc.into();
 
         for attr in m.attrs.iter_mut() {
             match attr {
                 &mut AttrInfo::Code(ref mut c) => {
                     std::mem::swap(&mut r.code, &mut c.code);
+                    std::mem::swap(&mut r.exceptions, &mut c.exceptions);
                     r.max_stack = c.max_stack;
                     r.max_locals = c.max_locals;
+
+                    for a in c.attrs.iter_mut() {
+                        if let AttrInfo::LineNumberTable(t) = a {
+                            std::mem::swap(&mut r.line_numbers, t);
+                        }
+                    }
                 }
                 &mut AttrInfo::ConstantValue(i) => {
                     r.cons_i = i as usize;
@@ -54,8 +72,86 @@ impl From<&mut MemberInfo> for ClassMember {
     }
 }
 
+// lock word of object header
+// bits 0..2 state: 00 unlocked, 01 thin locked, 10 inflated
+// thin locked: recursion count in bits 2..32, owner thread id in bits 32..64
+// inflated: monitor index in bits 2..64
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LockWord(pub u64);
+
+impl LockWord {
+    const THIN: u64 = 0b01;
+    const INFLATED: u64 = 0b10;
+
+    pub fn unlocked() -> Self {
+        LockWord(0)
+    }
+
+    pub fn thin(owner: u32, count: u32) -> Self {
+        LockWord(((owner as u64) << 32) | ((count as u64 & 0x3fff_ffff) << 2) | Self::THIN)
+    }
+
+    pub fn inflated(monitor: usize) -> Self {
+        LockWord(((monitor as u64) << 2) | Self::INFLATED)
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.0 & 0b11 == 0
+    }
+
+    pub fn is_thin(&self) -> bool {
+        self.0 & 0b11 == Self::THIN
+    }
+
+    pub fn is_inflated(&self) -> bool {
+        self.0 & 0b11 == Self::INFLATED
+    }
+
+    // owner thread of thin lock
+    pub fn owner(&self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    // recursion count of thin lock
+    pub fn count(&self) -> u32 {
+        ((self.0 >> 2) & 0x3fff_ffff) as u32
+    }
+
+    // monitor index of inflated lock
+    pub fn monitor(&self) -> usize {
+        (self.0 >> 2) as usize
+    }
+}
+
+// object header
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Header {
+    // identity hash, 0 until first requested
+    pub hash: u32,
+    // gc mark bits
+    pub marks: u8,
+    pub lock: LockWord,
+}
+
+impl Header {
+    pub const MARKED: u8 = 0x01;
+
+    pub fn is_marked(&self) -> bool {
+        self.marks & Self::MARKED != 0
+    }
+
+    pub fn set_marked(&mut self, marked: bool) {
+        if marked {
+            self.marks |= Self::MARKED;
+        } else {
+            self.marks &= !Self::MARKED;
+        }
+    }
+}
+
 // represents both object and primitive array
 pub struct Object {
+    pub header: Header,
     pub class: Rp<Class>,
     // object size/array length
     pub size: usize,
@@ -63,7 +159,22 @@ pub struct Object {
     pub data: usize,
 }
 
+// only the class name, the class refers back to its objects through sym_refs
+impl Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Object")
+            .field("class", &self.class.name)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
 impl Object {
+    // heap bytes of an object with n fields or elements
+    pub fn bytes(n: usize, elem_size: usize) -> usize {
+        std::mem::size_of::<Object>().saturating_add(n.saturating_mul(elem_size))
+    }
+
     pub fn fields(&self) -> &mut [u64] {
         let p: Rp<u64> = self.data.into();
         p.as_slice(self.size)
@@ -79,6 +190,41 @@ impl Object {
         (p as usize).into()
     }
 
+    // identity hash is assigned on first use and kept in header, so it doesn't depend on address
+    pub fn identity_hash(&mut self) -> i32 {
+        if self.header.hash == 0 {
+            let mut heap = self.class.heap;
+            self.header.hash = heap.next_hash();
+        }
+        self.header.hash as i32
+    }
+
+    // references held in fields or array elements
+    pub fn refs(&self) -> Vec<Rp<Object>> {
+        let c = self.class;
+        let data: Rp<u64> = self.data.into();
+
+        if c.dim > 0 {
+            if c.dim == 1 && c.element_class.is_primitive() {
+                return Vec::new();
+            }
+            return data
+                .as_slice(self.size)
+                .iter()
+                .filter(|&&x| x != 0)
+                .map(|&x| (x as usize).into())
+                .collect();
+        }
+
+        // class objects carry a class pointer in the slot behind fields
+        c.ins_ref_map
+            .iter()
+            .enumerate()
+            .filter(|&(i, &r)| r && data[i] != 0)
+            .map(|(i, _)| (data[i] as usize).into())
+            .collect()
+    }
+
     pub fn instance_of(&self, c: &Class) -> bool {
         c.is_assignable(&self.class)
     }
@@ -87,24 +233,54 @@ impl Object {
         self.set_field(field, r.ptr() as u64);
     }
 
-    pub fn set_field(&mut self, field: &str, v: u64) {
-        for i in (0..self.class.ins_fields.len()).rev() {
-            if self.class.ins_fields[i].name == field {
-                self.set(i, v);
+    pub fn set_field(&mut self, field: impl Into<Symbol>, v: u64) {
+        let field: Symbol = field.into();
+        for f in self.class.ins_fields.iter().rev() {
+            if f.name == field {
+                self.store(f.offset, f.field_type(), v);
                 return;
             }
         }
     }
 
-    pub fn get_field(&self, field: &str) -> u64 {
-        for i in (0..self.class.ins_fields.len()).rev() {
-            if self.class.ins_fields[i].name == field {
-                return self.fields()[i];
+    pub fn get_field(&self, field: impl Into<Symbol>) -> u64 {
+        let field: Symbol = field.into();
+        for f in self.class.ins_fields.iter().rev() {
+            if f.name == field {
+                return self.load(f.offset, f.field_type());
             }
         }
         0
     }
 
+    // field of type t at byte offset, small ints are extended as kept in a slot
+    pub fn load(&self, off: usize, t: u8) -> u64 {
+        let p = self.data + off;
+        unsafe {
+            match t {
+                b'Z' => *(p as *const u8) as u64,
+                b'B' => *(p as *const i8) as i32 as u32 as u64,
+                b'C' => *(p as *const u16) as u64,
+                b'S' => *(p as *const i16) as i32 as u32 as u64,
+                b'I' | b'F' => *(p as *const u32) as u64,
+                _ => *(p as *const u64),
+            }
+        }
+    }
+
+    // store low bytes of a slot value to field of type t at byte offset
+    pub fn store(&mut self, off: usize, t: u8, v: u64) {
+        let p = self.data + off;
+        unsafe {
+            match t {
+                b'Z' | b'B' => *(p as *mut u8) = v as u8,
+                b'C' | b'S' => *(p as *mut u16) = v as u16,
+                b'I' | b'F' => *(p as *mut u32) = v as u32,
+                _ => *(p as *mut u64) = v,
+            }
+        }
+    }
+
     pub fn jarray<T: 'static>(&self) -> &mut [T] {
         let p: Rp<T> = self.data.into();
         p.as_slice(self.size)
@@ -127,14 +303,42 @@ impl Object {
     }
 }
 
+// initialization state of class, JVMS 5.5
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InitState {
+    #[default]
+    Uninitialized,
+    // <clinit> is running on thread of id
+    BeingInitialized(u32),
+    Initialized,
+    // <clinit> completed abruptly
+    Erroneous,
+}
+
+// linkage error raised by method selection, thrown as a java exception
+#[derive(Debug)]
+pub struct LinkError {
+    // internal name of exception class
+    pub class: &'static str,
+    pub msg: String,
+}
+
+impl LinkError {
+    pub fn new(class: &'static str, msg: String) -> Self {
+        LinkError { class, msg }
+    }
+}
+
 #[derive(Default)]
 pub struct Class {
     pub heap: Rp<Heap>,
     pub access_flags: AccessFlags,
-    pub name: String,
-    pub desc: String,
-    pub super_name: String,
-    pub iface_names: Vec<String>,
+    pub name: Symbol,
+    pub desc: Symbol,
+    pub super_name: Symbol,
+    pub iface_names: Vec<Symbol>,
+    pub source_file: String,
+    pub bootstrap_methods: Vec<BootstrapMethod>,
     pub cp: ConstantPool,
     pub fields: Vec<ClassMember>,
     pub methods: Vec<ClassMember>,
@@ -142,15 +346,28 @@ pub struct Class {
     pub super_class: Rp<Class>,
     pub interfaces: Vec<Rp<Class>>,
 
+    // virtual methods by vtable index, entries of super class come first
+    // for interface, declared instance methods by itable index
+    pub vtable: Vec<Rp<ClassMember>>,
+    // methods selected for each super interface by its itable index, null if selection fails
+    pub itables: Vec<(Rp<Class>, Vec<Rp<ClassMember>>)>,
+
     pub static_fields: Vec<Rp<ClassMember>>,
     pub static_vars: Vec<u64>,
+    // whether static_vars[i] holds a reference
+    pub static_ref_map: Vec<bool>,
 
+    // fields of instances, super class fields come first
     pub ins_fields: Vec<Rp<ClassMember>>,
+    // bytes of instance fields, objects take them rounded up to words
+    pub ins_size: usize,
+    // whether word i of instances holds a reference
+    pub ins_ref_map: Vec<bool>,
 
     // runtime loaded symbols
     pub sym_refs: Vec<Rp<SymRef>>,
     pub id: usize,
-    pub initialized: bool,
+    pub init_state: InitState,
 
     // for array type, this is class of element
     pub element_class: Rp<Class>,
@@ -192,7 +409,12 @@ impl Class {
         return Rp::null();
     }
 
-    pub fn lookup_method_in_class(&self, name: &str, desc: &str) -> Rp<ClassMember> {
+    pub fn lookup_method_in_class(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Rp<ClassMember> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
         let mut cur: &Class = &self;
 
         // lookup in class
@@ -211,28 +433,33 @@ impl Class {
         Rp::null()
     }
 
-    // run static block
-    pub fn clinit(&mut self, th: &mut JThread) -> bool {
-        if self.initialized {
-            return false;
-        }
-
-        self.initialized = true;
-
-        let init = self.clinit_method();
+    pub fn is_initialized(&self) -> bool {
+        self.init_state == InitState::Initialized
+    }
 
-        if !init.is_null() {
-            th.push_frame(init);
-        }
+    // super interfaces declaring non-abstract instance methods, JVMS 5.5 step 7
+    // they are initialized before a class implementing them, other interfaces aren't
+    pub fn default_ifaces(&self) -> Vec<Rp<Class>> {
+        let mut r: Vec<Rp<Class>> = Vec::new();
+        let mut stack = self.interfaces.clone();
 
-        // init super class
-        if !self.super_class.is_null() {
-            self.super_class.clinit(th);
+        while let Some(i) = stack.pop() {
+            if r.iter().any(|x| x.id == i.id) {
+                continue;
+            }
+            let defaults = i
+                .methods
+                .iter()
+                .any(|m| !m.access_flags.is_abstract() && !m.access_flags.is_static());
+            if defaults {
+                r.push(i);
+            }
+            stack.extend(i.interfaces.iter());
         }
-        true
+        r
     }
 
-    fn clinit_method(&self) -> Rp<ClassMember> {
+    pub fn clinit_method(&self) -> Rp<ClassMember> {
         self.methods
             .iter()
             .find(|&x| x.name == "<clinit>" && x.desc == "()V")
@@ -240,50 +467,307 @@ impl Class {
             .unwrap_or(Rp::null())
     }
 
-    pub fn lookup_method(&self, name: &str, desc: &str) -> Rp<ClassMember> {
+    // method resolution, JVMS 5.4.3.3
+    // lookup in class and super classes, then in super interfaces
+    pub fn lookup_method(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Rp<ClassMember> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
         let m = self.lookup_method_in_class(name, desc);
         if !m.is_null() {
             return m;
         }
-        // lookup in super interfaces
-        Self::lookup_method_in_ifaces(&self.interfaces, name, desc)
+        self.lookup_max_specific(name, desc)
     }
 
-    // lookup in interfaces and parent interfaces
-    pub fn lookup_iface_method(&self, name: &str, desc: &str) -> Rp<ClassMember> {
+    // interface method resolution, JVMS 5.4.3.4
+    // lookup in interface, public instance methods of Object, then super interfaces
+    pub fn lookup_iface_method(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Rp<ClassMember> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
         for m in self.methods.iter() {
             if m.name == name && m.desc == desc {
                 return m.into();
             }
         }
 
-        // lookup in super interfaces
-        Self::lookup_method_in_ifaces(&self.interfaces, name, desc)
+        // super class of interface is Object
+        if !self.super_class.is_null() {
+            for m in self.super_class.methods.iter() {
+                if m.name == name
+                    && m.desc == desc
+                    && m.access_flags.is_public()
+                    && !m.access_flags.is_static()
+                {
+                    return m.into();
+                }
+            }
+        }
+
+        self.lookup_max_specific(name, desc)
     }
 
-    fn lookup_method_in_ifaces<T: Deref<Target = Class>>(
-        ifaces: &[T],
-        name: &str,
-        desc: &str,
+    // the only non-abstract maximally-specific method, otherwise any of them
+    fn lookup_max_specific(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
     ) -> Rp<ClassMember> {
-        for i in ifaces.iter() {
-            for m in i.methods.iter() {
-                if m.name == name && m.desc == desc {
-                    return m.into();
-                }
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
+        let ms = self.max_specific_methods(name, desc);
+        let concrete: Vec<&Rp<ClassMember>> = ms
+            .iter()
+            .filter(|m| !m.access_flags.is_abstract())
+            .collect();
+
+        if concrete.len() == 1 {
+            return *concrete[0];
+        }
+        ms.first().map(|m| *m).unwrap_or(Rp::null())
+    }
+
+    // all super interfaces of this class, its super classes and super interfaces
+    fn super_ifaces(&self) -> Vec<Rp<Class>> {
+        let mut r: Vec<Rp<Class>> = Vec::new();
+        let mut pending: Vec<Rp<Class>> = Vec::new();
+        let mut cur: &Class = self;
+
+        loop {
+            pending.extend(cur.interfaces.iter());
+            if cur.super_class.is_null() {
+                break;
             }
+            cur = &cur.super_class;
+        }
+
+        while let Some(i) = pending.pop() {
+            if r.iter().any(|x| x.id == i.id) {
+                continue;
+            }
+            r.push(i);
+            pending.extend(i.interfaces.iter());
+        }
+        r
+    }
+
+    // maximally-specific super interface methods, JVMS 5.4.3.3
+    // non-private instance methods not declared in a super interface of another candidate's interface
+    pub fn max_specific_methods(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Vec<Rp<ClassMember>> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
+        let candidates: Vec<Rp<ClassMember>> = self
+            .super_ifaces()
+            .iter()
+            .filter_map(|i| {
+                i.methods.iter().find(|m| {
+                    m.name == name
+                        && m.desc == desc
+                        && !m.access_flags.is_private()
+                        && !m.access_flags.is_static()
+                })
+            })
+            .map(|m| m.into())
+            .collect();
+
+        candidates
+            .iter()
+            .filter(|m| {
+                !candidates
+                    .iter()
+                    .any(|o| o.class.id != m.class.id && o.class.is_sub_iface(&m.class))
+            })
+            .map(|m| *m)
+            .collect()
+    }
 
-            let m = Self::lookup_method_in_ifaces(&i.interfaces, name, desc);
+    // build vtable and itables at link time, super class and interfaces are linked
+    pub fn link_methods(&mut self) {
+        let is_virtual = |m: &&ClassMember| {
+            !m.access_flags.is_static() && !m.access_flags.is_private() && !m.name.starts_with('<')
+        };
 
-            if !m.is_null() {
-                return m;
+        if self.access_flags.is_iface() {
+            self.vtable = self
+                .methods
+                .iter()
+                .filter(is_virtual)
+                .map(|m| m.into())
+                .collect();
+            return;
+        }
+
+        let mut vtable = if self.super_class.is_null() {
+            Vec::new()
+        } else {
+            self.super_class.vtable.clone()
+        };
+
+        // overriding method takes the slot of the overridden one
+        for m in self.methods.iter().filter(is_virtual) {
+            match vtable
+                .iter()
+                .position(|x| x.name == m.name && x.desc == m.desc)
+            {
+                Some(i) => vtable[i] = m.into(),
+                None => vtable.push(m.into()),
             }
         }
+        self.vtable = vtable;
+
+        let itables = self
+            .super_ifaces()
+            .into_iter()
+            .map(|i| {
+                let t = i
+                    .vtable
+                    .iter()
+                    .map(|m| self.select_method(*m).unwrap_or(Rp::null()))
+                    .collect();
+                (i, t)
+            })
+            .collect();
+        self.itables = itables;
+    }
 
-        Rp::null()
+    // index of method in vtable of this class, or in itable of this interface
+    pub fn vtable_index(&self, m: Rp<ClassMember>) -> Option<usize> {
+        self.vtable.iter().position(|x| x.ptr() == m.ptr())
+    }
+
+    // select method of resolved method m by its vtable or itable index
+    pub fn dispatch(
+        &self,
+        m: Rp<ClassMember>,
+        index: Option<usize>,
+    ) -> Result<Rp<ClassMember>, LinkError> {
+        let found = index.and_then(|i| {
+            if m.class.access_flags.is_iface() {
+                self.itables
+                    .iter()
+                    .find(|(c, _)| c.id == m.class.id)
+                    .map(|(_, t)| t[i])
+            } else {
+                self.vtable.get(i).copied()
+            }
+        });
+
+        match found {
+            Some(x) if !x.is_null() && !x.access_flags.is_abstract() => Ok(x),
+            // private methods, or selection error to report
+            _ => self.select_method(m),
+        }
+    }
+
+    // method selection of invokevirtual and invokeinterface on instances of this class, JVMS 5.4.6
+    pub fn select_method(&self, m: Rp<ClassMember>) -> Result<Rp<ClassMember>, LinkError> {
+        if m.access_flags.is_private() {
+            return Ok(m);
+        }
+
+        let mut cur: &Class = self;
+        loop {
+            let found = cur.methods.iter().find(|x| {
+                x.name == m.name
+                    && x.desc == m.desc
+                    && !x.access_flags.is_private()
+                    && !x.access_flags.is_static()
+            });
+
+            if let Some(x) = found {
+                return self.check_abstract(x.into());
+            }
+            if cur.super_class.is_null() {
+                break;
+            }
+            cur = &cur.super_class;
+        }
+
+        self.select_default(&m.name, &m.desc)
     }
 
-    pub fn lookup_field(&self, name: &str, desc: &str) -> Rp<ClassMember> {
+    // method selection of invokespecial, this class is the super class of caller or the referenced class
+    pub fn select_special(&self, m: Rp<ClassMember>) -> Result<Rp<ClassMember>, LinkError> {
+        let found: Rp<ClassMember> = if self.access_flags.is_iface() {
+            // declared in the interface, or a public instance method of Object
+            self.methods
+                .iter()
+                .find(|x| x.name == m.name && x.desc == m.desc)
+                .map(|x| x.into())
+                .unwrap_or_else(|| {
+                    let o = self.super_class.lookup_method_in_class(&m.name, &m.desc);
+                    if !o.is_null() && o.access_flags.is_public() && !o.access_flags.is_static() {
+                        o
+                    } else {
+                        Rp::null()
+                    }
+                })
+        } else {
+            self.lookup_method_in_class(&m.name, &m.desc)
+        };
+
+        if !found.is_null() {
+            return self.check_abstract(found);
+        }
+        self.select_default(&m.name, &m.desc)
+    }
+
+    // the only non-abstract maximally-specific method
+    fn select_default(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Result<Rp<ClassMember>, LinkError> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
+        let concrete: Vec<Rp<ClassMember>> = self
+            .max_specific_methods(name, desc)
+            .into_iter()
+            .filter(|m| !m.access_flags.is_abstract())
+            .collect();
+
+        match concrete.len() {
+            0 => Err(LinkError::new(
+                "java/lang/AbstractMethodError",
+                format!("{}.{}{}", self.name.replace('/', "."), name, desc),
+            )),
+            1 => Ok(concrete[0]),
+            _ => Err(LinkError::new(
+                "java/lang/IncompatibleClassChangeError",
+                format!(
+                    "Conflicting default methods: {}",
+                    concrete
+                        .iter()
+                        .map(|m| format!("{}.{}", m.class.name.replace('/', "."), name))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                ),
+            )),
+        }
+    }
+
+    fn check_abstract(&self, m: Rp<ClassMember>) -> Result<Rp<ClassMember>, LinkError> {
+        if m.access_flags.is_abstract() {
+            return Err(LinkError::new(
+                "java/lang/AbstractMethodError",
+                format!("{}.{}{}", self.name.replace('/', "."), m.name, m.desc),
+            ));
+        }
+        Ok(m)
+    }
+
+    pub fn lookup_field(
+        &self,
+        name: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Rp<ClassMember> {
+        let (name, desc): (Symbol, Symbol) = (name.into(), desc.into());
         // lookup in this class fields
         for f in self.fields.iter() {
             if f.name == name && f.desc == desc {
@@ -315,15 +799,26 @@ impl Class {
         self.static_vars[i]
     }
 
-    pub fn get_instance(&self, obj: &Object, i: usize) -> u64 {
-        obj.get(i)
-    }
-
     pub fn is_assignable(&self, from: &Class) -> bool {
         if self.id == from.id {
             return true;
         }
 
+        if from.dim > 0 {
+            if self.dim == 0 {
+                return self.name == "java/lang/Object"
+                    || self.name == "java/lang/Cloneable"
+                    || self.name == "java/io/Serializable";
+            }
+
+            // primitive arrays are only assignable to the same type
+            let (sc, fc) = (self.component_class(), from.component_class());
+            if sc.is_primitive() || fc.is_primitive() {
+                return false;
+            }
+            return sc.is_assignable(&fc);
+        }
+
         if self.access_flags.is_iface() {
             from.is_impl(self)
         } else {
@@ -331,6 +826,33 @@ impl Class {
         }
     }
 
+    pub fn is_primitive(&self) -> bool {
+        self.dim == 0 && PRIMITIVES.contains(&self.name.as_str())
+    }
+
+    // bytes of an array element or an object field
+    pub fn elem_size(&self) -> usize {
+        if self.dim != 1 || !self.element_class.is_primitive() {
+            return 8;
+        }
+
+        match self.element_class.desc.as_str() {
+            "Z" | "B" => 1,
+            "C" | "S" => 2,
+            "F" | "I" => 4,
+            _ => 8,
+        }
+    }
+
+    // component type of array, [[I -> [I, [Ljava/lang/String; -> java/lang/String
+    pub fn component_class(&self) -> Rp<Class> {
+        if self.dim == 1 {
+            return self.element_class;
+        }
+        let mut loader = self.heap.loader;
+        loader.load(&self.name[1..])
+    }
+
     pub fn is_sub_class(&self, other: &Class) -> bool {
         let mut sup = self.super_class;
 
@@ -347,7 +869,7 @@ impl Class {
         let mut cur: &Class = self;
 
         loop {
-            for i in self.interfaces.iter() {
+            for i in cur.interfaces.iter() {
                 if i.id == iface.id || i.is_sub_iface(iface) {
                     return true;
                 }
@@ -396,33 +918,120 @@ impl Class {
     }
 
     pub fn new_obj_size(class: Rp<Class>, size: usize) -> Rp<Object> {
-        let v: Rp<u64> = Rp::new_a(size);
-        let obj = Object {
-            class: class,
-            size: size,
-            data: v.ptr(),
-        };
-
-        Rp::new(obj)
+        let mut heap = class.heap;
+        heap.alloc(class, size, 8)
     }
 
     pub fn new_obj(class: Rp<Class>) -> Rp<Object> {
-        Self::new_obj_size(class, class.ins_fields.len())
+        Self::new_obj_size(class, class.ins_words())
+    }
+
+    // words of instance data
+    pub fn ins_words(&self) -> usize {
+        (self.ins_size + 7) / 8
     }
 }
 
 #[derive(Default)]
 pub struct ClassMember {
     pub access_flags: AccessFlags,
-    pub name: String,
-    pub desc: String,
+    pub name: Symbol,
+    pub desc: Symbol,
     pub max_stack: u16,
     pub max_locals: u16,
     pub code: Vec<u8>,
+    pub exceptions: Vec<Exception>,
+    pub line_numbers: Vec<LineNumber>,
     pub cons_i: usize,
+    // field index in static_fields or ins_fields
     pub id: usize,
+    // byte offset of instance field in object data
+    pub offset: usize,
     pub class: Rp<Class>,
     pub m_desc: MethodDescriptor,
+    // reference maps by pc, computed on first use
+    pub ref_maps: Vec<Option<RefMap>>,
+    // inline caches of virtual call sites by pc, created on first call
+    pub inline_caches: Vec<Option<Box<InlineCache>>>,
+    // code decoded on first invocation, and index of the instruction at each bytecode pc
+    pub insns: Vec<Insn>,
+    pub insn_index: Vec<u32>,
+    // hotness counters and code of the baseline compiler
+    pub invocations: u32,
+    pub backedges: u32,
+    pub compiled: Option<Box<Code>>,
+    // native bound at first call, and registry epoch it was bound at
+    pub native: Rp<NativeMethodW>,
+    pub native_epoch: u32,
+    // signature polymorphic method, set when its class is linked
+    pub polymorphic: bool,
+}
+
+impl ClassMember {
+    // first byte of field descriptor, L for arrays too
+    pub fn field_type(&self) -> u8 {
+        match self.desc.as_bytes()[0] {
+            b'[' => b'L',
+            t => t,
+        }
+    }
+
+    // bytes of field in object data
+    pub fn field_size(&self) -> usize {
+        match self.field_type() {
+            b'Z' | b'B' => 1,
+            b'C' | b'S' => 2,
+            b'I' | b'F' => 4,
+            _ => 8,
+        }
+    }
+
+    // source line of the instruction at pc, -1 if unknown, -2 for native methods
+    pub fn line_number(&self, pc: u32) -> i32 {
+        if self.access_flags.is_native() {
+            return -2;
+        }
+
+        self.line_numbers
+            .iter()
+            .filter(|l| l.start_pc as u32 <= pc)
+            .max_by_key(|l| l.start_pc)
+            .map(|l| l.line_number as i32)
+            .unwrap_or(-1)
+    }
+
+    // which slots of a frame of this method hold references before the instruction at pc
+    // None if pc is not reachable
+    pub fn ref_map(&mut self, pc: u32) -> Option<&RefMap> {
+        if self.ref_maps.is_empty() {
+            self.ref_maps = refmap::compute(self);
+        }
+        self.ref_maps.get(pc as usize).and_then(|x| x.as_ref())
+    }
+
+    // decoded instruction starting at bytecode pc
+    #[inline]
+    pub fn insn(&mut self, pc: u32) -> &mut Insn {
+        if self.insns.is_empty() {
+            let (insns, index) = decode::decode(&self.code);
+            self.insns = insns;
+            self.insn_index = index;
+        }
+        &mut self.insns[self.insn_index[pc as usize] as usize]
+    }
+
+    // inline cache of the call site at pc
+    pub fn inline_cache(&mut self, pc: u32) -> &mut InlineCache {
+        if self.inline_caches.is_empty() {
+            self.inline_caches = (0..self.code.len()).map(|_| None).collect();
+        }
+        self.inline_caches[pc as usize].get_or_insert_with(Default::default)
+    }
+
+    // invokeExact, invoke, invokeBasic and linkTo* of MethodHandle
+    pub fn is_signature_polymorphic(&self) -> bool {
+        self.polymorphic
+    }
 }
 
 impl Debug for ClassMember {
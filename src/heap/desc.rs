@@ -10,7 +10,7 @@ pub struct MethodDescriptor {
     pub arg_slots: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JType {
     FI(char),
     DJ(char),
@@ -28,6 +28,23 @@ impl JType {
             Self::V => "V".to_string(),
         }
     }
+
+    // field descriptor, I -> I, java/lang/String -> Ljava/lang/String;
+    pub fn descriptor(&self) -> String {
+        match self {
+            Self::A(x) if x.starts_with('[') => x.to_string(),
+            Self::A(x) => format!("L{};", x),
+            _ => self.class(),
+        }
+    }
+
+    pub fn slots(&self) -> u16 {
+        match self {
+            Self::FI(_) | Self::A(_) => 1,
+            Self::DJ(_) => 2,
+            Self::V => 0,
+        }
+    }
 }
 
 impl Default for JType {
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+
+use crate::heap::arena::Arena;
+use crate::heap::class::{Class, Header, Object};
+use crate::heap::misc::Heap;
+use crate::runtime::vm::JThread;
+use rp::Rp;
+
+// default max heap size
+pub const DEFAULT_LIMIT: usize = 256 * 1024 * 1024;
+// collect at safepoint once this many bytes are in use
+const MIN_THRESHOLD: usize = 8 * 1024 * 1024;
+
+// mark-and-sweep collector over all objects allocated by heap
+pub struct Collector {
+    // memory objects are allocated from
+    arena: Arena,
+    // addresses of all allocated objects
+    objects: HashSet<usize>,
+    // bytes of allocated objects
+    used: usize,
+    // max heap size
+    limit: usize,
+    threshold: usize,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Collector {
+            arena: Arena::default(),
+            objects: HashSet::new(),
+            used: 0,
+            limit: DEFAULT_LIMIT,
+            threshold: MIN_THRESHOLD,
+        }
+    }
+}
+
+fn object_bytes(o: &Object) -> usize {
+    Object::bytes(o.size, o.class.elem_size())
+}
+
+impl Collector {
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    // bytes of memory reserved for objects
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.threshold = self.threshold.min(limit);
+    }
+
+    // number of live and not yet collected objects
+    pub fn objects(&self) -> usize {
+        self.objects.len()
+    }
+
+    // whether a safepoint should collect
+    pub fn should_collect(&self) -> bool {
+        self.used > self.threshold
+    }
+
+    pub fn fits(&self, bytes: usize) -> bool {
+        self.used.saturating_add(bytes) <= self.limit
+    }
+}
+
+impl Heap {
+    // allocate an object with n fields or elements of elem_size bytes, data follows the object
+    // it's freed by sweep once unreachable
+    pub fn alloc(&mut self, class: Rp<Class>, n: usize, elem_size: usize) -> Rp<Object> {
+        let bytes = Object::bytes(n, elem_size);
+        let p = self.gc.arena.alloc(bytes);
+        let o = Object {
+            header: Header::default(),
+            class,
+            size: n,
+            data: p as usize + std::mem::size_of::<Object>(),
+        };
+        unsafe { std::ptr::write(p as *mut Object, o) };
+
+        self.gc.used += bytes;
+        self.gc.objects.insert(p as usize);
+        (p as usize).into()
+    }
+
+    // bytes of live and not yet collected objects
+    pub fn used_bytes(&self) -> usize {
+        self.gc.used
+    }
+
+    // bytes of memory reserved for objects
+    pub fn capacity(&self) -> usize {
+        self.gc.capacity()
+    }
+
+    // collect unreachable objects, must be called between instructions
+    pub fn collect(&mut self, threads: &[Rp<JThread>]) {
+        let mut grey: Vec<usize> = Vec::new();
+
+        for c in self.loader.classes() {
+            grey.push(c.j_class.ptr());
+            for (i, _) in c.static_ref_map.iter().enumerate().filter(|x| *x.1) {
+                grey.push(c.static_vars[i] as usize);
+            }
+            for r in c.sym_refs.iter().filter(|r| !r.is_null()) {
+                grey.push(r.object.ptr());
+            }
+        }
+
+        grey.extend(self.string_pool.values().map(|x| x.ptr()));
+        grey.extend(self.pinned.iter().map(|x| x.ptr()));
+
+        for th in threads {
+            grey.push(th.exception.ptr());
+            grey.push(th.j_thread.ptr());
+
+            for f in th.stack().frames() {
+                grey.push(f.monitor.ptr());
+                match f.refs() {
+                    Some(refs) => grey.extend(refs.iter().map(|x| x.ptr())),
+                    // no map for the pc, any slot holding an object address keeps it alive
+                    None => grey.extend(
+                        f.local_vars()
+                            .iter()
+                            .chain(f.operands().iter())
+                            .map(|&x| x as usize)
+                            .filter(|x| self.gc.objects.contains(x)),
+                    ),
+                }
+            }
+        }
+
+        while let Some(p) = grey.pop() {
+            if p == 0 || !self.gc.objects.contains(&p) {
+                continue;
+            }
+            let mut o: Rp<Object> = p.into();
+            if o.header.is_marked() {
+                continue;
+            }
+            o.header.set_marked(true);
+            grey.extend(o.refs().iter().map(|x| x.ptr()));
+        }
+
+        let mut freed = 0;
+        let mut monitors: Vec<usize> = Vec::new();
+        let arena = &mut self.gc.arena;
+        self.gc.objects.retain(|&p| {
+            let mut o: Rp<Object> = p.into();
+            if o.header.is_marked() {
+                o.header.set_marked(false);
+                return true;
+            }
+            if o.header.lock.is_inflated() {
+                monitors.push(o.header.lock.monitor());
+            }
+            let bytes = object_bytes(&o);
+            freed += bytes;
+            arena.free(o.raw() as *mut u8, bytes);
+            false
+        });
+        arena.trim();
+
+        for i in monitors {
+            self.monitors.release(i);
+        }
+        self.gc.used -= freed;
+        self.gc.threshold = (self.gc.used * 2).max(MIN_THRESHOLD).min(self.gc.limit);
+    }
+}
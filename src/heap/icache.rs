@@ -0,0 +1,111 @@
+use crate::heap::class::ClassMember;
+use crate::heap::misc::Heap;
+use rp::Rp;
+
+// receiver classes a call site remembers before it turns megamorphic
+pub const POLY_LIMIT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheState {
+    Empty,
+    Monomorphic,
+    Polymorphic,
+    // too many receiver classes, dispatch by vtable only
+    Megamorphic,
+}
+
+// inline cache of an invokevirtual or invokeinterface call site
+#[derive(Default)]
+pub struct InlineCache {
+    // class loader epoch the entries were selected in
+    epoch: u32,
+    // receiver class id and selected method
+    entries: Vec<(usize, Rp<ClassMember>)>,
+    megamorphic: bool,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl InlineCache {
+    // method selected for receiver class, None on a miss
+    pub fn lookup(&mut self, class_id: usize, epoch: u32) -> Option<Rp<ClassMember>> {
+        // classes redefined since the entries were filled
+        if self.epoch != epoch {
+            self.entries.clear();
+            self.megamorphic = false;
+            self.epoch = epoch;
+        }
+
+        match self.entries.iter().find(|e| e.0 == class_id) {
+            Some(e) => {
+                self.hits += 1;
+                Some(e.1)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    // remember method selected on a miss
+    pub fn update(&mut self, class_id: usize, m: Rp<ClassMember>) {
+        if self.megamorphic {
+            return;
+        }
+
+        if self.entries.len() == POLY_LIMIT {
+            self.entries.clear();
+            self.megamorphic = true;
+            return;
+        }
+        self.entries.push((class_id, m));
+    }
+
+    pub fn state(&self) -> CacheState {
+        match self.entries.len() {
+            _ if self.megamorphic => CacheState::Megamorphic,
+            0 => CacheState::Empty,
+            1 => CacheState::Monomorphic,
+            _ => CacheState::Polymorphic,
+        }
+    }
+}
+
+// counters of a call site
+#[derive(Debug, Clone)]
+pub struct CallSiteStats {
+    pub class: String,
+    pub method: String,
+    pub desc: String,
+    pub pc: u32,
+    pub state: CacheState,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl Heap {
+    // counters of every call site executed so far
+    pub fn inline_cache_stats(&self) -> Vec<CallSiteStats> {
+        let mut r = Vec::new();
+
+        for c in self.loader.classes().iter() {
+            for m in c.methods.iter() {
+                for (pc, ic) in m.inline_caches.iter().enumerate() {
+                    if let Some(ic) = ic {
+                        r.push(CallSiteStats {
+                            class: c.name.to_string(),
+                            method: m.name.to_string(),
+                            desc: m.desc.to_string(),
+                            pc: pc as u32,
+                            state: ic.state(),
+                            hits: ic.hits,
+                            misses: ic.misses,
+                        });
+                    }
+                }
+            }
+        }
+        r
+    }
+}
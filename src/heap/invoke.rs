@@ -0,0 +1,128 @@
+use crate::heap::class::{Class, ClassMember, Object};
+use crate::heap::desc::DescriptorParser;
+use crate::heap::misc::{ref_kind, Heap};
+use rp::Rp;
+
+// flags of java.lang.invoke.MemberName
+pub mod mn_flags {
+    pub const IS_METHOD: u32 = 0x00010000;
+    pub const IS_CONSTRUCTOR: u32 = 0x00020000;
+    pub const IS_FIELD: u32 = 0x00040000;
+    pub const REFERENCE_KIND_SHIFT: u32 = 24;
+    pub const REFERENCE_KIND_MASK: u32 = 0x0f;
+}
+
+// descriptor of a MethodType object
+pub fn method_type_desc(mt: &Object) -> String {
+    let rtype: Rp<Object> = (mt.get_field("rtype") as usize).into();
+    let ptypes: Rp<Object> = (mt.get_field("ptypes") as usize).into();
+    let mut s = "(".to_string();
+
+    for &p in ptypes.fields().iter() {
+        let p: Rp<Object> = (p as usize).into();
+        s.push_str(&p.extra_class().desc);
+    }
+
+    s.push(')');
+    s.push_str(&rtype.extra_class().desc);
+    s
+}
+
+impl Heap {
+    // load class of a field descriptor
+    pub fn desc_class(&mut self, desc: &str) -> Rp<Class> {
+        if desc.starts_with('L') {
+            self.loader.load(&desc[1..desc.len() - 1])
+        } else {
+            self.loader.load(desc)
+        }
+    }
+
+    // create MethodType from method descriptor
+    pub fn new_method_type(&mut self, desc: &str) -> Rp<Object> {
+        let d = DescriptorParser::new(desc.as_bytes()).parse_method();
+        let rtype = self.loader.load(&d.ret.class()).j_class;
+        let mut ptypes = self.new_array("java/lang/Class", d.params.len());
+
+        for (i, p) in d.params.iter().enumerate() {
+            let c = self.loader.load(&p.class());
+            ptypes.set(i, c.j_class.ptr() as u64);
+        }
+
+        self.make_method_type(rtype, ptypes)
+    }
+
+    // create MethodType without interning
+    pub fn make_method_type(&mut self, rtype: Rp<Object>, ptypes: Rp<Object>) -> Rp<Object> {
+        let c = self.loader.load("java/lang/invoke/MethodType");
+        let mut mt = Class::new_obj(c);
+        mt.set_field_ref("rtype", rtype);
+        mt.set_field_ref("ptypes", ptypes);
+        mt
+    }
+
+    // resolve field or method referenced by a method handle
+    pub fn link_handle_member(
+        &mut self,
+        kind: u8,
+        class: Rp<Class>,
+        name: &str,
+        desc: &str,
+    ) -> Rp<ClassMember> {
+        match kind {
+            ref_kind::GET_FIELD..=ref_kind::PUT_STATIC => class.lookup_field(name, desc),
+            _ if class.access_flags.is_iface() => class.lookup_iface_method(name, desc),
+            _ => class.lookup_method(name, desc),
+        }
+    }
+
+    // create a MemberName linked to member
+    pub fn new_member_name(&mut self, kind: u8, m: Rp<ClassMember>) -> Rp<Object> {
+        use mn_flags::*;
+        let (t, f) = match kind {
+            ref_kind::GET_FIELD..=ref_kind::PUT_STATIC => {
+                (self.desc_class(&m.desc).j_class, IS_FIELD)
+            }
+            ref_kind::NEW_INVOKE_SPECIAL => (self.new_method_type(&m.desc), IS_CONSTRUCTOR),
+            _ => (self.new_method_type(&m.desc), IS_METHOD),
+        };
+
+        let c = self.loader.load("java/lang/invoke/MemberName");
+        let mut mn = Class::new_obj(c);
+        let flags = m.access_flags.0 as u32 | f | ((kind as u32) << REFERENCE_KIND_SHIFT);
+        mn.set_field_ref("clazz", m.class.j_class);
+        mn.set_field_ref("name", self.new_jstr(&m.name));
+        mn.set_field_ref("type", t);
+        mn.set_field("flags", flags as u64);
+        mn.set_field("vmtarget", m.ptr() as u64);
+        mn
+    }
+
+    // create a direct method handle of kind to member, refc is the type of receiver
+    pub fn new_method_handle(
+        &mut self,
+        kind: u8,
+        refc: Rp<Class>,
+        m: Rp<ClassMember>,
+    ) -> Rp<Object> {
+        let recv = &refc.desc;
+        let t = match kind {
+            ref_kind::GET_FIELD => format!("({}){}", recv, m.desc),
+            ref_kind::PUT_FIELD => format!("({}{})V", recv, m.desc),
+            ref_kind::GET_STATIC => format!("(){}", m.desc),
+            ref_kind::PUT_STATIC => format!("({})V", m.desc),
+            ref_kind::INVOKE_STATIC => m.desc.to_string(),
+            // (args)V -> (args)C
+            ref_kind::NEW_INVOKE_SPECIAL => format!("{}{}", &m.desc[..m.desc.len() - 1], recv),
+            _ => format!("({}{}", recv, &m.desc[1..]),
+        };
+
+        let mt = self.new_method_type(&t);
+        let mn = self.new_member_name(kind, m);
+        let c = self.loader.load("java/lang/invoke/DirectMethodHandle");
+        let mut mh = Class::new_obj(c);
+        mh.set_field_ref("type", mt);
+        mh.set_field_ref("member", mn);
+        mh
+    }
+}
@@ -0,0 +1,418 @@
+use crate::heap::class::{Class, ClassMember};
+use crate::heap::desc::{DescriptorParser, JType, MethodDescriptor};
+use crate::heap::misc::{flags, ref_kind, AccessFlags, Heap};
+use cp::{ConstantInfo, ConstantPool, Symbol};
+use err::StringErr;
+use rp::Rp;
+
+// flags of LambdaMetafactory.altMetafactory
+const FLAG_MARKERS: u32 = 1 << 1;
+const FLAG_BRIDGES: u32 = 1 << 2;
+
+const FACTORY: &str = "get$Lambda";
+
+// assembler for constant pool and code of synthesized classes
+struct Asm {
+    infos: Vec<ConstantInfo>,
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn new() -> Self {
+        Asm {
+            infos: vec![ConstantInfo::Blank],
+            code: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, info: ConstantInfo) -> u16 {
+        self.infos.push(info);
+        (self.infos.len() - 1) as u16
+    }
+
+    fn utf8(&mut self, s: &str) -> u16 {
+        let i = self
+            .infos
+            .iter()
+            .position(|x| matches!(x, ConstantInfo::Utf8(u) if u == s));
+
+        match i {
+            Some(i) => i as u16,
+            None => self.add(ConstantInfo::Utf8(Symbol::intern(s))),
+        }
+    }
+
+    fn class(&mut self, name: &str) -> u16 {
+        let name_i = self.utf8(name);
+        self.add(ConstantInfo::Class { name_i })
+    }
+
+    fn name_type(&mut self, name: &str, desc: &str) -> u16 {
+        let name_i = self.utf8(name);
+        let desc_i = self.utf8(desc);
+        self.add(ConstantInfo::NameAndType { name_i, desc_i })
+    }
+
+    fn field_ref(&mut self, class: &str, name: &str, desc: &str) -> u16 {
+        let class_i = self.class(class);
+        let name_type_i = self.name_type(name, desc);
+        self.add(ConstantInfo::FieldRef {
+            class_i,
+            name_type_i,
+        })
+    }
+
+    fn method_ref(&mut self, class: &str, name: &str, desc: &str, iface: bool) -> u16 {
+        let class_i = self.class(class);
+        let name_type_i = self.name_type(name, desc);
+
+        if iface {
+            self.add(ConstantInfo::IFaceMethodRef {
+                class_i,
+                name_type_i,
+            })
+        } else {
+            self.add(ConstantInfo::MethodRef {
+                class_i,
+                name_type_i,
+            })
+        }
+    }
+
+    fn op(&mut self, op: u8) {
+        self.code.push(op);
+    }
+
+    fn op_u16(&mut self, op: u8, i: u16) {
+        self.code.push(op);
+        self.code.extend_from_slice(&i.to_be_bytes());
+    }
+
+    // xload from local variable i
+    fn load(&mut self, t: &JType, i: u16) {
+        let op = match t {
+            JType::FI('F') => 0x17,
+            JType::FI(_) => 0x15,
+            JType::DJ('J') => 0x16,
+            JType::DJ(_) => 0x18,
+            _ => 0x19,
+        };
+
+        if i > 0xff {
+            // wide
+            self.op(0xc4);
+            self.op_u16(op, i);
+        } else {
+            self.op(op);
+            self.op(i as u8);
+        }
+    }
+
+    fn ret(&mut self, t: &JType) {
+        self.op(match t {
+            JType::V => 0xb1,
+            JType::FI('F') => 0xae,
+            JType::FI(_) => 0xac,
+            JType::DJ('J') => 0xad,
+            JType::DJ(_) => 0xaf,
+            JType::A(_) => 0xb0,
+        });
+    }
+
+    fn take_code(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.code)
+    }
+
+    // adapt value on top of stack from type `from` to type `to`
+    fn convert(&mut self, from: &JType, to: &JType) -> Result<(), StringErr> {
+        match (primitive(from), primitive(to)) {
+            (None, None) => {
+                if let JType::A(t) = to {
+                    if t != "java/lang/Object" {
+                        // checkcast
+                        let c = self.class(t);
+                        self.op_u16(0xc0, c);
+                    }
+                }
+            }
+            // unbox
+            (None, Some(c)) => {
+                let (b, m) = box_of(c);
+                let ci = self.class(b);
+                self.op_u16(0xc0, ci);
+                let mi = self.method_ref(b, m, &format!("(){}", c), false);
+                self.op_u16(0xb6, mi);
+            }
+            // box
+            (Some(c), None) => {
+                let (b, _) = box_of(c);
+                let mi = self.method_ref(b, "valueOf", &format!("({})L{};", c, b), false);
+                self.op_u16(0xb8, mi);
+            }
+            // widening primitive conversion
+            (Some(a), Some(b)) => {
+                let op = match (a, b) {
+                    _ if a == b => None,
+                    ('B' | 'S' | 'C', 'I') | ('B', 'S') => None,
+                    ('B' | 'S' | 'C' | 'I', 'J') => Some(0x85),
+                    ('B' | 'S' | 'C' | 'I', 'F') => Some(0x86),
+                    ('B' | 'S' | 'C' | 'I', 'D') => Some(0x87),
+                    ('J', 'F') => Some(0x89),
+                    ('J', 'D') => Some(0x8a),
+                    ('F', 'D') => Some(0x8d),
+                    _ => return err!("cannot convert {} to {}", a, b),
+                };
+
+                if let Some(op) = op {
+                    self.op(op);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn primitive(t: &JType) -> Option<char> {
+    match t {
+        JType::FI(c) | JType::DJ(c) => Some(*c),
+        _ => None,
+    }
+}
+
+// box class and unbox method of primitive type
+pub fn box_of(c: char) -> (&'static str, &'static str) {
+    match c {
+        'Z' => ("java/lang/Boolean", "booleanValue"),
+        'B' => ("java/lang/Byte", "byteValue"),
+        'C' => ("java/lang/Character", "charValue"),
+        'S' => ("java/lang/Short", "shortValue"),
+        'I' => ("java/lang/Integer", "intValue"),
+        'J' => ("java/lang/Long", "longValue"),
+        'F' => ("java/lang/Float", "floatValue"),
+        _ => ("java/lang/Double", "doubleValue"),
+    }
+}
+
+fn parse(desc: &str) -> MethodDescriptor {
+    DescriptorParser::new(desc.as_bytes()).parse_method()
+}
+
+fn member(
+    name: &str,
+    desc: &str,
+    access: u16,
+    code: Vec<u8>,
+    stack: u16,
+    locals: u16,
+) -> ClassMember {
+    let mut m = ClassMember::default();
+    m.name = name.into();
+    m.desc = desc.into();
+    m.access_flags = AccessFlags(access);
+    m.code = code;
+    m.max_stack = stack;
+    m.max_locals = locals;
+    m
+}
+
+// spin a class implementing the functional interface of invokedynamic call site i,
+// instead of running LambdaMetafactory in java.
+// returns the static factory method which captures call site arguments into a new lambda object
+pub fn spin_lambda(
+    heap: &mut Heap,
+    caller: &Class,
+    i: usize,
+    name: &str,
+) -> Result<Rp<ClassMember>, StringErr> {
+    let cp = &caller.cp;
+    let (boot_i, sam_name, indy_desc) = cp.invoke_dynamic(i);
+    let boot = &caller.bootstrap_methods[boot_i as usize];
+    let (_, boot_ref) = cp.method_handle(boot.method_ref as usize);
+    let (boot_class, boot_name, _) = cp.member_ref(boot_ref as usize);
+
+    if boot_class != "java/lang/invoke/LambdaMetafactory"
+        || (boot_name != "metafactory" && boot_name != "altMetafactory")
+        || boot.args.len() < 3
+    {
+        return err!("unsupported bootstrap method {}.{}", boot_class, boot_name);
+    }
+
+    let int_arg = |j: usize| match cp.constant(boot.args[j] as usize) {
+        cp::Constant::Primitive(v, _) => v as u32,
+        _ => 0,
+    };
+
+    let (kind, impl_i) = cp.method_handle(boot.args[1] as usize);
+    let (impl_class, impl_name, impl_desc) = cp.member_ref(impl_i as usize);
+
+    let indy = parse(indy_desc);
+    let mut ifaces = match &indy.ret {
+        JType::A(x) => vec![Symbol::from(x)],
+        _ => return err!("invalid call site descriptor {}", indy_desc),
+    };
+    let mut descs = vec![cp.method_type(boot.args[0] as usize).to_string()];
+
+    if boot_name == "altMetafactory" {
+        let flags = int_arg(3);
+        let mut j = 4;
+
+        if flags & FLAG_MARKERS != 0 {
+            let n = int_arg(j) as usize;
+            for k in 0..n {
+                ifaces.push(cp.class(boot.args[j + 1 + k] as usize).into());
+            }
+            j += n + 1;
+        }
+
+        if flags & FLAG_BRIDGES != 0 {
+            let n = int_arg(j) as usize;
+            for k in 0..n {
+                descs.push(cp.method_type(boot.args[j + 1 + k] as usize).to_string());
+            }
+        }
+    }
+
+    let mut asm = Asm::new();
+    let mut fields: Vec<ClassMember> = Vec::new();
+    let mut methods: Vec<ClassMember> = Vec::new();
+
+    // factory: new lambda object and store captured arguments into fields
+    let this_i = asm.class(name);
+    asm.op_u16(0xbb, this_i);
+    let mut local = 0;
+
+    for (k, t) in indy.params.iter().enumerate() {
+        let f_name = format!("arg${}", k + 1);
+        let f_desc = t.descriptor();
+
+        // dup, load, putfield
+        asm.op(0x59);
+        asm.load(t, local);
+        local += t.slots();
+        let f = asm.field_ref(name, &f_name, &f_desc);
+        asm.op_u16(0xb5, f);
+
+        fields.push(member(
+            &f_name,
+            &f_desc,
+            flags::ACC_PRIVATE | flags::ACC_FINAL,
+            Vec::new(),
+            0,
+            0,
+        ));
+    }
+    asm.op(0xb0);
+
+    let code = asm.take_code();
+    methods.push(member(
+        FACTORY,
+        indy_desc,
+        flags::ACC_PUBLIC | flags::ACC_STATIC,
+        code,
+        4,
+        indy.arg_slots,
+    ));
+
+    // parameters of implementation method, including receiver
+    let impl_d = parse(impl_desc);
+    let mut impl_params: Vec<JType> = Vec::new();
+    let impl_ret = match kind {
+        ref_kind::INVOKE_VIRTUAL | ref_kind::INVOKE_SPECIAL | ref_kind::INVOKE_INTERFACE => {
+            impl_params.push(JType::A(impl_class.to_string()));
+            impl_d.ret.clone()
+        }
+        ref_kind::INVOKE_STATIC => impl_d.ret.clone(),
+        ref_kind::NEW_INVOKE_SPECIAL => JType::A(impl_class.to_string()),
+        _ => return err!("unsupported method handle kind {}", kind),
+    };
+    impl_params.extend(impl_d.params.iter().cloned());
+    let impl_slots: u16 = impl_params.iter().map(|x| x.slots()).sum();
+
+    // forward interface method and its bridges to implementation method
+    for desc in descs.iter() {
+        let sam = parse(desc);
+
+        if sam.params.len() + indy.params.len() != impl_params.len() {
+            return err!(
+                "{}.{}{} is not applicable to {}{}",
+                impl_class,
+                impl_name,
+                impl_desc,
+                sam_name,
+                desc
+            );
+        }
+
+        if kind == ref_kind::NEW_INVOKE_SPECIAL {
+            let c = asm.class(impl_class);
+            asm.op_u16(0xbb, c);
+            asm.op(0x59);
+        }
+
+        for (k, t) in indy.params.iter().enumerate() {
+            // aload_0, getfield
+            asm.op(0x2a);
+            let f = asm.field_ref(name, &format!("arg${}", k + 1), &t.descriptor());
+            asm.op_u16(0xb4, f);
+            asm.convert(t, &impl_params[k])?;
+        }
+
+        let mut local = 1;
+        for (k, t) in sam.params.iter().enumerate() {
+            asm.load(t, local);
+            local += t.slots();
+            asm.convert(t, &impl_params[indy.params.len() + k])?;
+        }
+
+        let iface = kind == ref_kind::INVOKE_INTERFACE;
+        let m = asm.method_ref(impl_class, impl_name, impl_desc, iface);
+        match kind {
+            ref_kind::INVOKE_STATIC => asm.op_u16(0xb8, m),
+            ref_kind::INVOKE_VIRTUAL => asm.op_u16(0xb6, m),
+            ref_kind::INVOKE_INTERFACE => {
+                asm.op_u16(0xb9, m);
+                asm.op(impl_slots as u8);
+                asm.op(0);
+            }
+            _ => asm.op_u16(0xb7, m),
+        }
+
+        match (&sam.ret, &impl_ret) {
+            (JType::V, r) => {
+                // discard result, pop or pop2
+                match r.slots() {
+                    1 => asm.op(0x57),
+                    2 => asm.op(0x58),
+                    _ => {}
+                }
+            }
+            (_, JType::V) => return err!("{}.{} returns void", impl_class, impl_name),
+            (r, i) => asm.convert(i, r)?,
+        }
+        asm.ret(&sam.ret);
+
+        let code = asm.take_code();
+        methods.push(member(
+            sam_name,
+            desc,
+            flags::ACC_PUBLIC,
+            code,
+            impl_slots + 4,
+            sam.arg_slots + 1,
+        ));
+    }
+
+    let mut c = Class::default();
+    c.access_flags = AccessFlags(flags::ACC_FINAL | flags::ACC_SUPER | flags::ACC_SYNTHETIC);
+    c.name = name.into();
+    c.desc = format!("L{};", name).into();
+    c.super_name = "java/lang/Object".into();
+    c.iface_names = ifaces;
+    c.fields = fields;
+    c.methods = methods;
+    c.cp = ConstantPool::new(asm.infos);
+    c.sym_refs = vec![Rp::null(); c.cp.len()];
+
+    let c = heap.loader.define_class(name, c);
+    Ok(c.lookup_method_in_class(FACTORY, indy_desc))
+}
@@ -1,7 +1,7 @@
-use crate::heap::class::Class;
+use crate::heap::class::{Class, InitState};
 use crate::heap::desc::DescriptorParser;
 use cp::ClassFile;
-use cp::Entry;
+use cp::{Entry, Symbol};
 use err::StringErr;
 use rp::Rp;
 use std::collections::BTreeMap;
@@ -10,13 +10,71 @@ use super::class::ClassMember;
 use super::misc::{flags, AccessFlags, PRIMITIVES, PRIMITIVE_DESC, PRIMITIVE_N};
 use crate::heap::misc::Heap;
 
+// java methods whose bytecode is replaced by natives
+static INTRINSICS: [(&str, &str, &str); 10] = [
+    (
+        "java/lang/invoke/MethodHandles",
+        "lookup",
+        "()Ljava/lang/invoke/MethodHandles$Lookup;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findVirtual",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findStatic",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findSpecial",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findConstructor",
+        "(Ljava/lang/Class;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findGetter",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findSetter",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findStaticGetter",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodHandles$Lookup",
+        "findStaticSetter",
+        "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    ),
+    (
+        "java/lang/invoke/MethodType",
+        "makeImpl",
+        "(Ljava/lang/Class;[Ljava/lang/Class;Z)Ljava/lang/invoke/MethodType;",
+    ),
+];
+
 pub struct ClassLoader {
     entry: Box<dyn Entry>,
-    loaded: BTreeMap<String, Rp<Class>>,
+    loaded: BTreeMap<Symbol, Rp<Class>>,
     classes: Vec<Rp<Class>>,
     jclass: Rp<Class>,
     jstring: Rp<Class>,
     heap: Rp<Heap>,
+    // bumped when a loaded class is redefined, invalidates inline caches
+    pub epoch: u32,
+    // print classes defined from class files, -verbose:class
+    pub verbose: bool,
 }
 
 impl ClassLoader {
@@ -26,10 +84,10 @@ impl ClassLoader {
         self.classes.push(Rp::new(class));
         let p = self.classes[class_id];
         p.get_mut().id = class_id;
-        self.loaded.insert(p.name.to_string(), p);
+        self.loaded.insert(p.name.into(), p);
 
         if !alias.is_empty() {
-            self.loaded.insert(alias.to_string(), p);
+            self.loaded.insert(alias.into(), p);
         }
 
         self.assign_j_class(p);
@@ -44,9 +102,10 @@ impl ClassLoader {
 
         // create a class object of size field + 1
         // we use the last field to store class pointer
-        let mut o = Class::new_obj_size(self.jclass, self.jclass.ins_fields.len() + 1);
+        let n = self.jclass.ins_words();
+        let mut o = Class::new_obj_size(self.jclass, n + 1);
 
-        o.set(self.jclass.ins_fields.len(), c.ptr());
+        o.set(n, c.ptr());
         c.get_mut().j_class = o;
     }
 
@@ -60,6 +119,8 @@ impl ClassLoader {
             jclass: Rp::null(),
             jstring: Rp::null(),
             heap,
+            epoch: 0,
+            verbose: false,
         });
 
         heap.get_mut().loader = cl;
@@ -86,15 +147,29 @@ impl ClassLoader {
             let mut c = Class::default();
             c.heap = self.heap;
             c.access_flags = AccessFlags(flags::ACC_PUBLIC);
-            c.name = PRIMITIVES[i].to_string();
-            c.desc = PRIMITIVE_DESC[i].to_string();
-            c.initialized = true;
+            c.name = PRIMITIVES[i].into();
+            c.desc = PRIMITIVE_DESC[i].into();
+            c.init_state = InitState::Initialized;
             self.insert(c, PRIMITIVE_DESC[i]);
         }
+
+        // void.class
+        let mut c = Class::default();
+        c.heap = self.heap;
+        c.access_flags = AccessFlags(flags::ACC_PUBLIC);
+        c.name = "void".into();
+        c.desc = "V".into();
+        c.init_state = InitState::Initialized;
+        self.insert(c, "V");
+    }
+
+    // all loaded classes
+    pub fn classes(&self) -> &[Rp<Class>] {
+        &self.classes
     }
 
     pub fn load(&mut self, name: &str) -> Rp<Class> {
-        match self.loaded.get(name) {
+        match self.loaded.get(&Symbol::intern(name)) {
             Some(cl) => return *cl,
             _ => {}
         };
@@ -106,13 +181,14 @@ impl ClassLoader {
 
             let mut c = Class::default();
             c.heap = self.heap;
-            c.initialized = true;
+            c.init_state = InitState::Initialized;
             c.access_flags = AccessFlags(flags::ACC_PUBLIC);
             c.super_class = self.load("java/lang/Object");
-            c.name = name.to_string();
-            c.desc = name.to_string();
+            c.name = name.into();
+            c.desc = name.into();
             c.dim = dim;
             c.element_class = self.load(&el.class());
+            c.vtable = c.super_class.vtable.clone();
 
             return self.insert(c, "");
         }
@@ -121,7 +197,17 @@ impl ClassLoader {
         self.define(name, bytes)
     }
 
-    fn inject_native(&self, m: &mut ClassMember) {
+    // load class, None if no class file of name is found
+    pub fn try_load(&mut self, name: &str) -> Option<Rp<Class>> {
+        if name.starts_with('[') || self.loaded.contains_key(&Symbol::intern(name)) {
+            return Some(self.load(name));
+        }
+
+        let bytes = self.entry.read_class(name)?;
+        Some(self.define(name, bytes))
+    }
+
+    pub fn inject_native(&self, m: &mut ClassMember) {
         m.max_locals = if m.access_flags.is_static() {
             m.m_desc.arg_slots
         } else {
@@ -152,17 +238,49 @@ impl ClassLoader {
 
     fn define(&mut self, name: &str, bytes: Vec<u8>) -> Rp<Class> {
         let file = ClassFile::new(bytes);
-        let mut cl: Class = file.into();
+        let c = self.define_class(name, file.into());
+        if self.verbose {
+            println!("[Loaded {}]", name.replace('/', "."));
+        }
+        c
+    }
+
+    // link a class parsed from class file or synthesized at runtime
+    pub fn define_class(&mut self, name: &str, mut cl: Class) -> Rp<Class> {
         cl.heap = self.heap;
         for m in cl.methods.iter_mut() {
             let mut parser = DescriptorParser::new(m.desc.as_bytes());
             m.m_desc = parser.parse_method();
 
+            // native varargs methods of MethodHandle, JVMS 2.9.3
+            m.polymorphic = name == "java/lang/invoke/MethodHandle"
+                && m.access_flags.is_native()
+                && m.access_flags.is_varargs();
+
+            let intrinsic = INTRINSICS
+                .iter()
+                .any(|&(c, n, d)| c == name && n == m.name && d == m.desc);
+
+            if intrinsic {
+                m.access_flags.0 |= flags::ACC_NATIVE;
+                m.exceptions.clear();
+                m.line_numbers.clear();
+            }
+
             if m.access_flags.is_native() {
                 self.inject_native(m);
             }
         }
 
+        // hidden field holds the linked ClassMember of a member name
+        if name == "java/lang/invoke/MemberName" {
+            let mut f = ClassMember::default();
+            f.access_flags = AccessFlags(flags::ACC_PRIVATE);
+            f.name = "vmtarget".into();
+            f.desc = "J".into();
+            cl.fields.push(f);
+        }
+
         // load super and interfaces
         if &cl.super_name != "" {
             cl.super_class = self.load(&cl.super_name);
@@ -185,17 +303,13 @@ impl ClassLoader {
         cl.static_vars = vec![0u64; cl.static_fields.len()];
         cl.init_finals();
 
-        // init instance fields
-        let base = if cl.super_class.is_null() {
-            0
-        } else {
-            cl.super_class.ins_fields.len()
-        };
-
-        for i in 0..base {
-            cl.ins_fields.push(cl.super_class.ins_fields[i]);
+        // init instance fields, laid out behind fields of super class
+        if !cl.super_class.is_null() {
+            cl.ins_fields = cl.super_class.ins_fields.clone();
+            cl.ins_size = cl.super_class.ins_size;
         }
 
+        let base = cl.ins_fields.len();
         let mut i = base;
         for f in cl.fields.iter_mut().filter(|x| !x.access_flags.is_static()) {
             f.id = i;
@@ -203,6 +317,21 @@ impl ClassLoader {
             i += 1;
         }
 
+        let own = &cl.ins_fields[base..];
+        let sizes: Vec<usize> = own.iter().map(|f| f.field_size()).collect();
+        let (offsets, size) = layout(cl.ins_size, &sizes);
+        for (f, off) in own.iter().zip(offsets) {
+            f.get_mut().offset = off;
+        }
+        cl.ins_size = size;
+
+        let is_ref = |f: &Rp<ClassMember>| f.field_type() == b'L';
+        cl.static_ref_map = cl.static_fields.iter().map(is_ref).collect();
+        cl.ins_ref_map = vec![false; cl.ins_words()];
+        for f in cl.ins_fields.iter().filter(|f| is_ref(f)) {
+            cl.ins_ref_map[f.offset / 8] = true;
+        }
+
         let class_id = self.classes.len();
         self.classes.push(Rp::new(cl));
 
@@ -223,7 +352,74 @@ impl ClassLoader {
         for m in p.methods.iter_mut() {
             m.class = n;
         }
-        self.loaded.insert(name.to_string(), n);
+        p.get_mut().link_methods();
+
+        if self.loaded.insert(name.into(), n).is_some() {
+            self.epoch += 1;
+        }
         p
     }
 }
+
+// pack fields of the given sizes behind base bytes, largest first and aligned to their size
+// smaller fields fill the gaps left by alignment, returns offsets and the end of fields
+fn layout(base: usize, sizes: &[usize]) -> (Vec<usize>, usize) {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i]));
+
+    let mut offsets = vec![0; sizes.len()];
+    let mut gaps: Vec<(usize, usize)> = Vec::new();
+    let mut end = base;
+    let align = |off: usize, n: usize| (off + n - 1) / n * n;
+
+    for i in order {
+        let n = sizes[i];
+        let gap = gaps
+            .iter()
+            .position(|&(start, stop)| align(start, n) + n <= stop);
+
+        let off = match gap {
+            Some(g) => {
+                let (start, stop) = gaps.remove(g);
+                let off = align(start, n);
+                if off + n < stop {
+                    gaps.insert(g, (off + n, stop));
+                }
+                if start < off {
+                    gaps.insert(g, (start, off));
+                }
+                off
+            }
+            None => {
+                let off = align(end, n);
+                if end < off {
+                    gaps.push((end, off));
+                }
+                end = off + n;
+                off
+            }
+        };
+        offsets[i] = off;
+    }
+    (offsets, end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::layout;
+
+    #[test]
+    fn layout_test() {
+        // byte, long, int, ref, short, byte
+        let (offsets, end) = layout(0, &[1, 8, 4, 8, 2, 1]);
+        assert_eq!(offsets, vec![22, 0, 16, 8, 20, 23]);
+        assert_eq!(end, 24);
+
+        // fields of a subclass fill the tail of the last word of super class
+        let (offsets, end) = layout(1, &[8, 1, 4, 2]);
+        assert_eq!(offsets, vec![8, 1, 4, 2]);
+        assert_eq!(end, 16);
+
+        assert_eq!(layout(12, &[]), (vec![], 12));
+    }
+}
@@ -1,7 +1,13 @@
 use std::collections::BTreeMap;
 
 use crate::heap::class::{Class, Object};
+use crate::heap::desc::DescriptorParser;
+use crate::heap::gc::Collector;
+use crate::heap::lambda;
 use crate::heap::loader::ClassLoader;
+use crate::heap::monitor::Monitors;
+use crate::jit::Jit;
+use cp::Symbol;
 use err::StringErr;
 use rp::Rp;
 
@@ -60,6 +66,19 @@ pub mod flags {
     pub const ACC_ENUM: u16 = 0x4000; // class field
 }
 
+// reference kinds of constant pool method handles
+pub mod ref_kind {
+    pub const GET_FIELD: u8 = 1;
+    pub const GET_STATIC: u8 = 2;
+    pub const PUT_FIELD: u8 = 3;
+    pub const PUT_STATIC: u8 = 4;
+    pub const INVOKE_VIRTUAL: u8 = 5;
+    pub const INVOKE_STATIC: u8 = 6;
+    pub const INVOKE_SPECIAL: u8 = 7;
+    pub const NEW_INVOKE_SPECIAL: u8 = 8;
+    pub const INVOKE_INTERFACE: u8 = 9;
+}
+
 pub const PRIMITIVE_N: usize = 8;
 
 pub mod primitives {
@@ -83,8 +102,19 @@ pub struct Heap {
     pub loader: Rp<ClassLoader>,
     primitives: Vec<Rp<Class>>,
     primitive_array: Vec<Rp<Class>>,
-    string_pool: BTreeMap<String, Rp<Object>>,
+    pub(crate) string_pool: BTreeMap<String, Rp<Object>>,
     pub jstring: Rp<Class>,
+    // number of lambda classes spun
+    lambdas: usize,
+    pub gc: Collector,
+    pub monitors: Monitors,
+    pub jit: Jit,
+    // state of identity hash generator
+    hash_seed: u32,
+    // system properties given to the vm, override defaults of System.initProperties
+    pub props: BTreeMap<String, String>,
+    // objects kept alive for rust code, see Jvm::pin
+    pub pinned: Vec<Rp<Object>>,
 }
 
 macro_rules! xx_ref {
@@ -101,9 +131,15 @@ macro_rules! xx_ref {
 
         let sym = SymRef {
             class: class,
-            name: name.to_string(),
-            desc: desc.to_string(),
+            name: name.into(),
+            desc: desc.into(),
             member: m,
+            vtable_index: if m.is_null() {
+                None
+            } else {
+                m.class.vtable_index(m)
+            },
+            object: Rp::null(),
         };
 
         *r = Rp::new(sym);
@@ -111,23 +147,9 @@ macro_rules! xx_ref {
     }};
 }
 
-macro_rules! arm {
-    ($x: expr, $sz: expr, $t: ty) => {
-        $x => Rp::new(Object {
-                class,
-                data: Rp::<$t>::new_vec($sz).ptr(),
-        })
-    };
-}
-
 macro_rules! arr {
-    ($c: expr, $t: ty, $sz: expr) => {{
-        let o = Object {
-            class: $c,
-            data: Rp::<$t>::new_a($sz).ptr(),
-            size: $sz,
-        };
-        Rp::new(o)
+    ($s: ident, $c: expr, $t: ty, $sz: expr) => {{
+        $s.alloc($c, $sz, std::mem::size_of::<$t>())
     }};
 }
 
@@ -139,6 +161,13 @@ impl Heap {
             primitive_array: Vec::new(),
             jstring: Rp::null(),
             string_pool: BTreeMap::new(),
+            lambdas: 0,
+            gc: Collector::default(),
+            monitors: Monitors::default(),
+            jit: Jit::default(),
+            hash_seed: 0x2545_f491,
+            props: BTreeMap::new(),
+            pinned: Vec::new(),
         });
 
         let mut l = ClassLoader::new(cp, h)?;
@@ -156,6 +185,31 @@ impl Heap {
         Ok(h)
     }
 
+    // next identity hash, 31 bits and never 0
+    pub fn next_hash(&mut self) -> u32 {
+        loop {
+            // xorshift32
+            let mut x = self.hash_seed;
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.hash_seed = x;
+
+            let h = x & 0x7fff_ffff;
+            if h != 0 {
+                return h;
+            }
+        }
+    }
+
+    // contents of a java.lang.String, None if o is null or not a string
+    pub fn get_jstr(&self, o: Rp<Object>) -> Option<String> {
+        if o.is_null() || o.class.ptr() != self.jstring.ptr() {
+            return None;
+        }
+        Some(o.jstring())
+    }
+
     // create a string from pool
     pub fn new_jstr(&mut self, s: &str) -> Rp<Object> {
         let x = self.string_pool.get(s).map(|x| *x).unwrap_or(Rp::null());
@@ -169,19 +223,34 @@ impl Heap {
         o
     }
 
-    fn create_jstr(&mut self, s: &str) -> Rp<Object> {
-        let mut o = Class::new_obj(self.jstring);
-        self.string_pool.insert(s.to_string(), o);
+    // keep o alive for rust code until it is unpinned, an object may be pinned more than once
+    pub fn pin(&mut self, o: Rp<Object>) {
+        self.pinned.push(o);
+    }
+
+    pub fn unpin(&mut self, o: Rp<Object>) {
+        if let Some(i) = self.pinned.iter().position(|x| x.ptr() == o.ptr()) {
+            self.pinned.swap_remove(i);
+        }
+    }
 
-        // set first field
+    // create char[] of utf16 units of s
+    pub fn new_char_array(&mut self, s: &str) -> Rp<Object> {
         let v: Vec<u16> = s.encode_utf16().collect();
         let chars = self.new_primitive_array(primitives::C as i32, v.len());
         let mut arr: Rp<u16> = chars.data.into();
         for i in 0..v.len() {
             arr[i] = v[i];
         }
+        chars
+    }
+
+    // create a string not in pool
+    pub fn create_jstr(&mut self, s: &str) -> Rp<Object> {
+        let mut o = Class::new_obj(self.jstring);
 
-        o.set(0, chars.ptr() as u64);
+        let chars = self.new_char_array(s);
+        o.set_field_ref("value", chars);
         o
     }
 
@@ -196,9 +265,11 @@ impl Heap {
         let class = self.loader.load(name);
         let sym = SymRef {
             class,
-            name: name.to_string(),
-            desc: "".to_string(),
+            name: name.into(),
+            desc: "".into(),
             member: Rp::null(),
+            vtable_index: None,
+            object: Rp::null(),
         };
 
         *r = Rp::new(sym);
@@ -210,13 +281,81 @@ impl Heap {
     }
 
     pub fn method_ref(&mut self, cur: &mut Class, i: usize) -> Rp<SymRef> {
-        xx_ref!(self, cur, i, method_ref, lookup_method)
+        // invokestatic and invokespecial may reference interface methods
+        if let cp::ConstantInfo::IFaceMethodRef { .. } = cur.cp.infos()[i] {
+            return self.iface_ref(cur, i);
+        }
+
+        let mut sym = xx_ref!(self, cur, i, method_ref, lookup_method);
+
+        if sym.member.is_null() {
+            sym.member = self.polymorphic_member(sym.class, &sym.name, &sym.desc);
+        }
+        sym
+    }
+
+    // signature polymorphic methods of MethodHandle are linked against the descriptor of call site
+    fn polymorphic_member(&mut self, class: Rp<Class>, name: &str, desc: &str) -> Rp<ClassMember> {
+        let mh = self.loader.load("java/lang/invoke/MethodHandle");
+
+        if class.id != mh.id && !class.is_sub_class(&mh) {
+            return Rp::null();
+        }
+
+        let m = match mh
+            .methods
+            .iter()
+            .find(|m| m.name == name && m.is_signature_polymorphic())
+        {
+            Some(m) => m,
+            _ => return Rp::null(),
+        };
+
+        // keep declared descriptor for native lookup, arguments are passed as call site declares
+        let mut p = ClassMember::default();
+        p.access_flags = AccessFlags(m.access_flags.0);
+        p.name = m.name.clone();
+        p.desc = m.desc.clone();
+        p.class = mh;
+        p.polymorphic = true;
+        p.m_desc = DescriptorParser::new(desc.as_bytes()).parse_method();
+        self.loader.inject_native(&mut p);
+        // invoke converts a result of the handle, a long may be on the stack before its conversion
+        p.max_stack = 2;
+        Rp::new(p)
     }
 
     pub fn iface_ref(&mut self, cur: &mut Class, i: usize) -> Rp<SymRef> {
         xx_ref!(self, cur, i, iface_ref, lookup_iface_method)
     }
 
+    // resolve invokedynamic call site, member of the symbol is the factory of lambda object
+    pub fn call_site_ref(&mut self, cur: &mut Class, i: usize) -> Result<Rp<SymRef>, StringErr> {
+        let r = cur.sym_refs[i];
+
+        if !r.is_null() {
+            return Ok(r);
+        }
+
+        let name = format!("{}$$Lambda${}", cur.name, self.lambdas);
+        self.lambdas += 1;
+
+        let factory = lambda::spin_lambda(self, cur, i, &name)?;
+        let (_, sam, desc) = cur.cp.invoke_dynamic(i);
+        let sym = SymRef {
+            class: factory.class,
+            name: sam.into(),
+            desc: desc.into(),
+            member: factory,
+            vtable_index: None,
+            object: Rp::null(),
+        };
+
+        let r = Rp::new(sym);
+        cur.sym_refs[i] = r;
+        Ok(r)
+    }
+
     pub fn array_class(&mut self, element_class: Rp<Class>) -> Rp<Class> {
         self.loader.load(&format!("[{}", element_class.desc))
     }
@@ -226,7 +365,7 @@ impl Heap {
             return self.new_array(class.element_class.name.as_str(), size[0] as usize);
         }
 
-        let mut obj = arr!(class, u64, size[0] as usize);
+        let mut obj = arr!(self, class, u64, size[0] as usize);
         let next = self.loader.load(&class.desc[1..]);
 
         for i in 0..size[0] as usize {
@@ -235,25 +374,25 @@ impl Heap {
         obj
     }
 
-    pub fn new_primitive_array(&self, id: i32, size: usize) -> Rp<Object> {
+    pub fn new_primitive_array(&mut self, id: i32, size: usize) -> Rp<Object> {
         let c = self.primitive_array[id as usize];
         return match id {
             // boolean
-            0 => arr!(c, u8, size),
+            0 => arr!(self, c, u8, size),
             // char
-            1 => arr!(c, u16, size),
+            1 => arr!(self, c, u16, size),
             // float
-            2 => arr!(c, u32, size),
+            2 => arr!(self, c, u32, size),
             // double
-            3 => arr!(c, u64, size),
+            3 => arr!(self, c, u64, size),
             // byte
-            4 => arr!(c, u8, size),
+            4 => arr!(self, c, u8, size),
             // short
-            5 => arr!(c, u16, size),
+            5 => arr!(self, c, u16, size),
             // int
-            6 => arr!(c, u32, size),
+            6 => arr!(self, c, u32, size),
             // long
-            7 => arr!(c, u64, size),
+            7 => arr!(self, c, u64, size),
             _ => panic!(),
         };
     }
@@ -269,21 +408,25 @@ impl Heap {
         if o >= 0 {
             return self.new_primitive_array(o, size);
         }
-        return arr!(c, u64, size);
+        return arr!(self, c, u64, size);
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SymRef {
     pub class: Rp<Class>,
-    pub name: String,
-    pub desc: String,
+    pub name: Symbol,
+    pub desc: Symbol,
     pub member: Rp<ClassMember>,
+    // vtable index of virtual method, or itable index of interface method
+    pub vtable_index: Option<usize>,
+    // MethodType or MethodHandle resolved by ldc, the same object on every execution
+    pub object: Rp<Object>,
 }
 
 #[cfg(test)]
 mod test {
-    use crate::heap::{desc::DescriptorParser, loader::ClassLoader, misc::Heap};
+    use crate::heap::{desc::DescriptorParser, misc::Heap};
 
     #[test]
     fn loader_test() {
@@ -293,6 +436,33 @@ mod test {
         println!("{:#?}", r);
     }
 
+    #[test]
+    fn vtable_test() {
+        let mut loader = Heap::new(".:test/rt.jar").unwrap().loader;
+        let base = loader.load("test/DefaultMethodTest$Base");
+        let derived = loader.load("test/DefaultMethodTest$Derived");
+
+        // overriding method takes the slot of the overridden one
+        let m = base.lookup_method("name", "()Ljava/lang/String;");
+        let i = base.vtable_index(m).unwrap();
+        assert_eq!(
+            derived.vtable[i].class.name,
+            "test/DefaultMethodTest$Derived"
+        );
+        assert_eq!(derived.vtable.len(), base.vtable.len());
+
+        // private methods are not virtual
+        let m = base.lookup_method("secret", "()Ljava/lang/String;");
+        assert!(base.vtable_index(m).is_none());
+
+        // itable entry is the maximally-specific default method
+        let named = loader.load("test/DefaultMethodTest$Named");
+        let doc = loader.load("test/DefaultMethodTest$Doc");
+        let m = named.lookup_iface_method("name", "()Ljava/lang/String;");
+        let selected = doc.dispatch(m, named.vtable_index(m)).unwrap();
+        assert_eq!(selected.class.name, "test/DefaultMethodTest$Titled");
+    }
+
     #[test]
     fn method_test() {
         let method = "(Ljava.lang.Object;[[IIIF)V";
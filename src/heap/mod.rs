@@ -1,5 +1,12 @@
+pub mod arena;
 pub mod class;
 pub mod desc;
 pub mod desc2;
+pub mod gc;
+pub mod icache;
+pub mod invoke;
+pub mod lambda;
 pub mod loader;
 pub mod misc;
+pub mod monitor;
+pub mod refmap;
@@ -0,0 +1,221 @@
+use std::time::{Duration, Instant};
+
+use crate::heap::class::{LockWord, Object};
+use crate::heap::misc::Heap;
+use rp::Rp;
+
+// thread waiting on a monitor
+pub struct Waiter {
+    pub thread: u32,
+    // recursion count to restore when the monitor is reacquired
+    pub count: u32,
+    pub deadline: Option<Instant>,
+    pub notified: bool,
+}
+
+// inflated lock, created for contention and wait/notify
+#[derive(Default)]
+pub struct Monitor {
+    // 0 if not owned
+    pub owner: u32,
+    pub count: u32,
+    pub waiters: Vec<Waiter>,
+}
+
+// inflated monitors of heap, indexed by lock words
+#[derive(Default)]
+pub struct Monitors {
+    data: Vec<Monitor>,
+    free: Vec<usize>,
+}
+
+impl Monitors {
+    pub fn get(&mut self, i: usize) -> &mut Monitor {
+        &mut self.data[i]
+    }
+
+    fn alloc(&mut self, m: Monitor) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.data[i] = m;
+                i
+            }
+            None => {
+                self.data.push(m);
+                self.data.len() - 1
+            }
+        }
+    }
+
+    // recycle monitor of a collected object
+    pub fn release(&mut self, i: usize) {
+        self.data[i] = Monitor::default();
+        self.free.push(i);
+    }
+}
+
+impl Heap {
+    // inflate lock of o and return its monitor index
+    fn inflate(&mut self, mut o: Rp<Object>) -> usize {
+        let lock = o.header.lock;
+        if lock.is_inflated() {
+            return lock.monitor();
+        }
+
+        let (owner, count) = if lock.is_thin() {
+            (lock.owner(), lock.count())
+        } else {
+            (0, 0)
+        };
+
+        let i = self.monitors.alloc(Monitor {
+            owner,
+            count,
+            waiters: Vec::new(),
+        });
+        o.header.lock = LockWord::inflated(i);
+        i
+    }
+
+    // acquire lock of o for thread, false if it's owned by another thread
+    pub fn monitor_enter(&mut self, mut o: Rp<Object>, thread: u32) -> bool {
+        let lock = o.header.lock;
+
+        if lock.is_unlocked() {
+            o.header.lock = LockWord::thin(thread, 1);
+            return true;
+        }
+
+        if lock.is_thin() && lock.owner() == thread && lock.count() < 0x3fff_ffff {
+            o.header.lock = LockWord::thin(thread, lock.count() + 1);
+            return true;
+        }
+
+        // contended or count overflow
+        let i = self.inflate(o);
+        let m = self.monitors.get(i);
+        if m.owner == 0 {
+            m.owner = thread;
+            m.count = 1;
+        } else if m.owner == thread {
+            m.count += 1;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // release lock of o once, false if thread doesn't own it
+    pub fn monitor_exit(&mut self, mut o: Rp<Object>, thread: u32) -> bool {
+        let lock = o.header.lock;
+
+        if lock.is_thin() && lock.owner() == thread {
+            o.header.lock = if lock.count() == 1 {
+                LockWord::unlocked()
+            } else {
+                LockWord::thin(thread, lock.count() - 1)
+            };
+            return true;
+        }
+
+        if lock.is_inflated() {
+            let m = self.monitors.get(lock.monitor());
+            if m.owner == thread {
+                m.count -= 1;
+                if m.count == 0 {
+                    m.owner = 0;
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn holds_lock(&mut self, o: Rp<Object>, thread: u32) -> bool {
+        let lock = o.header.lock;
+
+        if lock.is_thin() {
+            return lock.owner() == thread;
+        }
+        lock.is_inflated() && self.monitors.get(lock.monitor()).owner == thread
+    }
+
+    // release lock of o entirely and enter its wait set, false if thread doesn't own it
+    pub fn monitor_wait(&mut self, o: Rp<Object>, thread: u32, timeout: Option<Duration>) -> bool {
+        if !self.holds_lock(o, thread) {
+            return false;
+        }
+
+        let i = self.inflate(o);
+        let m = self.monitors.get(i);
+        m.waiters.push(Waiter {
+            thread,
+            count: m.count,
+            deadline: timeout.map(|t| Instant::now() + t),
+            notified: false,
+        });
+        m.owner = 0;
+        m.count = 0;
+        true
+    }
+
+    // None if thread isn't waiting on o
+    // Some(true) if it's notified, interrupted or timed out and has reacquired the lock
+    // Some(false) if it must keep waiting
+    pub fn monitor_wait_done(
+        &mut self,
+        o: Rp<Object>,
+        thread: u32,
+        interrupted: bool,
+    ) -> Option<bool> {
+        let lock = o.header.lock;
+        if !lock.is_inflated() {
+            return None;
+        }
+
+        let m = self.monitors.get(lock.monitor());
+        let i = m.waiters.iter().position(|w| w.thread == thread)?;
+        let w = &m.waiters[i];
+        let timed_out = w.deadline.map(|d| Instant::now() >= d).unwrap_or(false);
+
+        if !(w.notified || interrupted || timed_out) || m.owner != 0 {
+            return Some(false);
+        }
+
+        m.owner = thread;
+        m.count = w.count;
+        m.waiters.remove(i);
+        Some(true)
+    }
+
+    // wake one or all threads waiting on o, false if thread doesn't own it
+    pub fn monitor_notify(&mut self, o: Rp<Object>, thread: u32, all: bool) -> bool {
+        if !self.holds_lock(o, thread) {
+            return false;
+        }
+
+        self.wake(o, all);
+        true
+    }
+
+    // wake all threads waiting on o without owning it, used when a thread terminates
+    pub fn monitor_wake_all(&mut self, o: Rp<Object>) {
+        self.wake(o, true);
+    }
+
+    fn wake(&mut self, o: Rp<Object>, all: bool) {
+        let lock = o.header.lock;
+        if !lock.is_inflated() {
+            // no waiters
+            return;
+        }
+
+        let m = self.monitors.get(lock.monitor());
+        for w in m.waiters.iter_mut().filter(|w| !w.notified) {
+            w.notified = true;
+            if !all {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use crate::heap::class::ClassMember;
+use crate::heap::desc::{DescriptorParser, JType, JTypeDescriptor};
+
+// which slots of a frame hold references before an instruction executes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RefMap {
+    pub locals: Vec<bool>,
+    // operand stack from bottom to top, long and double take two slots
+    pub stack: Vec<bool>,
+}
+
+impl RefMap {
+    fn push(&mut self, r: bool) {
+        self.stack.push(r);
+    }
+
+    // push n primitive slots
+    fn push_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.stack.push(false);
+        }
+    }
+
+    fn pop(&mut self, n: usize) {
+        let l = self.stack.len().saturating_sub(n);
+        self.stack.truncate(l);
+    }
+
+    fn top(&self) -> bool {
+        self.stack.last().copied().unwrap_or(false)
+    }
+
+    fn push_desc(&mut self, desc: &str) {
+        match desc.slots() {
+            0 => self.push(true),
+            n => self.push_n(n as usize),
+        }
+    }
+
+    fn load(&mut self, i: usize) {
+        let r = self.locals.get(i).copied().unwrap_or(false);
+        self.push(r);
+    }
+
+    // pop n slots into locals[i..i + n]
+    fn store(&mut self, i: usize, n: usize) {
+        let r = n == 1 && self.top();
+        self.pop(n);
+        for j in i..i + n {
+            if let Some(x) = self.locals.get_mut(j) {
+                *x = r;
+            }
+        }
+    }
+
+    // duplicate top n slots below m slots under them
+    fn dup(&mut self, n: usize, m: usize) {
+        let l = self.stack.len();
+        if l < n + m {
+            return;
+        }
+        let top: Vec<bool> = self.stack[l - n..].to_vec();
+        let at = l - n - m;
+        self.stack.splice(at..at, top);
+    }
+
+    // a slot holds a reference only if it does on every path, returns whether self changed
+    fn merge(&mut self, other: &RefMap) -> bool {
+        let mut changed = false;
+        for (a, b) in self.locals.iter_mut().zip(other.locals.iter()) {
+            if *a && !*b {
+                *a = false;
+                changed = true;
+            }
+        }
+
+        if other.stack.len() < self.stack.len() {
+            self.stack.truncate(other.stack.len());
+            changed = true;
+        }
+
+        for (a, b) in self.stack.iter_mut().zip(other.stack.iter()) {
+            if *a && !*b {
+                *a = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn u16_at(code: &[u8], i: usize) -> usize {
+    ((code[i] as usize) << 8) | code[i + 1] as usize
+}
+
+fn i32_at(code: &[u8], i: usize) -> i32 {
+    i32::from_be_bytes([code[i], code[i + 1], code[i + 2], code[i + 3]])
+}
+
+fn flow(maps: &mut Vec<Option<RefMap>>, work: &mut Vec<usize>, pc: usize, s: &RefMap) {
+    if pc >= maps.len() {
+        return;
+    }
+
+    match &mut maps[pc] {
+        Some(m) => {
+            if m.merge(s) {
+                work.push(pc);
+            }
+        }
+        x => {
+            *x = Some(s.clone());
+            work.push(pc);
+        }
+    }
+}
+
+// pop arguments and push result of an invocation
+fn invoke(s: &mut RefMap, desc: &str, this: bool) {
+    let d = DescriptorParser::new(desc.as_bytes()).parse_method();
+    s.pop(d.arg_slots as usize + if this { 1 } else { 0 });
+    match d.ret {
+        JType::A(_) => s.push(true),
+        t => s.push_n(t.slots() as usize),
+    }
+}
+
+// execute the instruction at pc on s
+// returns length of instruction, whether it falls through and its branch targets
+fn step(m: &ClassMember, pc: usize, s: &mut RefMap) -> (usize, bool, Vec<usize>) {
+    let code = &m.code;
+    let cp = &m.class.cp;
+    let op = code[pc];
+    let branch = |off: i32| (pc as i32 + off) as usize;
+    let mut targets = Vec::new();
+
+    // wide prefix of load, store, ret and iinc
+    if op == 0xc4 {
+        let op = code[pc + 1];
+        let i = u16_at(code, pc + 2);
+        match op {
+            0x15 | 0x17 => s.push_n(1),
+            0x16 | 0x18 => s.push_n(2),
+            0x19 => s.load(i),
+            0x36 | 0x38 | 0x3a => s.store(i, 1),
+            0x37 | 0x39 => s.store(i, 2),
+            // ret
+            0xa9 => return (4, false, targets),
+            // iinc
+            0x84 => return (6, true, targets),
+            _ => {}
+        }
+        return (4, true, targets);
+    }
+
+    let len = match op {
+        // nop
+        0x00 => 1,
+        // aconst_null
+        0x01 => {
+            s.push(true);
+            1
+        }
+        // iconst_<i>, fconst_<f>
+        0x02..=0x08 | 0x0b..=0x0d => {
+            s.push_n(1);
+            1
+        }
+        // lconst_<l>, dconst_<d>
+        0x09 | 0x0a | 0x0e | 0x0f => {
+            s.push_n(2);
+            1
+        }
+        // bipush
+        0x10 => {
+            s.push_n(1);
+            2
+        }
+        // sipush
+        0x11 => {
+            s.push_n(1);
+            3
+        }
+        // ldc, ldc_w, ldc2_w
+        0x12..=0x14 => {
+            let (i, len) = if op == 0x12 {
+                (code[pc + 1] as usize, 2)
+            } else {
+                (u16_at(code, pc + 1), 3)
+            };
+            match cp.constant(i) {
+                cp::Constant::Primitive(_, w) => s.push_n(if w { 2 } else { 1 }),
+                _ => s.push(true),
+            }
+            len
+        }
+        // iload, fload
+        0x15 | 0x17 => {
+            s.push_n(1);
+            2
+        }
+        // lload, dload
+        0x16 | 0x18 => {
+            s.push_n(2);
+            2
+        }
+        // aload
+        0x19 => {
+            s.load(code[pc + 1] as usize);
+            2
+        }
+        // iload_<n>, fload_<n>
+        0x1a..=0x1d | 0x22..=0x25 => {
+            s.push_n(1);
+            1
+        }
+        // lload_<n>, dload_<n>
+        0x1e..=0x21 | 0x26..=0x29 => {
+            s.push_n(2);
+            1
+        }
+        // aload_<n>
+        0x2a..=0x2d => {
+            s.load((op - 0x2a) as usize);
+            1
+        }
+        // iaload, faload, baload, caload, saload
+        0x2e | 0x30 | 0x33..=0x35 => {
+            s.pop(2);
+            s.push_n(1);
+            1
+        }
+        // laload, daload
+        0x2f | 0x31 => {
+            s.pop(2);
+            s.push_n(2);
+            1
+        }
+        // aaload
+        0x32 => {
+            s.pop(2);
+            s.push(true);
+            1
+        }
+        // istore, fstore, astore
+        0x36 | 0x38 | 0x3a => {
+            s.store(code[pc + 1] as usize, 1);
+            2
+        }
+        // lstore, dstore
+        0x37 | 0x39 => {
+            s.store(code[pc + 1] as usize, 2);
+            2
+        }
+        // istore_<n>, fstore_<n>, astore_<n>
+        0x3b..=0x3e | 0x43..=0x46 | 0x4b..=0x4e => {
+            s.store(((op - 0x3b) % 4) as usize, 1);
+            1
+        }
+        // lstore_<n>, dstore_<n>
+        0x3f..=0x42 | 0x47..=0x4a => {
+            s.store(((op - 0x3f) % 4) as usize, 2);
+            1
+        }
+        // iastore, fastore, aastore, bastore, castore, sastore
+        0x4f | 0x51 | 0x53..=0x56 => {
+            s.pop(3);
+            1
+        }
+        // lastore, dastore
+        0x50 | 0x52 => {
+            s.pop(4);
+            1
+        }
+        // pop, pop2
+        0x57 => {
+            s.pop(1);
+            1
+        }
+        0x58 => {
+            s.pop(2);
+            1
+        }
+        // dup, dup_x1, dup_x2
+        0x59..=0x5b => {
+            s.dup(1, (op - 0x59) as usize);
+            1
+        }
+        // dup2, dup2_x1, dup2_x2
+        0x5c..=0x5e => {
+            s.dup(2, (op - 0x5c) as usize);
+            1
+        }
+        // swap
+        0x5f => {
+            let l = s.stack.len();
+            if l >= 2 {
+                s.stack.swap(l - 1, l - 2);
+            }
+            1
+        }
+        // add, sub, mul, div, rem of int long float double
+        0x60..=0x73 => {
+            let w = if (op - 0x60) % 2 == 1 { 2 } else { 1 };
+            s.pop(w * 2);
+            s.push_n(w);
+            1
+        }
+        // neg
+        0x74..=0x77 => {
+            let w = if (op - 0x74) % 2 == 1 { 2 } else { 1 };
+            s.pop(w);
+            s.push_n(w);
+            1
+        }
+        // shl, shr, ushr, shift distance is int
+        0x78..=0x7d => {
+            let w = if (op - 0x78) % 2 == 1 { 2 } else { 1 };
+            s.pop(w + 1);
+            s.push_n(w);
+            1
+        }
+        // and, or, xor
+        0x7e..=0x83 => {
+            let w = if (op - 0x7e) % 2 == 1 { 2 } else { 1 };
+            s.pop(w * 2);
+            s.push_n(w);
+            1
+        }
+        // iinc
+        0x84 => 3,
+        // conversions
+        0x85..=0x93 => {
+            // (pop, push) of i2l i2f i2d l2i l2f l2d f2i f2l f2d d2i d2l d2f i2b i2c i2s
+            const CONV: [(usize, usize); 15] = [
+                (1, 2),
+                (1, 1),
+                (1, 2),
+                (2, 1),
+                (2, 1),
+                (2, 2),
+                (1, 1),
+                (1, 2),
+                (1, 2),
+                (2, 1),
+                (2, 2),
+                (2, 1),
+                (1, 1),
+                (1, 1),
+                (1, 1),
+            ];
+            let (a, b) = CONV[(op - 0x85) as usize];
+            s.pop(a);
+            s.push_n(b);
+            1
+        }
+        // lcmp, dcmpl, dcmpg
+        0x94 | 0x97 | 0x98 => {
+            s.pop(4);
+            s.push_n(1);
+            1
+        }
+        // fcmpl, fcmpg
+        0x95 | 0x96 => {
+            s.pop(2);
+            s.push_n(1);
+            1
+        }
+        // if<cond>, ifnull, ifnonnull
+        0x99..=0x9e | 0xc6 | 0xc7 => {
+            s.pop(1);
+            targets.push(branch(u16_at(code, pc + 1) as i16 as i32));
+            3
+        }
+        // if_icmp<cond>, if_acmp<cond>
+        0x9f..=0xa6 => {
+            s.pop(2);
+            targets.push(branch(u16_at(code, pc + 1) as i16 as i32));
+            3
+        }
+        // goto
+        0xa7 => {
+            targets.push(branch(u16_at(code, pc + 1) as i16 as i32));
+            return (3, false, targets);
+        }
+        // jsr, jsr_w push the return address
+        0xa8 | 0xc9 => {
+            let (off, len) = if op == 0xa8 {
+                (u16_at(code, pc + 1) as i16 as i32, 3)
+            } else {
+                (i32_at(code, pc + 1), 5)
+            };
+            s.push_n(1);
+            targets.push(branch(off));
+            return (len, false, targets);
+        }
+        // ret
+        0xa9 => return (2, false, targets),
+        // tableswitch, lookupswitch
+        0xaa | 0xab => {
+            s.pop(1);
+            let base = (pc + 4) & !3;
+            targets.push(branch(i32_at(code, base)));
+            let len = if op == 0xaa {
+                let low = i32_at(code, base + 4);
+                let high = i32_at(code, base + 8);
+                let n = (high - low + 1).max(0) as usize;
+                for j in 0..n {
+                    targets.push(branch(i32_at(code, base + 12 + j * 4)));
+                }
+                base + 12 + n * 4 - pc
+            } else {
+                let n = i32_at(code, base + 4).max(0) as usize;
+                for j in 0..n {
+                    targets.push(branch(i32_at(code, base + 12 + j * 8)));
+                }
+                base + 8 + n * 8 - pc
+            };
+            return (len, false, targets);
+        }
+        // ireturn .. return, athrow
+        0xac..=0xb1 | 0xbf => return (1, false, targets),
+        // getstatic
+        0xb2 => {
+            s.push_desc(cp.field_ref(u16_at(code, pc + 1)).2);
+            3
+        }
+        // putstatic
+        0xb3 => {
+            let d = cp.field_ref(u16_at(code, pc + 1)).2;
+            s.pop(d.slots().max(1) as usize);
+            3
+        }
+        // getfield
+        0xb4 => {
+            s.pop(1);
+            s.push_desc(cp.field_ref(u16_at(code, pc + 1)).2);
+            3
+        }
+        // putfield
+        0xb5 => {
+            let d = cp.field_ref(u16_at(code, pc + 1)).2;
+            s.pop(d.slots().max(1) as usize + 1);
+            3
+        }
+        // invokevirtual, invokespecial, invokestatic
+        0xb6..=0xb8 => {
+            let d = cp.member_ref(u16_at(code, pc + 1)).2;
+            invoke(s, d, op != 0xb8);
+            3
+        }
+        // invokeinterface
+        0xb9 => {
+            invoke(s, cp.member_ref(u16_at(code, pc + 1)).2, true);
+            5
+        }
+        // invokedynamic
+        0xba => {
+            invoke(s, cp.invoke_dynamic(u16_at(code, pc + 1)).2, false);
+            5
+        }
+        // new
+        0xbb => {
+            s.push(true);
+            3
+        }
+        // newarray
+        0xbc => {
+            s.pop(1);
+            s.push(true);
+            2
+        }
+        // anewarray, checkcast
+        0xbd | 0xc0 => {
+            s.pop(1);
+            s.push(true);
+            3
+        }
+        // arraylength
+        0xbe => {
+            s.pop(1);
+            s.push_n(1);
+            1
+        }
+        // instanceof
+        0xc1 => {
+            s.pop(1);
+            s.push_n(1);
+            3
+        }
+        // monitorenter, monitorexit
+        0xc2 | 0xc3 => {
+            s.pop(1);
+            1
+        }
+        // multianewarray
+        0xc5 => {
+            s.pop(code[pc + 3] as usize);
+            s.push(true);
+            4
+        }
+        // goto_w
+        0xc8 => {
+            targets.push(branch(i32_at(code, pc + 1)));
+            return (5, false, targets);
+        }
+        _ => return (1, false, targets),
+    };
+
+    (len, true, targets)
+}
+
+// reference maps before every instruction of a method, None for unreachable pc
+pub fn compute(m: &ClassMember) -> Vec<Option<RefMap>> {
+    let mut maps: Vec<Option<RefMap>> = vec![None; m.code.len()];
+    if m.code.is_empty() {
+        return maps;
+    }
+
+    let mut entry = RefMap {
+        locals: vec![false; m.max_locals as usize],
+        stack: Vec::new(),
+    };
+
+    let mut i = 0;
+    if !m.access_flags.is_static() {
+        entry.locals[0] = true;
+        i = 1;
+    }
+
+    for p in m.m_desc.params.iter() {
+        if let (JType::A(_), Some(x)) = (p, entry.locals.get_mut(i)) {
+            *x = true;
+        }
+        i += p.slots() as usize;
+    }
+
+    // natives are impdep1 and return, operand stack only holds the return value
+    if m.access_flags.is_native() {
+        entry.stack = match &m.m_desc.ret {
+            JType::A(_) => vec![true],
+            t => vec![false; t.slots() as usize],
+        };
+        for x in maps.iter_mut() {
+            *x = Some(entry.clone());
+        }
+        return maps;
+    }
+
+    maps[0] = Some(entry);
+    let mut work = vec![0usize];
+
+    while let Some(pc) = work.pop() {
+        let before = maps[pc].clone().unwrap();
+        let mut s = before.clone();
+        let (len, next, targets) = step(m, pc, &mut s);
+
+        for t in targets {
+            flow(&mut maps, &mut work, t, &s);
+        }
+
+        if next {
+            flow(&mut maps, &mut work, pc + len, &s);
+        }
+
+        // subroutine of jsr returns to the next instruction
+        if m.code[pc] == 0xa8 || m.code[pc] == 0xc9 {
+            flow(&mut maps, &mut work, pc + len, &before);
+        }
+
+        // handler sees locals of any point in the protected range and the throwable on stack
+        for e in m.exceptions.iter() {
+            if (e.start_pc as usize) <= pc && pc < e.end_pc as usize {
+                let mut h = RefMap {
+                    locals: before.locals.clone(),
+                    stack: vec![true],
+                };
+                h.merge(&RefMap {
+                    locals: s.locals.clone(),
+                    stack: vec![true],
+                });
+                flow(&mut maps, &mut work, e.handler_pc as usize, &h);
+            }
+        }
+    }
+
+    maps
+}
+
+#[cfg(test)]
+mod test {
+    use crate::heap::misc::Heap;
+
+    #[test]
+    fn ref_map_test() {
+        let mut loader = Heap::new(".:test/rt.jar").unwrap().loader;
+        let class = loader.load("test/LambdaTest");
+        assert_eq!(class.ins_ref_map, vec![false]);
+
+        // aload_0, getfield base, ireturn
+        let mut m = class.lookup_method("getBase", "()I");
+        assert_eq!(m.ref_map(0).unwrap().locals, vec![true]);
+        assert_eq!(m.ref_map(1).unwrap().stack, vec![true]);
+        assert_eq!(m.ref_map(4).unwrap().stack, vec![false]);
+        assert!(m.ref_map(2).is_none());
+
+        let s = loader.load("java/lang/String");
+        assert!(s.ins_ref_map[0]);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::ins::decode::Insn;
 use crate::ins::Compare;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 
 macro_rules! cmp {
     ($mf: ident, $p: ident, $el: expr) => {{
@@ -19,8 +20,8 @@ macro_rules! cmp {
 }
 
 macro_rules! br_1 {
-    ($th: ident, $rd: ident, $mf: ident, $p: ident, $x: ident, $e: expr) => {{
-        let off = $rd.i16() as i32;
+    ($th: ident, $ins: ident, $mf: ident, $p: ident, $x: ident, $e: expr) => {{
+        let off = $ins.a;
         let $x = { $mf.$p() };
 
         if $e {
@@ -30,20 +31,20 @@ macro_rules! br_1 {
 }
 
 macro_rules! br_1i {
-    ($th: ident, $rd: ident, $mf: ident, $x: ident, $e: expr) => {
-        br_1!($th, $rd, $mf, pop_i32, $x, $e)
+    ($th: ident, $ins: ident, $mf: ident, $x: ident, $e: expr) => {
+        br_1!($th, $ins, $mf, pop_i32, $x, $e)
     };
 }
 
 macro_rules! br_1a {
-    ($th: ident, $rd: ident, $mf: ident, $x: ident, $e: expr) => {
-        br_1!($th, $rd, $mf, pop_slot, $x, $e)
+    ($th: ident, $ins: ident, $mf: ident, $x: ident, $e: expr) => {
+        br_1!($th, $ins, $mf, pop_slot, $x, $e)
     };
 }
 
 macro_rules! br_2 {
-    ($th: ident, $rd: ident, $mf: ident, $p: ident, $x: ident, $y: ident, $e: expr) => {{
-        let off = $rd.i16() as i32;
+    ($th: ident, $ins: ident, $mf: ident, $p: ident, $x: ident, $y: ident, $e: expr) => {{
+        let off = $ins.a;
         let ($y, $x) = { ($mf.$p(), $mf.$p()) };
 
         if $e {
@@ -53,19 +54,19 @@ macro_rules! br_2 {
 }
 
 macro_rules! br_2i {
-    ($th: ident, $rd: ident, $mf: ident, $x: ident, $y: ident, $e: expr) => {
-        br_2!($th, $rd, $mf, pop_i32, $x, $y, $e)
+    ($th: ident, $ins: ident, $mf: ident, $x: ident, $y: ident, $e: expr) => {
+        br_2!($th, $ins, $mf, pop_i32, $x, $y, $e)
     };
 }
 
 macro_rules! br_2a {
-    ($th: ident, $rd: ident, $mf: ident, $x: ident, $y: ident, $e: expr) => {
-        br_2!($th, $rd, $mf, pop_slot, $x, $y, $e)
+    ($th: ident, $ins: ident, $mf: ident, $x: ident, $y: ident, $e: expr) => {
+        br_2!($th, $ins, $mf, pop_slot, $x, $y, $e)
     };
 }
 
 impl Compare for OpCode {
-    fn cmp(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn cmp(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
@@ -74,23 +75,23 @@ impl Compare for OpCode {
             fcmpg => cmp!(mf, pop_f32, 1),
             dcmpl => cmp!(mf, pop_f64, -1),
             dcmpg => cmp!(mf, pop_f64, 1),
-            ifeq => br_1i!(th, rd, mf, x, x == 0),
-            ifne => br_1i!(th, rd, mf, x, x != 0),
-            iflt => br_1i!(th, rd, mf, x, x < 0),
-            ifge => br_1i!(th, rd, mf, x, x >= 0),
-            ifgt => br_1i!(th, rd, mf, x, x > 0),
-            ifle => br_1i!(th, rd, mf, x, x <= 0),
-            if_icmpeq => br_2i!(th, rd, mf, x, y, x == y),
-            if_icmpne => br_2i!(th, rd, mf, x, y, x != y),
-            if_icmplt => br_2i!(th, rd, mf, x, y, x < y),
-            if_icmpge => br_2i!(th, rd, mf, x, y, x >= y),
-            if_icmpgt => br_2i!(th, rd, mf, x, y, x > y),
-            if_icmple => br_2i!(th, rd, mf, x, y, x <= y),
-            if_acmpeq => br_2a!(th, rd, mf, x, y, x == y),
-            if_acmpne => br_2a!(th, rd, mf, x, y, x != y),
+            ifeq => br_1i!(th, ins, mf, x, x == 0),
+            ifne => br_1i!(th, ins, mf, x, x != 0),
+            iflt => br_1i!(th, ins, mf, x, x < 0),
+            ifge => br_1i!(th, ins, mf, x, x >= 0),
+            ifgt => br_1i!(th, ins, mf, x, x > 0),
+            ifle => br_1i!(th, ins, mf, x, x <= 0),
+            if_icmpeq => br_2i!(th, ins, mf, x, y, x == y),
+            if_icmpne => br_2i!(th, ins, mf, x, y, x != y),
+            if_icmplt => br_2i!(th, ins, mf, x, y, x < y),
+            if_icmpge => br_2i!(th, ins, mf, x, y, x >= y),
+            if_icmpgt => br_2i!(th, ins, mf, x, y, x > y),
+            if_icmple => br_2i!(th, ins, mf, x, y, x <= y),
+            if_acmpeq => br_2a!(th, ins, mf, x, y, x == y),
+            if_acmpne => br_2a!(th, ins, mf, x, y, x != y),
 
-            ifnull => br_1a!(th, rd, mf, x, x == 0),
-            ifnonnull => br_1a!(th, rd, mf, x, x != 0),
+            ifnull => br_1a!(th, ins, mf, x, x == 0),
+            ifnonnull => br_1a!(th, ins, mf, x, x != 0),
             _ => {
                 panic!("invalid op {:?}", self);
             }
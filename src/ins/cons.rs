@@ -1,18 +1,47 @@
+use crate::heap::class::Object;
+use crate::heap::misc::{ref_kind, SymRef};
+use crate::ins::decode::{Insn, Operand};
 use crate::ins::Constant;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
+use rp::Rp;
+
+// MethodType or MethodHandle of cp index i resolved by an ldc of the class before
+fn resolved(f: &JFrame, i: usize) -> Option<Rp<Object>> {
+    let r = f.class().sym_refs[i];
+    if r.is_null() {
+        None
+    } else {
+        Some(r.object)
+    }
+}
+
+// keep the object of cp index i in its symbol, which is a gc root, and push it
+fn resolve(ins: &mut Insn, f: &mut JFrame, i: usize, o: Rp<Object>) {
+    let mut c = f.class();
+    if c.sym_refs[i].is_null() {
+        c.sym_refs[i] = Rp::new(SymRef {
+            class: c,
+            name: Default::default(),
+            desc: Default::default(),
+            member: Rp::null(),
+            vtable_index: None,
+            object: o,
+        });
+    }
+    ins.b = 0;
+    ins.quicken(OpCode::ldc_quick, Operand::Value(o.ptr() as u64));
+    f.push_obj(o);
+}
 
 trait Ldc {
-    fn _ldc(self, rd: &mut BytesReader, f: &mut JFrame);
+    fn _ldc(self, ins: &mut Insn, th: &mut JThread, f: &mut JFrame);
 }
 
 impl Ldc for OpCode {
-    fn _ldc(self, rd: &mut BytesReader, f: &mut JFrame) {
+    fn _ldc(self, ins: &mut Insn, th: &mut JThread, f: &mut JFrame) {
         use crate::op::OpCode::*;
-        let i = match self {
-            ldc => rd.u8() as usize,
-            _ => rd.u16() as usize,
-        };
+        let i = ins.a as usize;
 
         let cp = f.cp();
         let c = cp.constant(i);
@@ -20,11 +49,15 @@ impl Ldc for OpCode {
         match c {
             cp::Constant::Primitive(c, w) => {
                 if (self == ldc || self == ldc_w) && !w {
+                    ins.b = 1;
+                    ins.quicken(ldc_quick, Operand::Value(c));
                     f.push_u32(c as u32);
                     return;
                 }
 
                 if self == ldc2_w && w {
+                    ins.b = 2;
+                    ins.quicken(ldc_quick, Operand::Value(c));
                     f.push_u64(c);
                     return;
                 }
@@ -33,14 +66,51 @@ impl Ldc for OpCode {
                 let cp = f.cp();
                 let n = cp.utf8(i as usize);
                 let c = f.heap.loader.load(n);
+                // class objects and interned strings are never collected
+                ins.b = 0;
+                ins.quicken(ldc_quick, Operand::Value(c.j_class.ptr() as u64));
                 f.push_obj(c.j_class);
                 return;
             }
             cp::Constant::String(s) => {
                 let o = f.heap.new_jstr(s);
+                ins.b = 0;
+                ins.quicken(ldc_quick, Operand::Value(o.ptr() as u64));
                 f.push_obj(o);
                 return;
             }
+            cp::Constant::MethodType(d) => {
+                let o = match resolved(f, i) {
+                    Some(o) => o,
+                    None => f.heap.new_method_type(d),
+                };
+                resolve(ins, f, i, o);
+                return;
+            }
+            cp::Constant::MethodHandle(kind, j) => {
+                if let Some(o) = resolved(f, i) {
+                    resolve(ins, f, i, o);
+                    return;
+                }
+
+                let (c, n, d) = cp.member_ref(j as usize);
+                let class = f.heap.loader.load(c);
+                let m = f.heap.link_handle_member(kind, class, n, d);
+
+                if m.is_null() {
+                    let e = if kind <= ref_kind::PUT_STATIC {
+                        "java/lang/NoSuchFieldError"
+                    } else {
+                        "java/lang/NoSuchMethodError"
+                    };
+                    th.throw_new(e, Some(n));
+                    return;
+                }
+
+                let o = f.heap.new_method_handle(kind, class, m);
+                resolve(ins, f, i, o);
+                return;
+            }
         }
 
         panic!("java.lang.ClassFormatError");
@@ -48,7 +118,7 @@ impl Ldc for OpCode {
 }
 
 impl Constant for OpCode {
-    fn con(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn con(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
@@ -72,17 +142,15 @@ impl Constant for OpCode {
             dconst_0 => mf.push_f64(0.0f64),
             dconst_1 => mf.push_f64(1.0f64),
 
-            bipush => {
-                let i = rd.u8();
-                mf.push_u32(i as i8 as i32 as u32);
-            }
-
-            sipush => {
-                let i = rd.u16();
-                mf.push_u32(i as i16 as i32 as u32);
-            }
+            bipush | sipush => mf.push_u32(ins.a as u32),
 
-            ldc | ldc_w | ldc2_w => self._ldc(rd, mf),
+            ldc | ldc_w | ldc2_w => self._ldc(ins, th, mf),
+            // constant loaded by the first execution, b is its slots or 0 for reference
+            ldc_quick => match ins.b {
+                1 => mf.push_u32(ins.value() as u32),
+                2 => mf.push_u64(ins.value()),
+                _ => mf.push_slot(ins.value()),
+            },
             _ => {
                 panic!("invalid op {:?}", self);
             }
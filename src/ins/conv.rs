@@ -1,6 +1,7 @@
+use crate::ins::decode::Insn;
 use crate::ins::Conversion;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 
 macro_rules! cv {
     ($mf: ident, $p: ident, $psh: ident, $t: ty) => {{
@@ -19,7 +20,7 @@ macro_rules! i2x {
 }
 
 impl Conversion for OpCode {
-    fn conv(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn conv(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
@@ -1,9 +1,10 @@
+use crate::ins::decode::{Insn, Operand};
 use crate::ins::Control;
 use crate::op::OpCode;
 use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
 
 #[derive(Debug, Default)]
-struct TableSwitch {
+pub struct TableSwitch {
     default_off: i32,
     low: i32,
     high: i32,
@@ -11,7 +12,7 @@ struct TableSwitch {
 }
 
 impl TableSwitch {
-    fn read_from(rd: &mut BytesReader) -> Self {
+    pub fn read_from(rd: &mut BytesReader) -> Self {
         rd.skip_padding();
         let mut t: Self = Self::default();
         t.default_off = rd.i32();
@@ -22,7 +23,7 @@ impl TableSwitch {
         t
     }
 
-    fn exec(&self, th: &mut JThread, rd: &mut BytesReader, mf: &mut JFrame) {
+    fn exec(&self, th: &mut JThread, mf: &mut JFrame) {
         let i = mf.pop_i32();
 
         let off = if i >= self.low && i <= self.high {
@@ -36,14 +37,14 @@ impl TableSwitch {
 }
 
 #[derive(Debug, Default)]
-struct LookupSwitch {
+pub struct LookupSwitch {
     default_off: i32,
     n: i32,
     match_offs: Vec<i32>,
 }
 
 impl LookupSwitch {
-    fn read_from(rd: &mut BytesReader) -> Self {
+    pub fn read_from(rd: &mut BytesReader) -> Self {
         rd.skip_padding();
         let mut l = LookupSwitch::default();
         l.default_off = rd.i32();
@@ -52,7 +53,7 @@ impl LookupSwitch {
         l
     }
 
-    fn exec(&self, th: &mut JThread, rd: &mut BytesReader, mf: &mut JFrame) {
+    fn exec(&self, th: &mut JThread, mf: &mut JFrame) {
         let k = mf.pop_i32();
         let mut i = 0i32;
 
@@ -69,59 +70,54 @@ impl LookupSwitch {
 }
 
 impl Control for OpCode {
-    fn ctl(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn ctl(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
-            goto => {
-                let off = rd.i16() as i32;
-                th.branch(off);
-            }
-            goto_w => {
-                let off = rd.i32();
-                th.branch(off);
-            }
-            tableswitch => {
-                let tb = TableSwitch::read_from(rd);
-                tb.exec(th, rd, mf);
-            }
-            lookupswitch => {
-                let ls = LookupSwitch::read_from(rd);
-                ls.exec(th, rd, mf);
-            }
+            goto | goto_w => th.branch(ins.a),
+            // tables are prebuilt by decoding
+            tableswitch | lookupswitch => match &ins.operand {
+                Operand::Table(t) => t.exec(th, mf),
+                Operand::Lookup(l) => l.exec(th, mf),
+                _ => panic!("switch without table"),
+            },
             ireturn | lreturn | freturn | dreturn | areturn | return_void => {
-                let mut s = self;
+                let s = self;
 
-                if mf.no_ret {
-                    s = return_void;
+                // synchronized method must still hold its monitor
+                if !mf.monitor.is_null() && !th.heap.holds_lock(mf.monitor, th.id) {
+                    th.throw_new("java/lang/IllegalMonitorStateException", None);
+                    return;
                 }
 
-                if mf.method.name == "equals"
-                    && mf.method.desc == "(Ljava/lang/Object;)Z"
-                    && mf.class().name == "java/lang/String"
-                {
-                    println!("compare string {} to other", mf.this().jstring());
-                    use crate::heap::class::Object;
-                    use rp::Rp;
-                    let other: Rp<Object> = (mf.local_vars()[1] as usize).into();
-                }
+                // frame pushed by rust code returns to it
+                let bottom = mf.call;
 
-                if s == ireturn || self == freturn {
+                if s == ireturn || s == freturn {
                     let c = mf.pop_u32();
-                    th.prev_frame().push_u32(c)
+                    if bottom {
+                        th.result = c as u64;
+                    } else {
+                        th.prev_frame().push_u32(c)
+                    }
                 }
 
-                if s == lreturn || self == dreturn {
+                if s == lreturn || s == dreturn {
                     let c = mf.pop_u64();
-                    th.prev_frame().push_u64(c)
+                    if bottom {
+                        th.result = c;
+                    } else {
+                        th.prev_frame().push_u64(c)
+                    }
                 }
 
                 if s == areturn {
                     let c = mf.pop_slot();
-                    if c == 0 {
-                        println!("areturn returns a null")
+                    if bottom {
+                        th.result = c;
+                    } else {
+                        th.prev_frame().push_slot(c)
                     }
-                    th.prev_frame().push_slot(c)
                 }
                 // println!(
                 //     "exit frame {}.{} id = {}",
@@ -0,0 +1,173 @@
+use crate::heap::class::{Class, ClassMember};
+use crate::heap::misc::SymRef;
+use crate::ins::ctl::{LookupSwitch, TableSwitch};
+use crate::op::OpCode;
+use crate::runtime::misc::BytesReader;
+use rp::Rp;
+
+// operand prebuilt by decoding or resolved by quickening
+pub enum Operand {
+    None,
+    Table(Box<TableSwitch>),
+    Lookup(Box<LookupSwitch>),
+    Class(Rp<Class>),
+    // selected method of invokestatic or invokespecial
+    Member(Rp<ClassMember>),
+    Sym(Rp<SymRef>),
+    // loaded constant, 1 or 2 slots or a reference
+    Value(u64),
+}
+
+// instruction decoded from bytecode, executed instead of the raw bytes
+pub struct Insn {
+    pub op: OpCode,
+    // widened operands: local index, constant, constant pool index, branch offset or dimension
+    pub a: i32,
+    pub b: i32,
+    // bytecode pc of next instruction
+    pub next: u32,
+    pub operand: Operand,
+}
+
+impl Insn {
+    // rewrite to a quick variant once its constant pool reference is resolved
+    pub fn quicken(&mut self, op: OpCode, operand: Operand) {
+        self.op = op;
+        self.operand = operand;
+    }
+
+    pub fn class(&self) -> Rp<Class> {
+        match self.operand {
+            Operand::Class(c) => c,
+            _ => Rp::null(),
+        }
+    }
+
+    pub fn member(&self) -> Rp<ClassMember> {
+        match self.operand {
+            Operand::Member(m) => m,
+            _ => Rp::null(),
+        }
+    }
+
+    pub fn sym(&self) -> Rp<SymRef> {
+        match self.operand {
+            Operand::Sym(s) => s,
+            _ => Rp::null(),
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        match self.operand {
+            Operand::Value(v) => v,
+            _ => 0,
+        }
+    }
+}
+
+// decode bytecode of a method
+// returns instructions and the index of the instruction starting at each bytecode pc
+pub fn decode(code: &[u8]) -> (Vec<Insn>, Vec<u32>) {
+    use crate::op::OpCode::*;
+
+    let mut insns: Vec<Insn> = Vec::new();
+    let mut index = vec![u32::MAX; code.len()];
+    let mut rd = BytesReader { bytes: code, pc: 0 };
+
+    while (rd.pc as usize) < code.len() {
+        let pc = rd.pc;
+        let mut op: OpCode = rd.u8().into();
+        let is_wide = op == wide;
+        if is_wide {
+            op = rd.u8().into();
+        }
+
+        let (mut a, mut b) = (0i32, 0i32);
+        let mut operand = Operand::None;
+
+        match op {
+            bipush => a = rd.u8() as i8 as i32,
+            sipush => a = rd.i16() as i32,
+            ldc | newarray => a = rd.u8() as i32,
+            iload | lload | fload | dload | aload | istore | lstore | fstore | dstore | astore
+            | ret => {
+                a = if is_wide {
+                    rd.u16() as i32
+                } else {
+                    rd.u8() as i32
+                };
+            }
+            iinc => {
+                if is_wide {
+                    a = rd.u16() as i32;
+                    b = rd.i16() as i32;
+                } else {
+                    a = rd.u8() as i32;
+                    b = rd.u8() as i8 as i32;
+                }
+            }
+            ifeq | ifne | iflt | ifge | ifgt | ifle | if_icmpeq | if_icmpne | if_icmplt
+            | if_icmpge | if_icmpgt | if_icmple | if_acmpeq | if_acmpne | goto | jsr | ifnull
+            | ifnonnull => a = rd.i16() as i32,
+            goto_w | jsr_w => a = rd.i32(),
+            tableswitch => operand = Operand::Table(Box::new(TableSwitch::read_from(&mut rd))),
+            lookupswitch => operand = Operand::Lookup(Box::new(LookupSwitch::read_from(&mut rd))),
+            ldc_w | ldc2_w | getstatic | putstatic | getfield | putfield | invokevirtual
+            | invokespecial | invokestatic | new | anewarray | checkcast | instanceof => {
+                a = rd.u16() as i32;
+            }
+            invokeinterface | invokedynamic => {
+                a = rd.u16() as i32;
+                // count and zero bytes
+                rd.u16();
+            }
+            multianewarray => {
+                a = rd.u16() as i32;
+                b = rd.u8() as i32;
+            }
+            _ => {}
+        }
+
+        index[pc as usize] = insns.len() as u32;
+        insns.push(Insn {
+            op,
+            a,
+            b,
+            next: rd.pc,
+            operand,
+        });
+    }
+
+    (insns, index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, Operand};
+    use crate::op::OpCode;
+
+    #[test]
+    fn decode_test() {
+        let code = [
+            0x10, 0xff, // bipush -1
+            0xc4, 0x84, 0x01, 0x00, 0xff, 0xfe, // wide iinc 256 -2
+            0xaa, 0x00, 0x00, 0x00, // tableswitch, padded to pc 12
+            0x00, 0x00, 0x00, 0x10, // default
+            0x00, 0x00, 0x00, 0x01, // low
+            0x00, 0x00, 0x00, 0x02, // high
+            0x00, 0x00, 0x00, 0x20, // 1
+            0x00, 0x00, 0x00, 0x30, // 2
+            0xb1, // return
+        ];
+        let (insns, index) = decode(&code);
+
+        assert_eq!(insns.len(), 4);
+        assert_eq!((insns[0].op, insns[0].a), (OpCode::bipush, -1));
+        assert_eq!((insns[1].op, insns[1].a, insns[1].b), (OpCode::iinc, 256, -2));
+        assert_eq!(insns[1].next, 8);
+        assert!(matches!(insns[2].operand, Operand::Table(_)));
+        assert_eq!(insns[2].next, 32);
+        assert_eq!(index[2], 1);
+        assert_eq!(index[32], 3);
+    }
+}
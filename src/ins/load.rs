@@ -1,14 +1,11 @@
+use crate::ins::decode::Insn;
 use crate::ins::Load;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 
 macro_rules! xload {
-    ($rd: ident, $mf: ident, $gt: ident, $pt: ident, $wd: expr) => {{
-        let i = if $wd {
-            $rd.u16() as usize
-        } else {
-            $rd.u8() as usize
-        };
+    ($ins: ident, $mf: ident, $gt: ident, $pt: ident) => {{
+        let i = $ins.a as usize;
         let v = { $mf.local_vars().$gt(i) };
         $mf.$pt(v);
     }};
@@ -36,24 +33,27 @@ macro_rules! lload_n {
 }
 
 macro_rules! xaload {
-    ($mf: ident, $t: ty, $psh: ident) => {{
-        let i = $mf.pop_u32() as usize;
+    ($th: ident, $mf: ident, $t: ty, $psh: ident) => {{
+        let i = $mf.pop_i32();
         let obj = $mf.pop_obj();
+        if !$th.check_index(obj, i) {
+            return;
+        }
         let a: &[$t] = obj.jarray();
-        let v = a[i];
+        let v = a[i as usize];
         $mf.$psh(v);
     }};
 }
 
 impl Load for OpCode {
-    fn load(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame, w: bool) {
+    fn load(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
         use crate::runtime::misc::Slots;
 
         match self {
-            iload | fload => xload!(rd, mf, get_u32, push_u32, w),
-            lload | dload => xload!(rd, mf, get_u64, push_u64, w),
-            aload => xload!(rd, mf, get_slot, push_slot, w),
+            iload | fload => xload!(ins, mf, get_u32, push_u32),
+            lload | dload => xload!(ins, mf, get_u64, push_u64),
+            aload => xload!(ins, mf, get_slot, push_slot),
 
             iload_0 | fload_0 => iload_n!(mf, 0),
             iload_1 | fload_1 => iload_n!(mf, 1),
@@ -70,11 +70,11 @@ impl Load for OpCode {
             aload_2 => aload_n!(mf, 2),
             aload_3 => aload_n!(mf, 3),
 
-            iaload | faload => xaload!(mf, u32, push_u32),
-            daload | laload => xaload!(mf, u64, push_u64),
-            saload | caload => xaload!(mf, u16, push_u16),
-            aaload => xaload!(mf, u64, push_slot),
-            baload => xaload!(mf, u8, push_u8),
+            iaload | faload => xaload!(th, mf, u32, push_u32),
+            daload | laload => xaload!(th, mf, u64, push_u64),
+            saload | caload => xaload!(th, mf, u16, push_u16),
+            aaload => xaload!(th, mf, u64, push_slot),
+            baload => xaload!(th, mf, u8, push_u8),
             _ => panic!("invalid op {:?}", self),
         };
     }
@@ -1,7 +1,8 @@
+use crate::ins::decode::Insn;
 use crate::ins::Math;
 use crate::op::OpCode;
 use crate::runtime::misc::Slots;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 
 macro_rules! b_op {
     ($mf: ident, $p: ident, $psh: ident, $f: ident) => {{
@@ -47,6 +48,19 @@ macro_rules! b_f64 {
     }};
 }
 
+// integer division, throws ArithmeticException on zero divisor
+macro_rules! div {
+    ($th: ident, $mf: ident, $p: ident, $psh: ident, $f: ident) => {{
+        let (v2, v1) = { ($mf.$p(), $mf.$p()) };
+
+        if v2 == 0 {
+            $th.throw_new("java/lang/ArithmeticException", Some("/ by zero"));
+            return;
+        }
+        $mf.$psh(v1.$f(v2));
+    }};
+}
+
 macro_rules! u_op {
     ($mf: ident, $pp: ident, $psh: ident, $f: ident) => {{
         let v = $mf.$pp();
@@ -63,7 +77,7 @@ macro_rules! sh {
 }
 
 impl Math for OpCode {
-    fn math(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame, w: bool) {
+    fn math(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
         use core::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Rem, Shl, Shr, Sub};
 
@@ -80,12 +94,12 @@ impl Math for OpCode {
             lmul => b_i64!(mf, unchecked_mul),
             fmul => b_f32!(mf, mul),
             dmul => b_f64!(mf, mul),
-            idiv => b_i32!(mf, div),
-            ldiv => b_i64!(mf, div),
+            idiv => div!(th, mf, pop_i32, push_i32, wrapping_div),
+            ldiv => div!(th, mf, pop_i64, push_i64, wrapping_div),
             fdiv => b_f32!(mf, div),
             ddiv => b_f64!(mf, div),
-            irem => b_i32!(mf, rem),
-            lrem => b_i64!(mf, rem),
+            irem => div!(th, mf, pop_i32, push_i32, wrapping_rem),
+            lrem => div!(th, mf, pop_i64, push_i64, wrapping_rem),
             frem => b_f32!(mf, rem),
             drem => b_f64!(mf, rem),
             ineg => u_op!(mf, pop_i32, push_i32, neg),
@@ -96,8 +110,8 @@ impl Math for OpCode {
             lshl => sh!(mf, pop_i64, push_i64, shl, 0x3fu32),
             ishr => sh!(mf, pop_i32, push_i32, shr, 0x1fu32),
             lshr => sh!(mf, pop_i64, push_i64, shr, 0x3fu32),
-            iushr => sh!(mf, pop_u32, push_u32, shl, 0x1fu32),
-            lushr => sh!(mf, pop_u64, push_u64, shl, 0x3fu32),
+            iushr => sh!(mf, pop_u32, push_u32, shr, 0x1fu32),
+            lushr => sh!(mf, pop_u64, push_u64, shr, 0x3fu32),
             iand => b_u32!(mf, bitand),
             land => b_u64!(mf, bitand),
             ior => b_u32!(mf, bitor),
@@ -105,20 +119,7 @@ impl Math for OpCode {
             ixor => b_u32!(mf, bitxor),
             lxor => b_u64!(mf, bitxor),
             iinc => {
-                let (i, c) = {
-                    (
-                        if w {
-                            rd.u16() as usize
-                        } else {
-                            rd.u8() as usize
-                        },
-                        if w {
-                            rd.u16() as i16 as i32
-                        } else {
-                            rd.u8() as i8 as i32
-                        },
-                    )
-                };
+                let (i, c) = (ins.a as usize, ins.b);
                 let v = { mf.local_vars().get_i32(i) };
                 mf.local_vars().set_i32(i, v + c);
             }
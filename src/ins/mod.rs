@@ -2,6 +2,7 @@ mod cmp;
 mod cons;
 mod conv;
 mod ctl;
+pub mod decode;
 mod load;
 mod math;
 mod other;
@@ -10,55 +11,56 @@ mod stack;
 mod store;
 
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
+use decode::Insn;
 
 trait Constant {
-    fn con(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn con(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Load {
-    fn load(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame, wide: bool);
+    fn load(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Store {
-    fn store(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame, wide: bool);
+    fn store(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Stack {
-    fn stack(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn stack(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Math {
-    fn math(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame, wide: bool);
+    fn math(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 pub trait Conversion {
-    fn conv(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn conv(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Compare {
-    fn cmp(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn cmp(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Control {
-    fn ctl(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn ctl(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 pub trait Ins {
-    fn step(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame, wide: bool);
+    fn step(&mut self, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Refs {
-    fn refs(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn refs(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
 trait Other {
-    fn other(self, rd: &mut BytesReader, th: &mut JThread, frame: &mut JFrame);
+    fn other(self, ins: &mut Insn, th: &mut JThread, frame: &mut JFrame);
 }
 
-impl Ins for u8 {
-    fn step(self, rd: &mut BytesReader, th: &mut JThread, c: &mut JFrame, wide: bool) {
-        let op: OpCode = self.into();
+impl Ins for Insn {
+    fn step(&mut self, th: &mut JThread, c: &mut JFrame) {
+        let op = self.op;
 
         // if c.method.name != "hashCode" && c.method.name != "equals" {
         //     println!(
@@ -68,29 +70,32 @@ impl Ins for u8 {
         //     c.method.name,
         //     c.method.desc,
         //     c.method.access_flags.is_native(),
-        //     rd.pc - 1,
+        //     th.pc,
         //     c.stack_size,
         //     c.id,
         // );
         // }
 
-        match self {
-            0x00..=0x14 => op.con(rd, th, c),
-            0x15..=0x35 => op.load(rd, th, c, wide),
-            0x36..=0x56 => op.store(rd, th, c, wide),
-            0x57..=0x5f => op.stack(rd, th, c),
-            0x60..=0x84 => op.math(rd, th, c, wide),
-            0x85..=0x93 => op.conv(rd, th, c),
+        match op as u8 {
+            0x00..=0x14 => op.con(self, th, c),
+            0x15..=0x35 => op.load(self, th, c),
+            0x36..=0x56 => op.store(self, th, c),
+            0x57..=0x5f => op.stack(self, th, c),
+            0x60..=0x84 => op.math(self, th, c),
+            0x85..=0x93 => op.conv(self, th, c),
             // ifnull ifnonnull
-            0x94..=0xa6 | 0xc6 | 0xc7 => op.cmp(rd, th, c),
-            0xa7..=0xb1 => op.ctl(rd, th, c),
+            0x94..=0xa6 | 0xc6 | 0xc7 => op.cmp(self, th, c),
+            // goto_w
+            0xa7..=0xb1 | 0xc8 => op.ctl(self, th, c),
             // multinewarray
             0xb2..=0xc3 | 0xc5 => {
-                op.refs(rd, th, c);
+                op.refs(self, th, c);
             }
-            0xca..=0xff => op.other(rd, th, c),
-
-            _ => panic!("invalid op {}", self),
+            // impdep1
+            0xca..=0xcd => op.other(self, th, c),
+            // quick variants
+            _ if op == OpCode::ldc_quick => op.con(self, th, c),
+            _ => op.refs(self, th, c),
         }
     }
 }
@@ -1,18 +1,26 @@
+use crate::ins::decode::Insn;
 use crate::ins::Other;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 use rp::Rp;
 
 impl Other for OpCode {
-    fn other(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn other(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
             impdep1 => {
                 let p: Rp<JThread> = th.into();
-                let w = th
-                    .registry
-                    .find(&mf.class().name, &mf.method.name, &mf.method.desc);
+                let w = match th.registry.bind(mf.method.get_mut()) {
+                    Some(w) => w,
+                    None => {
+                        let m = mf.method;
+                        let msg =
+                            format!("{}.{}{}", m.class.name.replace('/', "."), m.name, m.desc);
+                        th.throw_new("java/lang/UnsatisfiedLinkError", Some(&msg));
+                        return;
+                    }
+                };
                 w.inner.exec(p.get_mut(), mf);
             }
             _ => {
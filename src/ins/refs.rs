@@ -1,163 +1,420 @@
-use crate::heap::class::Class;
+use crate::heap::class::{Class, ClassMember, Object};
 use crate::heap::desc::JTypeDescriptor;
+use crate::ins::decode::{Insn, Operand};
 use crate::ins::Refs;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, vm::JThread};
+use crate::runtime::{frame::JFrame, vm::JThread};
 
 macro_rules! asf {
-    ($op: expr, $c: ident, $sym: ident, $mf: ident, $psh: ident, $pp: ident, $t: ty) => {
+    ($op: expr, $c: ident, $id: ident, $mf: ident, $psh: ident, $pp: ident, $t: ty) => {
         match $op {
-            putstatic => $c.set_static($sym.member.id, $mf.$pp() as u64),
-            getstatic => $mf.$psh($c.get_static($sym.member.id) as $t),
-            putfield => {
-                let v = $mf.$pp();
-                let obj = $mf.pop_obj();
-                obj.fields()[$sym.member.id] = v as u64;
-            }
-            getfield => {
-                let obj = $mf.pop_obj();
-                let v = $c.get_instance(&obj, $sym.member.id);
-                $mf.$psh(v as $t);
-            }
+            putstatic_quick => $c.set_static($id, $mf.$pp() as u64),
+            getstatic_quick => $mf.$psh($c.get_static($id) as $t),
             _ => {}
         };
     };
 }
 impl Refs for OpCode {
-    fn refs(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn refs(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
-            new => {
-                let i = rd.u16() as usize;
-                let ptr = {
-                    let sym = { mf.class_ref(i) };
-                    if sym.class.get_mut().clinit(th) {
-                        th.revert_pc();
+            new | new_quick => {
+                if self == new {
+                    let sym = { mf.class_ref(ins.a as usize) };
+                    if !th.init_class(sym.class) {
                         return;
                     }
-                    let ptr = Class::new_obj(sym.class);
-                    ptr
+                    // a class still being initialized is checked again by the next execution
+                    if sym.class.is_initialized() {
+                        ins.quicken(new_quick, Operand::Class(sym.class));
+                    }
+                }
+
+                let class = match self {
+                    new_quick => ins.class(),
+                    _ => mf.class_ref(ins.a as usize).class,
                 };
+                if !th.reserve(Object::bytes(class.ins_words(), 8)) {
+                    return;
+                }
+                let ptr = Class::new_obj(class);
                 mf.push_obj(ptr);
             }
             multianewarray => {
-                let a_class = mf.class_ref(rd.u16() as usize);
-                let dim = rd.u8() as u16;
+                let a_class = mf.class_ref(ins.a as usize);
+                let dim = ins.b as u16;
                 let counts = mf.pop_slots(dim);
+
+                for &n in counts.iter() {
+                    if (n as i32) < 0 {
+                        let msg = (n as i32).to_string();
+                        th.throw_new("java/lang/NegativeArraySizeException", Some(&msg));
+                        return;
+                    }
+                }
+
+                // upper bound of all arrays, every element takes at most 8 bytes
+                let mut n = 1usize;
+                let mut bytes = 0usize;
+                for &c in counts.iter() {
+                    n = n.saturating_mul(c as usize);
+                    bytes = bytes.saturating_add(Object::bytes(n, 8));
+                }
+                if !th.reserve(bytes) {
+                    return;
+                }
                 let arr = mf.heap.new_multi_dim(a_class.class, counts);
                 mf.push_obj(arr)
             }
-            newarray | anewarray => {
-                let atype = if self == newarray {
-                    rd.u8() as usize
-                } else {
-                    rd.u16() as usize
-                };
+            newarray | anewarray | anewarray_quick => {
+                let atype = ins.a as usize;
                 let n = mf.pop_i32() as i32;
 
                 if n < 0 {
-                    panic!("java.lang.NegativeArraySize");
+                    let msg = n.to_string();
+                    th.throw_new("java/lang/NegativeArraySizeException", Some(&msg));
+                    return;
+                }
+
+                let elem = if self == newarray {
+                    [1, 2, 4, 8, 1, 2, 4, 8][atype - 4]
+                } else {
+                    8
+                };
+                if !th.reserve(Object::bytes(n as usize, elem)) {
+                    return;
                 }
 
                 if self == newarray {
                     let arr = mf.heap.new_primitive_array((atype - 4) as i32, n as usize);
                     mf.push_obj(arr);
                 } else {
-                    let c = mf.class_ref(atype).class;
+                    if self == anewarray {
+                        let c = mf.class_ref(atype).class;
+                        ins.quicken(anewarray_quick, Operand::Class(c));
+                    }
+                    let c = ins.class();
                     let arr = mf.heap.new_array(&c.name, n as usize);
                     mf.push_obj(arr);
                 };
             }
             arraylength => {
                 let obj = mf.pop_obj();
+                if obj.is_null() {
+                    th.throw_npe();
+                    return;
+                }
                 mf.push_u32(obj.size as u32);
             }
-            invokestatic | invokespecial | invokevirtual | invokeinterface => {
-                let sym = if self == invokeinterface {
-                    mf.iface_ref(rd.u16() as usize)
-                } else {
-                    mf.method_ref(rd.u16() as usize)
-                };
-                if self == invokeinterface {
-                    rd.u16();
+            invoke_quick => {
+                // selected method of invokestatic or invokespecial
+                let m = ins.member();
+                if !m.access_flags.is_static() && mf.back_obj(ins.b as usize).is_null() {
+                    th.throw_npe();
+                    return;
                 }
+                let mut new_frame = th.push_frame(m);
+                mf.pass_args(&mut new_frame, ins.b as u16);
+            }
+            invokestatic
+            | invokespecial
+            | invokevirtual
+            | invokeinterface
+            | invokevirtual_quick
+            | invokeinterface_quick => {
+                let quick = self == invokevirtual_quick || self == invokeinterface_quick;
+                let sym = match self {
+                    _ if quick => ins.sym(),
+                    invokeinterface => mf.iface_ref(ins.a as usize),
+                    _ => mf.method_ref(ins.a as usize),
+                };
 
                 let mut m = sym.member;
 
-                if self == invokestatic {
-                    if sym.class.get_mut().clinit(th) {
-                        th.revert_pc();
+                // resolution errors are thrown again on every execution
+                if !quick {
+                    if m.is_null() {
+                        let msg = format!(
+                            "{}.{}{}",
+                            sym.class.name.replace('/', "."),
+                            sym.name,
+                            sym.desc
+                        );
+                        th.throw_new("java/lang/NoSuchMethodError", Some(&msg));
+                        return;
+                    }
+
+                    if let Some(msg) = check_invoke(self, &sym.class, &m) {
+                        th.throw_new("java/lang/IncompatibleClassChangeError", Some(&msg));
+                        return;
+                    }
+
+                    // class declaring the resolved method is initialized
+                    if self == invokestatic && !th.init_class(m.class) {
                         return;
                     }
                 }
 
-                // invoke virtual, resolve method in object class
-                if self == invokevirtual || self == invokeinterface {
-                    let obj = mf.back_obj(sym.member.m_desc.arg_slots as usize + 1);
-                    if obj.is_null() {
-                        panic!("java.lang.NullPointerException");
+                let selected = match self {
+                    // invoke virtual, select method by class of receiver
+                    invokevirtual
+                    | invokeinterface
+                    | invokevirtual_quick
+                    | invokeinterface_quick => {
+                        let obj = mf.back_obj(m.m_desc.arg_slots as usize + 1);
+                        if obj.is_null() {
+                            th.throw_npe();
+                            return;
+                        }
+
+                        if (self == invokeinterface || self == invokeinterface_quick)
+                            && !obj.instance_of(&sym.class)
+                        {
+                            let msg = format!(
+                                "Class {} does not implement the requested interface {}",
+                                obj.class.name.replace('/', "."),
+                                sym.class.name.replace('/', ".")
+                            );
+                            th.throw_new("java/lang/IncompatibleClassChangeError", Some(&msg));
+                            return;
+                        }
+
+                        // method handle invokers are not overridden, they dispatch by the handle itself
+                        if m.is_signature_polymorphic() {
+                            Ok(m)
+                        } else {
+                            let epoch = th.heap.loader.epoch;
+                            let ic = mf.method.get_mut().inline_cache(mf.pc);
+                            match ic.lookup(obj.class.id, epoch) {
+                                Some(x) => Ok(x),
+                                None => obj.class.dispatch(m, sym.vtable_index).map(|x| {
+                                    ic.update(obj.class.id, x);
+                                    x
+                                }),
+                            }
+                        }
+                    }
+                    invokespecial => {
+                        if mf.back_obj(m.m_desc.arg_slots as usize + 1).is_null() {
+                            th.throw_npe();
+                            return;
+                        }
+
+                        let cur = mf.class();
+                        // super call of the current class, ACC_SUPER is assumed as in modern JVMs
+                        if m.name != "<init>"
+                            && !sym.class.access_flags.is_iface()
+                            && cur.is_sub_class(&sym.class)
+                        {
+                            cur.super_class.select_special(m)
+                        } else {
+                            sym.class.select_special(m)
+                        }
+                    }
+                    _ => Ok(m),
+                };
+
+                m = match selected {
+                    Ok(m) => m,
+                    Err(e) => {
+                        th.throw_new(e.class, Some(&e.msg));
+                        return;
                     }
+                };
+
+                let slots = if self == invokestatic {
+                    sym.member.m_desc.arg_slots
+                } else {
+                    // +1 this pointer
+                    sym.member.m_desc.arg_slots + 1
+                };
 
-                    m = obj.class.lookup_method_in_class(&sym.name, &sym.desc);
+                match self {
+                    invokevirtual => ins.quicken(invokevirtual_quick, Operand::Sym(sym)),
+                    invokeinterface => ins.quicken(invokeinterface_quick, Operand::Sym(sym)),
+                    // selection doesn't depend on receiver
+                    invokestatic if !m.class.is_initialized() => {}
+                    invokestatic | invokespecial => {
+                        ins.b = slots as i32;
+                        ins.quicken(invoke_quick, Operand::Member(m));
+                    }
+                    _ => {}
                 }
 
                 let mut new_frame = th.push_frame(m);
-                mf.pass_args(
-                    &mut new_frame,
-                    if self == invokestatic {
-                        sym.member.m_desc.arg_slots
-                    } else {
-                        // +1 this pointer
-                        sym.member.m_desc.arg_slots + 1
-                    },
-                );
+                mf.pass_args(&mut new_frame, slots);
             }
-            instanceof | checkcast => {
-                let i = rd.u16() as usize;
-                let sym = mf.class_ref(i);
+            invokedynamic => {
+                let i = ins.a as usize;
+
+                let sym = match mf.call_site_ref(i) {
+                    Ok(sym) => sym,
+                    Err(e) => {
+                        th.throw_new("java/lang/BootstrapMethodError", Some(e.0.as_str()));
+                        return;
+                    }
+                };
+
+                // factory of the call site captures arguments into a new lambda object
+                let mut new_frame = th.push_frame(sym.member);
+                mf.pass_args(&mut new_frame, sym.member.m_desc.arg_slots);
+            }
+            instanceof | checkcast | instanceof_quick | checkcast_quick => {
+                match self {
+                    instanceof => {
+                        let c = mf.class_ref(ins.a as usize).class;
+                        ins.quicken(instanceof_quick, Operand::Class(c));
+                    }
+                    checkcast => {
+                        let c = mf.class_ref(ins.a as usize).class;
+                        ins.quicken(checkcast_quick, Operand::Class(c));
+                    }
+                    _ => {}
+                }
+
+                let class = ins.class();
                 let o = mf.pop_obj();
 
                 let is = if o.is_null() {
                     false
                 } else {
-                    o.instance_of(&sym.class)
+                    o.instance_of(&class)
                 };
 
-                if self == instanceof {
+                if ins.op == instanceof_quick {
                     mf.push_u32(if is { 1 } else { 0 });
                     return;
                 }
 
+                if !o.is_null() && !is {
+                    let msg = format!(
+                        "{} cannot be cast to {}",
+                        o.class.name.replace('/', "."),
+                        class.name.replace('/', ".")
+                    );
+                    th.throw_new("java/lang/ClassCastException", Some(&msg));
+                    return;
+                }
+
                 mf.push_obj(o);
             }
-            putstatic | getstatic | putfield | getfield => {
-                let i = rd.u16() as usize;
-                let sym = mf.field_ref(i);
+            putstatic | getstatic | putfield | getfield | putstatic_quick | getstatic_quick
+            | putfield_quick | getfield_quick => {
+                let quick =
+                    self != putstatic && self != getstatic && self != putfield && self != getfield;
+                let (op, mut class, a, b) = if quick {
+                    (self, ins.class(), ins.a, ins.b)
+                } else {
+                    let sym = mf.field_ref(ins.a as usize);
 
-                if self == putstatic || self == getstatic {
-                    if sym.class.get_mut().clinit(th) {
-                        th.revert_pc();
+                    // class declaring the resolved field is initialized
+                    let owner = if sym.member.is_null() {
+                        sym.class
+                    } else {
+                        sym.member.class
+                    };
+                    let is_static = self == putstatic || self == getstatic;
+                    if is_static && !th.init_class(owner) {
                         return;
                     }
-                }
 
-                let mut class = sym.class;
+                    let op = match self {
+                        putstatic => putstatic_quick,
+                        getstatic => getstatic_quick,
+                        putfield => putfield_quick,
+                        _ => getfield_quick,
+                    };
+                    // static field slot and its size, or instance field offset and its type
+                    let (a, b) = if is_static {
+                        (sym.member.id as i32, sym.desc.slots() as i32)
+                    } else {
+                        (sym.member.offset as i32, sym.member.field_type() as i32)
+                    };
+
+                    // a class still being initialized is checked again by the next execution
+                    if !is_static || owner.is_initialized() {
+                        ins.a = a;
+                        ins.b = b;
+                        ins.quicken(op, Operand::Class(owner));
+                    }
+                    (op, owner, a, b)
+                };
 
-                match sym.desc.slots() {
+                if op == putfield_quick || op == getfield_quick {
+                    let (off, t) = (a as usize, b as u8);
+                    if op == putfield_quick {
+                        let v = match t {
+                            b'J' | b'D' => mf.pop_u64(),
+                            b'L' => mf.pop_slot(),
+                            _ => mf.pop_u32() as u64,
+                        };
+                        let mut obj = mf.pop_obj();
+                        if obj.is_null() {
+                            th.throw_npe();
+                            return;
+                        }
+                        obj.store(off, t, v);
+                    } else {
+                        let obj = mf.pop_obj();
+                        if obj.is_null() {
+                            th.throw_npe();
+                            return;
+                        }
+                        let v = obj.load(off, t);
+                        match t {
+                            b'J' | b'D' => mf.push_u64(v),
+                            b'L' => mf.push_slot(v),
+                            _ => mf.push_u32(v as u32),
+                        }
+                    }
+                    return;
+                }
+
+                let id = a as usize;
+                match b {
                     1 => {
-                        asf!(self, class, sym, mf, push_u32, pop_u32, u32);
+                        asf!(op, class, id, mf, push_u32, pop_u32, u32);
                     }
                     2 => {
-                        asf!(self, class, sym, mf, push_u64, pop_u64, u64);
+                        asf!(op, class, id, mf, push_u64, pop_u64, u64);
                     }
                     _ => {
-                        asf!(self, class, sym, mf, push_slot, pop_slot, u64);
+                        asf!(op, class, id, mf, push_slot, pop_slot, u64);
                     }
                 }
             }
-            monitorenter | monitorexit => {
-                mf.pop_slot();
+            athrow => {
+                let ex = mf.pop_obj();
+                if ex.is_null() {
+                    th.throw_npe();
+                    return;
+                }
+                th.throw(ex);
+            }
+            monitorenter => {
+                let o = mf.pop_obj();
+                if o.is_null() {
+                    th.throw_npe();
+                    return;
+                }
+
+                // owned by another thread, retry later
+                if !th.heap.monitor_enter(o, th.id) {
+                    mf.push_obj(o);
+                    th.park();
+                }
+            }
+            monitorexit => {
+                let o = mf.pop_obj();
+                if o.is_null() {
+                    th.throw_npe();
+                    return;
+                }
+
+                if !th.heap.monitor_exit(o, th.id) {
+                    th.throw_new("java/lang/IllegalMonitorStateException", None);
+                }
             }
             _ => {
                 panic!("invalid op {:?}", self);
@@ -166,6 +423,32 @@ impl Refs for OpCode {
     }
 }
 
+// kind of referenced class and method must match the invoke instruction
+fn check_invoke(op: OpCode, class: &Class, m: &ClassMember) -> Option<String> {
+    use crate::op::OpCode::*;
+    let name = || format!("{}.{}{}", m.class.name.replace('/', "."), m.name, m.desc);
+
+    if op == invokestatic && !m.access_flags.is_static() {
+        return Some(format!("Expected static method {}", name()));
+    }
+    if op != invokestatic && m.access_flags.is_static() {
+        return Some(format!("Expecting non-static method {}", name()));
+    }
+    if op == invokevirtual && class.access_flags.is_iface() {
+        return Some(format!(
+            "Found interface {}, but class was expected",
+            class.name.replace('/', ".")
+        ));
+    }
+    if op == invokeinterface && !class.access_flags.is_iface() {
+        return Some(format!(
+            "Found class {}, but interface was expected",
+            class.name.replace('/', ".")
+        ));
+    }
+    None
+}
+
 #[cfg(test)]
 mod test {
     use std::borrow::Cow;
@@ -1,9 +1,10 @@
+use crate::ins::decode::Insn;
 use crate::ins::Stack;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, misc::DupStack, vm::JThread};
+use crate::runtime::{frame::JFrame, misc::DupStack, vm::JThread};
 
 impl Stack for OpCode {
-    fn stack(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame) {
+    fn stack(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
@@ -1,14 +1,11 @@
+use crate::ins::decode::Insn;
 use crate::ins::Store;
 use crate::op::OpCode;
-use crate::runtime::{frame::JFrame, misc::BytesReader, misc::Slots, vm::JThread};
+use crate::runtime::{frame::JFrame, misc::Slots, vm::JThread};
 
 macro_rules! xstore {
-    ($rd: ident, $mf: ident, $p: ident, $s: ident, $w: expr) => {{
-        let end = if $w {
-            $rd.u16() as usize
-        } else {
-            $rd.u8() as usize
-        };
+    ($ins: ident, $mf: ident, $p: ident, $s: ident) => {{
+        let end = $ins.a as usize;
         let v = { $mf.$p() };
         $mf.local_vars().$s(end, v);
     }};
@@ -36,22 +33,25 @@ macro_rules! astore_n {
 }
 
 macro_rules! xastore {
-    ($mf: ident, $p: ident) => {{
+    ($th: ident, $mf: ident, $p: ident) => {{
         let v = $mf.$p();
-        let i = $mf.pop_u32() as usize;
-        let mut obj = $mf.pop_obj();
-        obj.jarray()[i] = v;
+        let i = $mf.pop_i32();
+        let obj = $mf.pop_obj();
+        if !$th.check_index(obj, i) {
+            return;
+        }
+        obj.jarray()[i as usize] = v;
     }};
 }
 
 impl Store for OpCode {
-    fn store(self, rd: &mut BytesReader, th: &mut JThread, mf: &mut JFrame, w: bool) {
+    fn store(self, ins: &mut Insn, th: &mut JThread, mf: &mut JFrame) {
         use crate::op::OpCode::*;
 
         match self {
-            istore | fstore => xstore!(rd, mf, pop_u32, set_u32, w),
-            lstore | dstore => xstore!(rd, mf, pop_u64, set_u64, w),
-            astore => xstore!(rd, mf, pop_slot, set_slot, w),
+            istore | fstore => xstore!(ins, mf, pop_u32, set_u32),
+            lstore | dstore => xstore!(ins, mf, pop_u64, set_u64),
+            astore => xstore!(ins, mf, pop_slot, set_slot),
             istore_0 | fstore_0 => istore_n!(mf, 0),
             istore_1 | fstore_1 => istore_n!(mf, 1),
             istore_2 | fstore_2 => istore_n!(mf, 2),
@@ -64,11 +64,26 @@ impl Store for OpCode {
             astore_1 => astore_n!(mf, 1),
             astore_2 => astore_n!(mf, 2),
             astore_3 => astore_n!(mf, 3),
-            iastore | fastore => xastore!(mf, pop_u32),
-            dastore | lastore => xastore!(mf, pop_u64),
-            sastore | castore => xastore!(mf, pop_u16),
-            aastore => xastore!(mf, pop_slot),
-            bastore => xastore!(mf, pop_u8),
+            iastore | fastore => xastore!(th, mf, pop_u32),
+            dastore | lastore => xastore!(th, mf, pop_u64),
+            sastore | castore => xastore!(th, mf, pop_u16),
+            bastore => xastore!(th, mf, pop_u8),
+            aastore => {
+                let v = mf.pop_obj();
+                let i = mf.pop_i32();
+                let obj = mf.pop_obj();
+                if !th.check_index(obj, i) {
+                    return;
+                }
+
+                let component = obj.class.component_class();
+                if !v.is_null() && !v.instance_of(&component) {
+                    let msg = v.class.name.replace('/', ".");
+                    th.throw_new("java/lang/ArrayStoreException", Some(&msg));
+                    return;
+                }
+                obj.jarray()[i as usize] = v.ptr() as u64;
+            }
             _ => panic!("invalid op {:?}", self),
         };
     }
@@ -0,0 +1,311 @@
+// minimal x86-64 assembler for the template compiler
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    Rax = 0,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+// xmm registers are encoded as their number in the reg field
+pub const XMM0: u8 = 0;
+
+// condition codes of jcc and setcc
+pub mod cc {
+    pub const B: u8 = 0x2;
+    pub const AE: u8 = 0x3;
+    pub const E: u8 = 0x4;
+    pub const NE: u8 = 0x5;
+    pub const P: u8 = 0xa;
+    pub const L: u8 = 0xc;
+    pub const GE: u8 = 0xd;
+    pub const LE: u8 = 0xe;
+    pub const G: u8 = 0xf;
+}
+
+// [base + index * scale + disp]
+#[derive(Debug, Clone, Copy)]
+pub struct Mem {
+    pub base: Reg,
+    pub index: Option<(Reg, u8)>,
+    pub disp: i32,
+}
+
+pub fn mem(base: Reg, disp: i32) -> Mem {
+    Mem {
+        base,
+        index: None,
+        disp,
+    }
+}
+
+pub fn mem_idx(base: Reg, index: Reg, scale: u8, disp: i32) -> Mem {
+    Mem {
+        base,
+        index: Some((index, scale)),
+        disp,
+    }
+}
+
+// register or memory operand of modrm
+#[derive(Debug, Clone, Copy)]
+pub enum Rm {
+    R(Reg),
+    M(Mem),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Label(usize);
+
+#[derive(Default)]
+pub struct Asm {
+    pub buf: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    // positions of rel32 to patch with label offsets
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Asm {
+    pub fn pos(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn u8(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn rex(&mut self, w: bool, r: u8, rm: Rm) {
+        let (x, b) = match rm {
+            Rm::R(b) => (0, b as u8),
+            Rm::M(m) => (m.index.map(|i| i.0 as u8).unwrap_or(0), m.base as u8),
+        };
+        let rex = (w as u8) << 3 | (r >> 3) << 2 | (x >> 3) << 1 | (b >> 3);
+        if rex != 0 {
+            self.u8(0x40 | rex);
+        }
+    }
+
+    fn modrm(&mut self, r: u8, rm: Rm) {
+        let r = (r & 7) << 3;
+        let m = match rm {
+            Rm::R(b) => {
+                self.u8(0xc0 | r | (b as u8 & 7));
+                return;
+            }
+            Rm::M(m) => m,
+        };
+
+        let base = m.base as u8 & 7;
+        // rbp and r13 have no form without displacement
+        let md = if m.disp == 0 && base != 5 {
+            0x00
+        } else if m.disp as i8 as i32 == m.disp {
+            0x40
+        } else {
+            0x80
+        };
+
+        match m.index {
+            Some((i, scale)) => {
+                let ss = match scale {
+                    1 => 0,
+                    2 => 1,
+                    4 => 2,
+                    _ => 3,
+                };
+                self.u8(md | r | 4);
+                self.u8(ss << 6 | (i as u8 & 7) << 3 | base);
+            }
+            // rsp and r12 need a sib byte
+            None if base == 4 => {
+                self.u8(md | r | 4);
+                self.u8(0x24);
+            }
+            None => self.u8(md | r | base),
+        }
+
+        match md {
+            0x40 => self.u8(m.disp as u8),
+            0x80 => self.i32(m.disp),
+            _ => {}
+        }
+    }
+
+    // [prefix] [rex] opcode modrm, r is a register or an opcode extension
+    pub fn op(&mut self, prefix: Option<u8>, w: bool, opcode: &[u8], r: u8, rm: Rm) {
+        if let Some(p) = prefix {
+            self.u8(p);
+        }
+        self.rex(w, r, rm);
+        self.buf.extend_from_slice(opcode);
+        self.modrm(r, rm);
+    }
+
+    pub fn op_imm8(&mut self, w: bool, opcode: &[u8], r: u8, rm: Rm, imm: u8) {
+        self.op(None, w, opcode, r, rm);
+        self.u8(imm);
+    }
+
+    pub fn op_imm32(&mut self, w: bool, opcode: &[u8], r: u8, rm: Rm, imm: i32) {
+        self.op(None, w, opcode, r, rm);
+        self.i32(imm);
+    }
+
+    pub fn load64(&mut self, dst: Reg, m: Mem) {
+        self.op(None, true, &[0x8b], dst as u8, Rm::M(m));
+    }
+
+    pub fn load32(&mut self, dst: Reg, m: Mem) {
+        self.op(None, false, &[0x8b], dst as u8, Rm::M(m));
+    }
+
+    pub fn store64(&mut self, m: Mem, src: Reg) {
+        self.op(None, true, &[0x89], src as u8, Rm::M(m));
+    }
+
+    pub fn store32(&mut self, m: Mem, src: Reg) {
+        self.op(None, false, &[0x89], src as u8, Rm::M(m));
+    }
+
+    // mov r32, imm32, zero extends to 64 bits
+    pub fn mov32_imm(&mut self, dst: Reg, imm: u32) {
+        if dst as u8 >= 8 {
+            self.u8(0x41);
+        }
+        self.u8(0xb8 + (dst as u8 & 7));
+        self.i32(imm as i32);
+    }
+
+    pub fn mov64_imm(&mut self, dst: Reg, imm: u64) {
+        self.u8(0x48 | (dst as u8 >> 3));
+        self.u8(0xb8 + (dst as u8 & 7));
+        self.buf.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    pub fn push(&mut self, r: Reg) {
+        if r as u8 >= 8 {
+            self.u8(0x41);
+        }
+        self.u8(0x50 + (r as u8 & 7));
+    }
+
+    pub fn pop(&mut self, r: Reg) {
+        if r as u8 >= 8 {
+            self.u8(0x41);
+        }
+        self.u8(0x58 + (r as u8 & 7));
+    }
+
+    pub fn ret(&mut self) {
+        self.u8(0xc3);
+    }
+
+    // cdq, or cqo if w
+    pub fn sign_extend_ax(&mut self, w: bool) {
+        if w {
+            self.u8(0x48);
+        }
+        self.u8(0x99);
+    }
+
+    // position to roll back to when a template gives up
+    pub fn mark(&self) -> (usize, usize) {
+        (self.buf.len(), self.fixups.len())
+    }
+
+    pub fn reset(&mut self, m: (usize, usize)) {
+        self.buf.truncate(m.0);
+        self.fixups.truncate(m.1);
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub fn bind(&mut self, l: Label) {
+        self.labels[l.0] = Some(self.pos());
+    }
+
+    pub fn offset(&self, l: Label) -> Option<usize> {
+        self.labels[l.0]
+    }
+
+    pub fn jmp(&mut self, l: Label) {
+        self.u8(0xe9);
+        self.rel32(l);
+    }
+
+    pub fn jcc(&mut self, c: u8, l: Label) {
+        self.u8(0x0f);
+        self.u8(0x80 | c);
+        self.rel32(l);
+    }
+
+    fn rel32(&mut self, l: Label) {
+        self.fixups.push((self.pos(), l));
+        self.i32(0);
+    }
+
+    // patch jumps, every referenced label must be bound
+    pub fn finish(mut self) -> Vec<u8> {
+        for &(p, l) in self.fixups.iter() {
+            let target = self.labels[l.0].expect("unbound label");
+            let rel = target as i64 - (p as i64 + 4);
+            self.buf[p..p + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_test() {
+        let mut a = Asm::default();
+        // mov rax, [r13 - 8]
+        a.load64(Reg::Rax, mem(Reg::R13, -8));
+        // mov [r12 + 16], rcx
+        a.store64(mem(Reg::R12, 16), Reg::Rcx);
+        // mov eax, [rdx + rcx * 4]
+        a.load32(Reg::Rax, mem_idx(Reg::Rdx, Reg::Rcx, 4, 0));
+        // dec qword [rbx + 32]
+        a.op(None, true, &[0xff], 1, Rm::M(mem(Reg::Rbx, 32)));
+        // addsd xmm0, [r13 - 16]
+        a.op(
+            Some(0xf2),
+            false,
+            &[0x0f, 0x58],
+            XMM0,
+            Rm::M(mem(Reg::R13, -16)),
+        );
+
+        assert_eq!(
+            a.finish(),
+            [
+                0x49, 0x8b, 0x45, 0xf8, 0x49, 0x89, 0x4c, 0x24, 0x10, 0x8b, 0x04, 0x8a, 0x48, 0xff,
+                0x4b, 0x20, 0xf2, 0x41, 0x0f, 0x58, 0x45, 0xf0
+            ]
+        );
+    }
+}
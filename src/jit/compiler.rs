@@ -0,0 +1,748 @@
+use std::collections::BTreeMap;
+
+use super::asm::{cc, mem, mem_idx, Asm, Label, Mem, Reg, Reg::*, Rm, XMM0};
+use crate::heap::class::{ClassMember, Object};
+use crate::ins::decode::Insn;
+use crate::op::OpCode;
+
+const DATA: i32 = std::mem::offset_of!(Object, data) as i32;
+const SIZE: i32 = std::mem::offset_of!(Object, size) as i32;
+
+// offsets of Context fields
+pub const CTX_SP: i32 = 16;
+pub const CTX_PC: i32 = 24;
+pub const CTX_BUDGET: i32 = 32;
+
+// machine code of a method with offsets of bytecode pc entries
+pub struct Output {
+    pub code: Vec<u8>,
+    // entry of each bytecode pc, None enters the exit
+    pub entries: Vec<Option<usize>>,
+    pub exit: usize,
+    // operand stack depth expected at each entry
+    pub depths: Vec<Option<u16>>,
+}
+
+// template compiler, every instruction has fixed code operating on frame slots
+// rbx: context, r12: local variables, r14: operand stack base
+// operand stack depth is known at each pc, so slots are addressed directly
+// exits store pc and stack depth to the context and return to the interpreter
+struct Compiler<'a> {
+    a: Asm,
+    insns: &'a [Insn],
+    pcs: Vec<u32>,
+    depths: Vec<Option<u16>>,
+    // code of instruction i
+    labels: Vec<Label>,
+    // stubs leaving at a pc, and budget checks before backward branches to a pc
+    exits: BTreeMap<u32, Label>,
+    backedges: BTreeMap<u32, Label>,
+    exit: Label,
+    // current instruction and stack depth
+    pc: u32,
+    d: u16,
+}
+
+// compile m, entries is the table the prologue jumps through
+pub fn compile(m: &mut ClassMember, entries: *const u64) -> Output {
+    m.insn(0);
+    let depths: Vec<Option<u16>> = (0..m.code.len() as u32)
+        .map(|pc| m.ref_map(pc).map(|r| r.stack.len() as u16))
+        .collect();
+    let pcs: Vec<u32> = (0..m.code.len() as u32)
+        .filter(|&pc| m.insn_index[pc as usize] != u32::MAX)
+        .collect();
+
+    let mut a = Asm::default();
+    let labels = pcs.iter().map(|_| a.new_label()).collect();
+    let exit = a.new_label();
+    let mut c = Compiler {
+        a,
+        insns: &m.insns,
+        pcs,
+        depths,
+        labels,
+        exits: BTreeMap::new(),
+        backedges: BTreeMap::new(),
+        exit,
+        pc: 0,
+        d: 0,
+    };
+    c.prologue(entries);
+
+    for i in 0..c.insns.len() {
+        c.pc = c.pcs[i];
+        c.a.bind(c.labels[i]);
+
+        let depth = match c.depths[c.pc as usize] {
+            Some(d) => d,
+            None => {
+                // unreachable
+                let l = c.exit;
+                c.a.jmp(l);
+                continue;
+            }
+        };
+        c.d = depth;
+
+        let mark = c.a.mark();
+        let insns = c.insns;
+        if c.insn(&insns[i]).is_none() {
+            c.a.reset(mark);
+            let l = c.exit_at(c.pc);
+            c.a.jmp(l);
+        }
+    }
+
+    c.stubs();
+
+    let Compiler {
+        a,
+        pcs,
+        labels,
+        depths,
+        exit,
+        ..
+    } = c;
+    let mut offsets = vec![None; depths.len()];
+    for (i, &pc) in pcs.iter().enumerate() {
+        if depths[pc as usize].is_some() {
+            offsets[pc as usize] = a.offset(labels[i]);
+        }
+    }
+    let exit = a.offset(exit).unwrap();
+
+    Output {
+        code: a.finish(),
+        entries: offsets,
+        exit,
+        depths,
+    }
+}
+
+impl<'a> Compiler<'a> {
+    fn prologue(&mut self, entries: *const u64) {
+        let a = &mut self.a;
+        a.push(Rbx);
+        a.push(R12);
+        a.push(R14);
+        // mov rbx, rdi
+        a.op(None, true, &[0x8b], Rbx as u8, Rm::R(Rdi));
+        a.load64(R12, mem(Rbx, 0));
+        a.load64(R14, mem(Rbx, 8));
+        a.load64(Rdx, mem(Rbx, CTX_SP));
+        a.load64(Rax, mem(Rbx, CTX_PC));
+        a.mov64_imm(Rcx, entries as u64);
+        // jmp [rcx + rax * 8]
+        a.op(None, false, &[0xff], 4, Rm::M(mem_idx(Rcx, Rax, 8, 0)));
+
+        // pc in eax, stack depth in edx
+        let exit = self.exit;
+        a.bind(exit);
+        a.store64(mem(Rbx, CTX_PC), Rax);
+        a.store64(mem(Rbx, CTX_SP), Rdx);
+        a.pop(R14);
+        a.pop(R12);
+        a.pop(Rbx);
+        a.ret();
+    }
+
+    // out of line code of exits and budget checks
+    fn stubs(&mut self) {
+        let targets: Vec<u32> = self.backedges.keys().copied().collect();
+        for t in targets {
+            let l = self.backedges[&t];
+            self.a.bind(l);
+            // dec qword [rbx + budget]
+            self.a
+                .op(None, true, &[0xff], 1, Rm::M(mem(Rbx, CTX_BUDGET)));
+            let e = self.exit_at(t);
+            self.a.jcc(cc::LE, e);
+            let i = self.index(t).unwrap();
+            self.a.jmp(self.labels[i]);
+        }
+
+        let exits: Vec<(u32, Label)> = self.exits.iter().map(|(&p, &l)| (p, l)).collect();
+        for (pc, l) in exits {
+            self.a.bind(l);
+            self.a.mov32_imm(Rax, pc);
+            self.a
+                .mov32_imm(Rdx, self.depths[pc as usize].unwrap_or(0) as u32);
+            self.a.jmp(self.exit);
+        }
+    }
+
+    fn index(&self, pc: u32) -> Option<usize> {
+        self.pcs.binary_search(&pc).ok()
+    }
+
+    // leave compiled code before the instruction at pc
+    fn exit_at(&mut self, pc: u32) -> Label {
+        if let Some(&l) = self.exits.get(&pc) {
+            return l;
+        }
+        let l = self.a.new_label();
+        self.exits.insert(pc, l);
+        l
+    }
+
+    fn deopt(&mut self) -> Label {
+        self.exit_at(self.pc)
+    }
+
+    // slot i of operand stack
+    fn slot(&self, i: u16) -> Mem {
+        mem(R14, i as i32 * 8)
+    }
+
+    // k slots below top of operand stack
+    fn top(&self, k: u16) -> Mem {
+        self.slot(self.d - 1 - k)
+    }
+
+    fn local(&self, i: i32) -> Mem {
+        mem(R12, i * 8)
+    }
+
+    fn need(&self, n: u16) -> Option<()> {
+        if self.d >= n {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // depth of pc must match the depth code leaves there
+    fn check_depth(&self, pc: u32) -> Option<()> {
+        if self.index(pc).is_some() && self.depths[pc as usize] == Some(self.d) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn push_imm(&mut self, v: u64, slots: u16) {
+        if v <= u32::MAX as u64 {
+            self.a.mov32_imm(Rax, v as u32);
+        } else {
+            self.a.mov64_imm(Rax, v);
+        }
+        self.a.store64(self.slot(self.d), Rax);
+        self.d += slots;
+    }
+
+    fn copy(&mut self, dst: Mem, src: Mem) {
+        self.a.load64(Rax, src);
+        self.a.store64(dst, Rax);
+    }
+
+    // jump to bytecode pc, backward branches check the budget first
+    fn branch(&mut self, c: Option<u8>, target: u32) -> Option<()> {
+        self.check_depth(target)?;
+        let l = if target <= self.pc {
+            match self.backedges.get(&target) {
+                Some(&l) => l,
+                None => {
+                    let l = self.a.new_label();
+                    self.backedges.insert(target, l);
+                    l
+                }
+            }
+        } else {
+            self.labels[self.index(target)?]
+        };
+
+        match c {
+            Some(c) => self.a.jcc(c, l),
+            None => self.a.jmp(l),
+        }
+        Some(())
+    }
+
+    fn null_check(&mut self, r: Reg) {
+        // test r, r
+        self.a.op(None, true, &[0x85], r as u8, Rm::R(r));
+        let l = self.deopt();
+        self.a.jcc(cc::E, l);
+    }
+
+    // rdx = array data, rcx = index, after null and bounds checks
+    fn array(&mut self, arr: Mem, index: Mem) {
+        self.a.load64(Rax, arr);
+        self.null_check(Rax);
+        // movsxd rcx, [index]
+        self.a.op(None, true, &[0x63], Rcx as u8, Rm::M(index));
+        // cmp rcx, [rax + size], negative index is a large unsigned
+        self.a
+            .op(None, true, &[0x3b], Rcx as u8, Rm::M(mem(Rax, SIZE)));
+        let l = self.deopt();
+        self.a.jcc(cc::AE, l);
+        self.a.load64(Rdx, mem(Rax, DATA));
+    }
+
+    // v1 op= v2 of two int or long operands
+    fn binary(&mut self, opcode: &[u8], long: bool) -> Option<()> {
+        let n = if long { 2 } else { 1 };
+        self.need(2 * n)?;
+        let (v1, v2) = (self.slot(self.d - 2 * n), self.slot(self.d - n));
+        self.a.op(None, long, &[0x8b], Rax as u8, Rm::M(v1));
+        self.a.op(None, long, opcode, Rax as u8, Rm::M(v2));
+        self.a.store64(v1, Rax);
+        self.d -= n;
+        Some(())
+    }
+
+    // float or double arithmetic by sse
+    fn binary_sse(&mut self, opcode: u8, double: bool) -> Option<()> {
+        let (n, prefix) = if double { (2, 0xf2) } else { (1, 0xf3) };
+        self.need(2 * n)?;
+        let (v1, v2) = (self.slot(self.d - 2 * n), self.slot(self.d - n));
+        self.a
+            .op(Some(prefix), false, &[0x0f, 0x10], XMM0, Rm::M(v1));
+        self.a
+            .op(Some(prefix), false, &[0x0f, opcode], XMM0, Rm::M(v2));
+        self.store_xmm0(v1, double);
+        self.d -= n;
+        Some(())
+    }
+
+    // store xmm0 to a slot, a float is zero extended like ints
+    fn store_xmm0(&mut self, m: Mem, double: bool) {
+        if double {
+            self.a.op(Some(0xf2), false, &[0x0f, 0x11], XMM0, Rm::M(m));
+        } else {
+            // movd eax, xmm0
+            self.a
+                .op(Some(0x66), false, &[0x0f, 0x7e], XMM0, Rm::R(Rax));
+            self.a.store64(m, Rax);
+        }
+    }
+
+    fn div(&mut self, rem: bool, long: bool) -> Option<()> {
+        let n = if long { 2 } else { 1 };
+        self.need(2 * n)?;
+        let (v1, v2) = (self.slot(self.d - 2 * n), self.slot(self.d - n));
+        let l = self.deopt();
+        // the interpreter throws ArithmeticException and handles overflow of MIN / -1
+        self.a.op(None, long, &[0x8b], Rcx as u8, Rm::M(v2));
+        self.a.op(None, long, &[0x85], Rcx as u8, Rm::R(Rcx));
+        self.a.jcc(cc::E, l);
+        self.a.op_imm8(long, &[0x83], 7, Rm::R(Rcx), 0xff);
+        self.a.jcc(cc::E, l);
+        self.a.op(None, long, &[0x8b], Rax as u8, Rm::M(v1));
+        self.a.sign_extend_ax(long);
+        // idiv rcx
+        self.a.op(None, long, &[0xf7], 7, Rm::R(Rcx));
+        self.a.store64(v1, if rem { Rdx } else { Rax });
+        self.d -= n;
+        Some(())
+    }
+
+    // shift value below an int count, x86 masks the count like java
+    fn shift(&mut self, ext: u8, long: bool) -> Option<()> {
+        let n = if long { 2 } else { 1 };
+        self.need(n + 1)?;
+        let v = self.slot(self.d - 1 - n);
+        self.a.load32(Rcx, self.top(0));
+        self.a.op(None, long, &[0x8b], Rax as u8, Rm::M(v));
+        self.a.op(None, long, &[0xd3], ext, Rm::R(Rax));
+        self.a.store64(v, Rax);
+        self.d -= 1;
+        Some(())
+    }
+
+    // compare of two long, float or double values to -1, 0 or 1
+    fn compare(&mut self, op: OpCode) -> Option<()> {
+        use crate::op::OpCode::*;
+        let n = if op == fcmpl || op == fcmpg { 1 } else { 2 };
+        self.need(2 * n)?;
+        let (v1, v2) = (self.slot(self.d - 2 * n), self.slot(self.d - n));
+        let done = self.a.new_label();
+
+        match op {
+            lcmp => {
+                self.a.load64(Rax, v1);
+                self.a.op(None, true, &[0x3b], Rax as u8, Rm::M(v2));
+                self.a.mov32_imm(Rax, -1i32 as u32);
+                self.a.jcc(cc::L, done);
+                self.a.mov32_imm(Rax, 0);
+                // setg al
+                self.a.op(None, false, &[0x0f, 0x9f], 0, Rm::R(Rax));
+            }
+            _ => {
+                let (prefix, ucomis) = if n == 1 {
+                    (0xf3, None)
+                } else {
+                    (0xf2, Some(0x66))
+                };
+                self.a
+                    .op(Some(prefix), false, &[0x0f, 0x10], XMM0, Rm::M(v1));
+                self.a.op(ucomis, false, &[0x0f, 0x2e], XMM0, Rm::M(v2));
+                let nan = if op == fcmpl || op == dcmpl { -1 } else { 1 };
+                self.a.mov32_imm(Rax, nan as u32);
+                self.a.jcc(cc::P, done);
+                self.a.mov32_imm(Rax, -1i32 as u32);
+                self.a.jcc(cc::B, done);
+                self.a.mov32_imm(Rax, 0);
+                // seta al
+                self.a.op(None, false, &[0x0f, 0x97], 0, Rm::R(Rax));
+            }
+        }
+        self.a.bind(done);
+        self.a.store64(v1, Rax);
+        self.d -= 2 * n - 1;
+        Some(())
+    }
+
+    // duplicate top n slots under m slots below them
+    fn dup(&mut self, n: u16, m: u16) -> Option<()> {
+        self.need(n + m)?;
+        let regs = [Rax, Rcx, Rdx, Rsi];
+        let base = self.d - n - m;
+        for i in 0..n + m {
+            self.a.load64(regs[i as usize], self.slot(base + i));
+        }
+        let order = (m..n + m).chain(0..n + m);
+        for (i, r) in order.enumerate() {
+            self.a.store64(self.slot(base + i as u16), regs[r as usize]);
+        }
+        self.d += n;
+        Some(())
+    }
+
+    fn insn(&mut self, ins: &Insn) -> Option<()> {
+        use crate::op::OpCode::*;
+        let op = ins.op;
+        let next = ins.next;
+        let code = op as u8;
+
+        match op {
+            nop => {}
+            aconst_null => self.push_imm(0, 1),
+            iconst_m1 | iconst_0 | iconst_1 | iconst_2 | iconst_3 | iconst_4 | iconst_5 => {
+                self.push_imm((code as i32 - 3) as u32 as u64, 1)
+            }
+            lconst_0 | lconst_1 => self.push_imm((code - 0x09) as u64, 2),
+            fconst_0 | fconst_1 | fconst_2 => {
+                self.push_imm(((code - 0x0b) as f32).to_bits() as u64, 1)
+            }
+            dconst_0 | dconst_1 => self.push_imm(((code - 0x0e) as f64).to_bits(), 2),
+            bipush | sipush => self.push_imm(ins.a as u32 as u64, 1),
+            ldc_quick => match ins.b {
+                1 => self.push_imm(ins.value() as u32 as u64, 1),
+                2 => self.push_imm(ins.value(), 2),
+                _ => self.push_imm(ins.value(), 1),
+            },
+
+            iload | lload | fload | dload | aload => {
+                let n = if op == lload || op == dload { 2 } else { 1 };
+                self.copy(self.slot(self.d), self.local(ins.a));
+                self.d += n;
+            }
+            // iload_0 ... aload_3
+            _ if (0x1a..=0x2d).contains(&code) => {
+                let (k, i) = ((code - 0x1a) / 4, (code - 0x1a) % 4);
+                self.copy(self.slot(self.d), self.local(i as i32));
+                self.d += if k == 1 || k == 3 { 2 } else { 1 };
+            }
+            istore | lstore | fstore | dstore | astore => {
+                let n = if op == lstore || op == dstore { 2 } else { 1 };
+                self.need(n)?;
+                self.copy(self.local(ins.a), self.slot(self.d - n));
+                self.d -= n;
+            }
+            // istore_0 ... astore_3
+            _ if (0x3b..=0x4e).contains(&code) => {
+                let (k, i) = ((code - 0x3b) / 4, (code - 0x3b) % 4);
+                let n = if k == 1 || k == 3 { 2 } else { 1 };
+                self.need(n)?;
+                self.copy(self.local(i as i32), self.slot(self.d - n));
+                self.d -= n;
+            }
+            iinc => {
+                let l = self.local(ins.a);
+                self.a.load32(Rax, l);
+                self.a.op_imm32(false, &[0x81], 0, Rm::R(Rax), ins.b);
+                self.a.store64(l, Rax);
+            }
+
+            iaload | faload | laload | daload | aaload | caload => {
+                self.need(2)?;
+                self.array(self.top(1), self.top(0));
+                let v = self.top(1);
+                match op {
+                    iaload | faload => self.a.load32(Rax, mem_idx(Rdx, Rcx, 4, 0)),
+                    // movzx eax, word
+                    caload => {
+                        let m = Rm::M(mem_idx(Rdx, Rcx, 2, 0));
+                        self.a.op(None, false, &[0x0f, 0xb7], Rax as u8, m)
+                    }
+                    _ => self.a.load64(Rax, mem_idx(Rdx, Rcx, 8, 0)),
+                }
+                self.a.store64(v, Rax);
+                self.d -= if op == laload || op == daload { 0 } else { 1 };
+            }
+            iastore | fastore | lastore | dastore | castore => {
+                let n = if op == lastore || op == dastore { 2 } else { 1 };
+                self.need(2 + n)?;
+                let v = self.slot(self.d - n);
+                self.array(self.slot(self.d - n - 2), self.slot(self.d - n - 1));
+                match op {
+                    iastore | fastore => {
+                        self.a.load32(Rsi, v);
+                        self.a.store32(mem_idx(Rdx, Rcx, 4, 0), Rsi);
+                    }
+                    castore => {
+                        self.a.load32(Rsi, v);
+                        let m = Rm::M(mem_idx(Rdx, Rcx, 2, 0));
+                        self.a.op(Some(0x66), false, &[0x89], Rsi as u8, m);
+                    }
+                    _ => {
+                        self.a.load64(Rsi, v);
+                        self.a.store64(mem_idx(Rdx, Rcx, 8, 0), Rsi);
+                    }
+                }
+                self.d -= 2 + n;
+            }
+
+            pop => {
+                self.need(1)?;
+                self.d -= 1;
+            }
+            pop2 => {
+                self.need(2)?;
+                self.d -= 2;
+            }
+            dup => self.dup(1, 0)?,
+            dup_x1 => self.dup(1, 1)?,
+            dup_x2 => self.dup(1, 2)?,
+            dup2 => self.dup(2, 0)?,
+            dup2_x1 => self.dup(2, 1)?,
+            dup2_x2 => self.dup(2, 2)?,
+            swap => {
+                self.need(2)?;
+                let (v1, v2) = (self.top(1), self.top(0));
+                self.a.load64(Rax, v1);
+                self.a.load64(Rcx, v2);
+                self.a.store64(v1, Rcx);
+                self.a.store64(v2, Rax);
+            }
+
+            iadd | ladd => self.binary(&[0x03], op == ladd)?,
+            isub | lsub => self.binary(&[0x2b], op == lsub)?,
+            imul | lmul => self.binary(&[0x0f, 0xaf], op == lmul)?,
+            iand | land => self.binary(&[0x23], op == land)?,
+            ior | lor => self.binary(&[0x0b], op == lor)?,
+            ixor | lxor => self.binary(&[0x33], op == lxor)?,
+            fadd | dadd => self.binary_sse(0x58, op == dadd)?,
+            fsub | dsub => self.binary_sse(0x5c, op == dsub)?,
+            fmul | dmul => self.binary_sse(0x59, op == dmul)?,
+            fdiv | ddiv => self.binary_sse(0x5e, op == ddiv)?,
+            idiv | ldiv => self.div(false, op == ldiv)?,
+            irem | lrem => self.div(true, op == lrem)?,
+            ishl | lshl => self.shift(4, op == lshl)?,
+            ishr | lshr => self.shift(7, op == lshr)?,
+            iushr | lushr => self.shift(5, op == lushr)?,
+            ineg | lneg => {
+                let n = if op == lneg { 2 } else { 1 };
+                self.need(n)?;
+                let v = self.slot(self.d - n);
+                self.a.op(None, n == 2, &[0x8b], Rax as u8, Rm::M(v));
+                self.a.op(None, n == 2, &[0xf7], 3, Rm::R(Rax));
+                self.a.store64(v, Rax);
+            }
+            fneg => {
+                self.need(1)?;
+                let v = self.top(0);
+                self.a.load32(Rax, v);
+                self.a.op_imm32(false, &[0x81], 6, Rm::R(Rax), i32::MIN);
+                self.a.store64(v, Rax);
+            }
+            dneg => {
+                self.need(2)?;
+                let v = self.top(1);
+                self.a.load64(Rax, v);
+                // btc rax, 63
+                self.a.op_imm8(true, &[0x0f, 0xba], 7, Rm::R(Rax), 63);
+                self.a.store64(v, Rax);
+            }
+
+            i2l => {
+                self.need(1)?;
+                let v = self.top(0);
+                self.a.op(None, true, &[0x63], Rax as u8, Rm::M(v));
+                self.a.store64(v, Rax);
+                self.d += 1;
+            }
+            l2i => {
+                self.need(2)?;
+                let v = self.top(1);
+                self.a.load32(Rax, v);
+                self.a.store64(v, Rax);
+                self.d -= 1;
+            }
+            i2b | i2c | i2s => {
+                self.need(1)?;
+                let v = self.top(0);
+                let opcode = match op {
+                    i2b => 0xbe,
+                    i2c => 0xb7,
+                    _ => 0xbf,
+                };
+                self.a.op(None, false, &[0x0f, opcode], Rax as u8, Rm::M(v));
+                self.a.store64(v, Rax);
+            }
+            i2f | i2d | l2f | l2d => {
+                let n = if op == l2f || op == l2d { 2 } else { 1 };
+                self.need(n)?;
+                let v = self.slot(self.d - n);
+                let double = op == i2d || op == l2d;
+                // cvtsi2ss or cvtsi2sd
+                let prefix = if double { 0xf2 } else { 0xf3 };
+                self.a
+                    .op(Some(prefix), n == 2, &[0x0f, 0x2a], XMM0, Rm::M(v));
+                self.store_xmm0(v, double);
+                self.d = self.d - n + if double { 2 } else { 1 };
+            }
+            f2d => {
+                self.need(1)?;
+                let v = self.top(0);
+                self.a.op(Some(0xf3), false, &[0x0f, 0x5a], XMM0, Rm::M(v));
+                self.store_xmm0(v, true);
+                self.d += 1;
+            }
+            d2f => {
+                self.need(2)?;
+                let v = self.top(1);
+                self.a.op(Some(0xf2), false, &[0x0f, 0x5a], XMM0, Rm::M(v));
+                self.store_xmm0(v, false);
+                self.d -= 1;
+            }
+
+            lcmp | fcmpl | fcmpg | dcmpl | dcmpg => self.compare(op)?,
+            ifeq | ifne | iflt | ifge | ifgt | ifle | ifnull | ifnonnull => {
+                self.need(1)?;
+                let long = op == ifnull || op == ifnonnull;
+                self.a.op_imm8(long, &[0x83], 7, Rm::M(self.top(0)), 0);
+                self.d -= 1;
+                let c = match op {
+                    ifeq | ifnull => cc::E,
+                    ifne | ifnonnull => cc::NE,
+                    iflt => cc::L,
+                    ifge => cc::GE,
+                    ifgt => cc::G,
+                    _ => cc::LE,
+                };
+                self.branch(Some(c), (self.pc as i32 + ins.a) as u32)?;
+            }
+            if_icmpeq | if_icmpne | if_icmplt | if_icmpge | if_icmpgt | if_icmple | if_acmpeq
+            | if_acmpne => {
+                self.need(2)?;
+                let long = op == if_acmpeq || op == if_acmpne;
+                self.a
+                    .op(None, long, &[0x8b], Rax as u8, Rm::M(self.top(1)));
+                self.a
+                    .op(None, long, &[0x3b], Rax as u8, Rm::M(self.top(0)));
+                self.d -= 2;
+                let c = match op {
+                    if_icmpeq | if_acmpeq => cc::E,
+                    if_icmpne | if_acmpne => cc::NE,
+                    if_icmplt => cc::L,
+                    if_icmpge => cc::GE,
+                    if_icmpgt => cc::G,
+                    _ => cc::LE,
+                };
+                self.branch(Some(c), (self.pc as i32 + ins.a) as u32)?;
+            }
+            goto | goto_w => return self.branch(None, (self.pc as i32 + ins.a) as u32),
+
+            arraylength => {
+                self.need(1)?;
+                let v = self.top(0);
+                self.a.load64(Rax, v);
+                self.null_check(Rax);
+                self.a.load64(Rax, mem(Rax, SIZE));
+                self.a.store64(v, Rax);
+            }
+            // field offset in a, first byte of its descriptor in b
+            getfield_quick | putfield_quick => {
+                let t = ins.b as u8;
+                let n = if t == b'J' || t == b'D' { 2 } else { 1 };
+                if op == getfield_quick {
+                    self.need(1)?;
+                    let o = self.top(0);
+                    self.a.load64(Rax, o);
+                    self.null_check(Rax);
+                    self.a.load64(Rcx, mem(Rax, DATA));
+                    self.load_typed(Rax, mem(Rcx, ins.a), t);
+                    self.a.store64(o, Rax);
+                    self.d += n - 1;
+                } else {
+                    self.need(n + 1)?;
+                    let v = self.slot(self.d - n);
+                    self.a.load64(Rax, self.slot(self.d - n - 1));
+                    self.null_check(Rax);
+                    self.a.load64(Rcx, mem(Rax, DATA));
+                    self.a.load64(Rdx, v);
+                    self.store_typed(mem(Rcx, ins.a), Rdx, t);
+                    self.d -= n + 1;
+                }
+            }
+            getstatic_quick | putstatic_quick => {
+                let n = ins.b.max(1) as u16;
+                let class = ins.class();
+                let p = class.static_vars.get(ins.a as usize)? as *const u64;
+                self.a.mov64_imm(Rcx, p as u64);
+                if op == getstatic_quick {
+                    self.load_field(Rax, mem(Rcx, 0), ins.b);
+                    self.a.store64(self.slot(self.d), Rax);
+                    self.d += n;
+                } else {
+                    self.need(n)?;
+                    self.load_field(Rax, self.slot(self.d - n), ins.b);
+                    self.a.store64(mem(Rcx, 0), Rax);
+                    self.d -= n;
+                }
+            }
+            // invocations, returns, allocations, exceptions and monitors are left to the interpreter
+            _ => return None,
+        }
+
+        self.check_depth(next)
+    }
+
+    // field of type t, extended as kept in a slot
+    fn load_typed(&mut self, r: Reg, m: Mem, t: u8) {
+        let m = Rm::M(m);
+        match t {
+            // movzx r32, byte
+            b'Z' => self.a.op(None, false, &[0x0f, 0xb6], r as u8, m),
+            // movsx r32, byte
+            b'B' => self.a.op(None, false, &[0x0f, 0xbe], r as u8, m),
+            b'C' => self.a.op(None, false, &[0x0f, 0xb7], r as u8, m),
+            b'S' => self.a.op(None, false, &[0x0f, 0xbf], r as u8, m),
+            b'I' | b'F' => self.a.op(None, false, &[0x8b], r as u8, m),
+            _ => self.a.op(None, true, &[0x8b], r as u8, m),
+        }
+    }
+
+    // low bytes of r to field of type t, r must be rax, rcx, rdx or rbx for byte fields
+    fn store_typed(&mut self, m: Mem, r: Reg, t: u8) {
+        let m = Rm::M(m);
+        match t {
+            b'Z' | b'B' => self.a.op(None, false, &[0x88], r as u8, m),
+            b'C' | b'S' => self.a.op(Some(0x66), false, &[0x89], r as u8, m),
+            b'I' | b'F' => self.a.op(None, false, &[0x89], r as u8, m),
+            _ => self.a.op(None, true, &[0x89], r as u8, m),
+        }
+    }
+
+    // ints are zero extended in 64 bits slots
+    fn load_field(&mut self, r: Reg, m: Mem, slots: i32) {
+        if slots == 1 {
+            self.a.load32(r, m);
+        } else {
+            self.a.load64(r, m);
+        }
+    }
+}
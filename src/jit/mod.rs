@@ -0,0 +1,311 @@
+// baseline compiler of hot methods to x86-64
+// compiled code runs on the same frame slots as the interpreter and exits to it
+// before any instruction it doesn't handle, so both tiers can run one method
+#[cfg(all(target_arch = "x86_64", unix))]
+mod asm;
+#[cfg(all(target_arch = "x86_64", unix))]
+mod compiler;
+
+use crate::heap::class::ClassMember;
+use crate::runtime::frame::JFrame;
+
+pub struct Jit {
+    pub enabled: bool,
+    // invocations or backward branches before a method is compiled
+    pub invoke_threshold: u32,
+    pub backedge_threshold: u32,
+    // number of compiled methods
+    pub compiled: u32,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit {
+            enabled: cfg!(all(target_arch = "x86_64", unix)),
+            invoke_threshold: 1000,
+            backedge_threshold: 10000,
+            compiled: 0,
+        }
+    }
+}
+
+impl Jit {
+    pub fn is_hot(&self, m: &ClassMember) -> bool {
+        self.enabled
+            && m.compiled.is_none()
+            && !m.access_flags.is_native()
+            && !m.code.is_empty()
+            && (m.invocations >= self.invoke_threshold || m.backedges >= self.backedge_threshold)
+    }
+
+    // compile m, the jit is disabled if executable memory is unavailable
+    pub fn compile(&mut self, m: &mut ClassMember) {
+        match Code::new(m) {
+            Some(c) => {
+                m.compiled = Some(Box::new(c));
+                self.compiled += 1;
+            }
+            None => self.enabled = false,
+        }
+    }
+}
+
+// state passed between interpreter and compiled code
+#[repr(C)]
+struct Context {
+    locals: *mut u64,
+    stack: *mut u64,
+    // operand stack depth
+    sp: u64,
+    // pc to enter at, and to resume interpreting at on return
+    pc: u64,
+    // backward branches left before compiled code returns
+    budget: i64,
+}
+
+// machine code of a method in executable memory
+pub struct Code {
+    mem: *mut u8,
+    len: usize,
+    // native address of each bytecode pc, read by the prologue
+    #[allow(dead_code)]
+    entries: Box<[u64]>,
+    // operand stack depth of each entry, compiled code isn't entered with another depth
+    depths: Vec<Option<u16>>,
+}
+
+#[cfg(all(target_arch = "x86_64", unix))]
+impl Code {
+    fn new(m: &mut ClassMember) -> Option<Self> {
+        let mut entries = vec![0u64; m.code.len()].into_boxed_slice();
+        let out = compiler::compile(m, entries.as_ptr());
+        let len = out.code.len();
+
+        let mem = unsafe {
+            let p = sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ | sys::PROT_WRITE,
+                sys::MAP_PRIVATE | sys::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if p as isize == -1 {
+                return None;
+            }
+            std::ptr::copy_nonoverlapping(out.code.as_ptr(), p, len);
+            if sys::mprotect(p, len, sys::PROT_READ | sys::PROT_EXEC) != 0 {
+                sys::munmap(p, len);
+                return None;
+            }
+            p
+        };
+
+        for (e, off) in entries.iter_mut().zip(out.entries.iter()) {
+            *e = mem as u64 + off.unwrap_or(out.exit) as u64;
+        }
+
+        Some(Code {
+            mem,
+            len,
+            entries,
+            depths: out.depths,
+        })
+    }
+
+    // run from pc of frame f until compiled code exits, returns the pc to resume interpreting at
+    // None if pc can't be entered with the current operand stack
+    pub fn run(&self, f: &mut JFrame, pc: u32, budget: &mut i64) -> Option<u32> {
+        if self.depths.get(pc as usize).copied().flatten() != Some(f.stack_size()) {
+            return None;
+        }
+
+        let (locals, stack) = f.slots();
+        let mut ctx = Context {
+            locals: locals.raw(),
+            stack: stack.raw(),
+            sp: f.stack_size() as u64,
+            pc: pc as u64,
+            budget: *budget,
+        };
+
+        let entry: extern "sysv64" fn(*mut Context) = unsafe { std::mem::transmute(self.mem) };
+        entry(&mut ctx);
+
+        *budget = ctx.budget;
+        f.set_stack_size(ctx.sp as u16);
+        Some(ctx.pc as u32)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", unix)))]
+impl Code {
+    fn new(_m: &mut ClassMember) -> Option<Self> {
+        None
+    }
+
+    pub fn run(&self, _f: &mut JFrame, _pc: u32, _budget: &mut i64) -> Option<u32> {
+        None
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        #[cfg(all(target_arch = "x86_64", unix))]
+        unsafe {
+            sys::munmap(self.mem, self.len);
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", unix))]
+mod sys {
+    pub const PROT_READ: i32 = 1;
+    pub const PROT_WRITE: i32 = 2;
+    pub const PROT_EXEC: i32 = 4;
+    pub const MAP_PRIVATE: i32 = 0x02;
+    #[cfg(target_os = "macos")]
+    pub const MAP_ANONYMOUS: i32 = 0x1000;
+    #[cfg(not(target_os = "macos"))]
+    pub const MAP_ANONYMOUS: i32 = 0x20;
+
+    extern "C" {
+        pub fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64)
+            -> *mut u8;
+        pub fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+        pub fn munmap(addr: *mut u8, len: usize) -> i32;
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64", unix))]
+mod test {
+    use super::Code;
+    use crate::heap::class::{Class, ClassMember, Header, Object};
+    use crate::heap::desc::DescriptorParser;
+    use crate::heap::misc::AccessFlags;
+    use crate::runtime::frame::JStack;
+    use rp::Rp;
+
+    fn method(desc: &str, code: Vec<u8>, stack: u16, locals: u16) -> ClassMember {
+        let mut m = ClassMember::default();
+        // static
+        m.access_flags = AccessFlags(0x0008);
+        m.desc = desc.into();
+        m.m_desc = DescriptorParser::new(desc.as_bytes()).parse_method();
+        m.code = code;
+        m.max_stack = stack;
+        m.max_locals = locals;
+        m.class = Rp::new(Class::default());
+        m
+    }
+
+    #[test]
+    fn loop_test() {
+        // int s = 0; for (int i = 0; i <= n; i++) s += i; return s;
+        let code = vec![
+            0x03, 0x3c, 0x03, 0x3d, // iconst_0 istore_1 iconst_0 istore_2
+            0x1c, 0x1a, 0xa3, 0x00, 0x0d, // 4: iload_2 iload_0 if_icmpgt 19
+            0x1b, 0x1c, 0x60, 0x3c, // iload_1 iload_2 iadd istore_1
+            0x84, 0x02, 0x01, // iinc 2 1
+            0xa7, 0xff, 0xf4, // goto 4
+            0x1b, 0xac, // 19: iload_1 ireturn
+        ];
+        let mut m = method("(I)I", code, 2, 3);
+        let code = Code::new(&mut m).unwrap();
+        let mut stack = JStack::new(Rp::null());
+        let mut f = stack.push_frame((&m).into());
+        f.local_vars()[0] = 100;
+
+        // out of budget at the loop head
+        let mut budget = 10;
+        assert_eq!(code.run(f.get_mut(), 0, &mut budget), Some(4));
+        assert_eq!((budget, f.local_vars()[1], f.local_vars()[2]), (0, 45, 10));
+
+        // ireturn is left to the interpreter
+        budget = 1000;
+        assert_eq!(code.run(f.get_mut(), 4, &mut budget), Some(20));
+        assert_eq!(f.operands(), &[5050]);
+        assert_eq!(budget, 1000 - 91);
+
+        // entered with another operand stack depth
+        assert_eq!(code.run(f.get_mut(), 4, &mut budget), None);
+    }
+
+    #[test]
+    fn deopt_test() {
+        // lload_0 lload_2 ldiv lreturn
+        let mut m = method("(JJ)J", vec![0x1e, 0x20, 0x6d, 0xad], 4, 4);
+        let code = Code::new(&mut m).unwrap();
+        let mut stack = JStack::new(Rp::null());
+        let mut f = stack.push_frame((&m).into());
+        let mut budget = 1;
+
+        f.local_vars()[0] = -7i64 as u64;
+        f.local_vars()[2] = 2;
+        assert_eq!(code.run(f.get_mut(), 0, &mut budget), Some(3));
+        assert_eq!(f.operands()[0] as i64, -3);
+
+        // division by zero is thrown by the interpreter
+        f.clear_stack();
+        f.local_vars()[2] = 0;
+        assert_eq!(code.run(f.get_mut(), 0, &mut budget), Some(2));
+        assert_eq!(f.operands().len(), 4);
+
+        // aload_1 iload_0 iaload ireturn
+        let mut m = method("(I[I)I", vec![0x2b, 0x1a, 0x2e, 0xac], 2, 2);
+        let code = Code::new(&mut m).unwrap();
+        let mut f = stack.push_frame((&m).into());
+        let data = vec![3u32, -4i32 as u32, 5];
+        let arr = Object {
+            header: Header::default(),
+            class: Rp::null(),
+            size: data.len(),
+            data: data.as_ptr() as usize,
+        };
+
+        f.local_vars()[1] = &arr as *const Object as u64;
+        f.local_vars()[0] = 1;
+        assert_eq!(code.run(f.get_mut(), 0, &mut budget), Some(3));
+        assert_eq!(f.operands(), &[-4i32 as u32 as u64]);
+
+        for i in [-1i32, 3] {
+            f.clear_stack();
+            f.local_vars()[0] = i as u32 as u64;
+            assert_eq!(code.run(f.get_mut(), 0, &mut budget), Some(2));
+            assert_eq!(f.operands().len(), 2);
+        }
+    }
+
+    #[test]
+    fn arith_test() {
+        let code = vec![
+            0x1a, 0x08, 0x7c, 0x36, 0x04, // iload_0 iconst_5 iushr istore 4
+            0x1f, 0x08, 0x7b, 0x37, 0x05, // lload_1 iconst_5 lshr lstore 5
+            0x25, 0x76, 0x8d, 0x39, 0x07, // fload_3 fneg f2d dstore 7
+            0x1f, 0x1f, 0x94, 0x36, 0x09, // lload_1 lload_1 lcmp istore 9
+            0x25, 0x0c, 0x95, 0x36, 0x0a, // fload_3 fconst_1 fcmpl istore 10
+            0x1a, 0x91, 0x36, 0x0b, // iload_0 i2b istore 11
+            0x1a, 0x87, 0x39, 0x0c, // iload_0 i2d dstore 12
+            0x03, 0x1a, 0x5a, 0x64, 0x64, 0x36,
+            0x0e, // iconst_0 iload_0 dup_x1 isub isub istore 14
+            0xb1, // return
+        ];
+        let mut m = method("(IJF)V", code, 4, 15);
+        let code = Code::new(&mut m).unwrap();
+        let mut stack = JStack::new(Rp::null());
+        let f = stack.push_frame((&m).into());
+        let l = f.local_vars();
+        l[0] = -100i32 as u32 as u64;
+        l[1] = -1_000_000i64 as u64;
+        l[3] = 2.5f32.to_bits() as u64;
+
+        assert_eq!(code.run(f.get_mut(), 0, &mut 1), Some(40));
+        assert_eq!(l[4], (-100i32 as u32 >> 5) as u64);
+        assert_eq!(l[5], (-1_000_000i64 >> 5) as u64);
+        assert_eq!(l[7], (-2.5f64).to_bits());
+        assert_eq!((l[9], l[10]), (0, 1));
+        assert_eq!(l[11], -100i32 as u32 as u64);
+        assert_eq!(l[12], (-100f64).to_bits());
+        assert_eq!(l[14], -200i32 as u32 as u64);
+    }
+}
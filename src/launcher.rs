@@ -0,0 +1,292 @@
+use err::StringErr;
+use jvm_rust::{Jvm, RunError};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const USAGE: &str = "Usage: jvm-rust [options] <mainclass> [args...]
+           (to execute a class)
+   or  jvm-rust [options] -jar <jarfile> [args...]
+           (to execute a jar file)
+where options include:
+    -cp <class search path of directories and zip/jar files>
+    -classpath <class search path of directories and zip/jar files>
+                  A : separated list of directories, JAR archives,
+                  and ZIP archives to search for class files.
+    -D<name>=<value>
+                  set a system property
+    -verbose:class
+                  print a line for each class loaded
+    -version      print product version and exit
+    -help -?      print this help message
+    -Xss<size>    set java thread stack size
+    -Xmx<size>    set maximum java heap size";
+
+// options of the java command line
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub class_path: Option<String>,
+    // -D properties in the order given
+    pub props: Vec<(String, String)>,
+    // main class, or the jar file with -jar
+    pub main: Option<String>,
+    pub jar: bool,
+    // arguments after the main class, passed to main
+    pub args: Vec<String>,
+    pub stack_size: Option<usize>,
+    pub max_heap: Option<usize>,
+    pub verbose_class: bool,
+    pub version: bool,
+    pub help: bool,
+}
+
+// parse launcher options, the first argument which is not an option is the main class
+pub fn parse(args: &[String]) -> Result<Options, StringErr> {
+    let mut o = Options::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let a = args[i].as_str();
+        i += 1;
+
+        if !a.starts_with('-') {
+            o.main = Some(a.to_string());
+            break;
+        }
+
+        match a {
+            "-cp" | "-classpath" | "--class-path" => {
+                if i == args.len() {
+                    return err!("{} requires class path specification", a);
+                }
+                o.class_path = Some(args[i].clone());
+                i += 1;
+            }
+            "-jar" => {
+                if i == args.len() {
+                    return err!("-jar requires jar file specification");
+                }
+                o.main = Some(args[i].clone());
+                o.jar = true;
+                i += 1;
+                break;
+            }
+            "-verbose" | "-verbose:class" => o.verbose_class = true,
+            "-version" => o.version = true,
+            "-help" | "-h" | "-?" => o.help = true,
+            _ if a.starts_with("-D") => {
+                let p = &a[2..];
+                let (k, v) = match p.find('=') {
+                    Some(n) => (&p[..n], &p[n + 1..]),
+                    None => (p, ""),
+                };
+                o.props.push((k.to_string(), v.to_string()));
+            }
+            _ if a.starts_with("-Xss") => match parse_size(&a[4..]) {
+                Some(n) => o.stack_size = Some(n),
+                None => return err!("Invalid thread stack size: {}", a),
+            },
+            _ if a.starts_with("-Xmx") => match parse_size(&a[4..]) {
+                Some(n) => o.max_heap = Some(n),
+                None => return err!("Invalid maximum heap size: {}", a),
+            },
+            _ => return err!("Unrecognized option: {}", a),
+        }
+    }
+
+    o.args = args[i..].to_vec();
+    Ok(o)
+}
+
+// 512k, 64m, 1g or bytes
+fn parse_size(s: &str) -> Option<usize> {
+    let (n, unit) = match s.as_bytes().last()? {
+        b'k' | b'K' => (&s[..s.len() - 1], 1 << 10),
+        b'm' | b'M' => (&s[..s.len() - 1], 1 << 20),
+        b'g' | b'G' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let n: usize = n.parse().ok()?;
+    n.checked_mul(unit).filter(|&b| b > 0)
+}
+
+// value of Main-Class in META-INF/MANIFEST.MF, continuation lines start with a space
+fn manifest_main_class(manifest: &str) -> Option<String> {
+    let mut value: Option<String> = None;
+
+    for line in manifest.lines() {
+        match value.as_mut() {
+            Some(v) if line.starts_with(' ') => v.push_str(&line[1..]),
+            Some(_) => break,
+            None => {
+                if let Some(v) = line.strip_prefix("Main-Class:") {
+                    value = Some(v.trim_start().to_string());
+                }
+            }
+        }
+    }
+    value.map(|v| v.trim_end().to_string())
+}
+
+fn jar_main_class(jar: &str) -> Result<String, StringErr> {
+    let file = match File::open(jar) {
+        Ok(f) => f,
+        Err(_) => return err!("Unable to access jarfile {}", jar),
+    };
+    let mut archive = match zip::ZipArchive::new(file) {
+        Ok(a) => a,
+        Err(_) => return err!("Invalid or corrupt jarfile {}", jar),
+    };
+
+    let mut manifest = String::new();
+    if let Ok(mut f) = archive.by_name("META-INF/MANIFEST.MF") {
+        f.read_to_string(&mut manifest)?;
+    }
+
+    match manifest_main_class(&manifest) {
+        Some(c) => Ok(c),
+        None => err!("no main manifest attribute, in {}", jar),
+    }
+}
+
+// rt.jar of JAVA_HOME, classes of the java library are looked up first
+fn boot_class_path(java_home: &str) -> Option<String> {
+    ["jre/lib/rt.jar", "lib/rt.jar"]
+        .iter()
+        .map(|p| Path::new(java_home).join(p))
+        .find(|p| p.is_file())
+        .and_then(|p| p.into_os_string().into_string().ok())
+}
+
+// run the java command line, return the exit status of the process
+pub fn run(args: &[String]) -> i32 {
+    let o = match parse(args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}", &*e);
+            eprintln!("Error: Could not create the Java Virtual Machine.");
+            return 1;
+        }
+    };
+
+    if o.version {
+        eprintln!("java version \"1.8.0\"");
+        eprintln!("jvm-rust {}", env!("CARGO_PKG_VERSION"));
+        return 0;
+    }
+
+    let main = match &o.main {
+        Some(m) if !o.help => m,
+        _ => {
+            eprintln!("{}", USAGE);
+            return if o.help { 0 } else { 1 };
+        }
+    };
+
+    // -jar ignores -cp and CLASSPATH
+    let (class, cp) = if o.jar {
+        match jar_main_class(main) {
+            Ok(c) => (c, main.clone()),
+            Err(e) => {
+                eprintln!("Error: {}", &*e);
+                return 1;
+            }
+        }
+    } else {
+        let cp = o
+            .class_path
+            .clone()
+            .or_else(|| std::env::var("CLASSPATH").ok())
+            .unwrap_or_else(|| ".".to_string());
+        (main.clone(), cp)
+    };
+
+    let java_home = std::env::var("JAVA_HOME").ok();
+    let boot = java_home.as_deref().and_then(boot_class_path);
+    let full = match &boot {
+        Some(b) => format!("{}:{}", b, cp),
+        None => cp.clone(),
+    };
+
+    let mut jvm = match Jvm::new(&full) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Error: {}", &*e);
+            return 1;
+        }
+    };
+
+    jvm.set_property("java.class.path", &cp);
+    if let Some(h) = &java_home {
+        jvm.set_property("java.home", h);
+    }
+    for (k, v) in o.props.iter() {
+        jvm.set_property(k, v);
+    }
+    if let Some(n) = o.stack_size {
+        jvm.set_stack_size(n);
+    }
+    if let Some(n) = o.max_heap {
+        jvm.set_max_heap(n);
+    }
+    jvm.set_verbose_class(o.verbose_class);
+
+    jvm.init();
+    match jvm.run_class_with_args(&class.replace('.', "/"), &o.args) {
+        Ok(status) => status,
+        Err(RunError::Load(e)) => {
+            eprintln!("Error: {}", &*e);
+            1
+        }
+        Err(RunError::Uncaught(_)) => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{manifest_main_class, parse, parse_size};
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn launcher_test() {
+        let o = parse(&args(
+            "-cp .:test/rt.jar -Dfoo=a=b -Dbar -Xss512k -Xmx64m -verbose:class test.Main x -cp",
+        ))
+        .unwrap();
+        assert_eq!(o.class_path.as_deref(), Some(".:test/rt.jar"));
+        assert_eq!(
+            o.props,
+            vec![
+                ("foo".to_string(), "a=b".to_string()),
+                ("bar".to_string(), "".to_string())
+            ]
+        );
+        assert_eq!(o.stack_size, Some(512 * 1024));
+        assert_eq!(o.max_heap, Some(64 * 1024 * 1024));
+        assert!(o.verbose_class && !o.jar);
+        assert_eq!(o.main.as_deref(), Some("test.Main"));
+        // options after the main class belong to the program
+        assert_eq!(o.args, args("x -cp"));
+
+        let o = parse(&args("-jar app.jar -version")).unwrap();
+        assert!(o.jar && !o.version);
+        assert_eq!(o.main.as_deref(), Some("app.jar"));
+        assert_eq!(o.args, args("-version"));
+
+        assert!(parse(&args("-Xss1x Main")).is_err());
+        assert!(parse(&args("-cp")).is_err());
+        assert!(parse(&args("-server Main")).is_err());
+        assert_eq!(parse_size("2g"), Some(2 << 30));
+        assert_eq!(parse_size("0"), None);
+
+        let m = "Manifest-Version: 1.0\r\nMain-Class: com.example.very.long.packag\r\n e.Main\r\nCreated-By: 1.8\r\n";
+        assert_eq!(
+            manifest_main_class(m).as_deref(),
+            Some("com.example.very.long.package.Main")
+        );
+        assert_eq!(manifest_main_class("Manifest-Version: 1.0\n"), None);
+    }
+}
@@ -0,0 +1,16 @@
+#![feature(unchecked_math)]
+#[macro_use]
+extern crate err;
+pub mod heap;
+mod ins;
+mod jit;
+mod natives;
+mod oop;
+mod op;
+pub mod runtime;
+
+pub use crate::heap::class::Object;
+pub use crate::natives::NativeEnv;
+pub use crate::runtime::value::{JValue, JavaException};
+pub use crate::runtime::vm::{Jvm, RunError};
+pub use rp::Rp;
@@ -1,24 +1,10 @@
-#![feature(unchecked_math)]
 #[macro_use]
 extern crate err;
-mod heap;
-mod ins;
-mod natives;
-mod oop;
-mod op;
-mod runtime;
+mod launcher;
 
 fn main() {
-    use crate::runtime::vm::Jvm;
-
-    let cp = match std::env::var("CLASSPATH") {
-        Ok(v) => v,
-        Err(_) => ".".to_string(),
-    };
-
-    let args: Vec<String> = std::env::args().collect();
-    let mut jvm = Jvm::new(&cp).unwrap();
-    jvm.init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(launcher::run(&args));
 }
 
 #[cfg(test)]
@@ -19,10 +19,9 @@ na!(
     f,
     {
         let s = f.this().jstring();
-        let mut cl = f.heap.loader.load(&s);
+        let cl = f.heap.loader.load(&s);
 
-        if cl.clinit(th) {
-            th.revert_pc();
+        if !th.init_class(cl) {
             return;
         }
 
@@ -104,15 +103,16 @@ na!(
         }
 
         for i in 0..fields.len() {
-            let mut o = Class::new_obj_size(field_class, field_class.ins_fields.len() + 1);
+            let mut o = Class::new_obj_size(field_class, field_class.ins_words() + 1);
             let p = o.ptr() as u64;
 
             // call init method of Field Constructor
             o.set_field_ref("clazz", class.j_class);
-            println!("field name = {}", &fields[i].name);
             o.set_field_ref("name", f.heap.new_jstr(&fields[i].name));
             o.set_field("slot", fields[i].id as u64);
             o.set_field("modifiers", fields[i].access_flags.0 as u64);
+            let t = f.heap.desc_class(&fields[i].desc).j_class;
+            o.set_field_ref("type", t);
             o.fields()[o.fields().len() - 1] = fields[i].ptr() as u64;
             field_arr.set(i, p);
         }
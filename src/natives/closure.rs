@@ -0,0 +1,144 @@
+use crate::heap::class::Object;
+use crate::heap::desc::JType;
+use crate::natives::NativeMethod;
+use crate::runtime::frame::JFrame;
+use crate::runtime::value::{JValue, JavaException};
+use crate::runtime::vm::JThread;
+use rp::Rp;
+
+// rust closure run as a native method, instance methods receive this first and errors are thrown
+pub type NativeFn = dyn Fn(&mut NativeEnv, &[JValue]) -> Result<JValue, JavaException>;
+
+// thread a closure native runs on
+pub struct NativeEnv<'a> {
+    pub thread: &'a mut JThread,
+}
+
+impl<'a> NativeEnv<'a> {
+    // java.lang.String of s, it is not interned
+    pub fn new_string(&mut self, s: &str) -> Rp<Object> {
+        self.thread.heap.create_jstr(s)
+    }
+
+    pub fn get_string(&self, o: Rp<Object>) -> Option<String> {
+        self.thread.heap.get_jstr(o)
+    }
+
+    // keep o alive across calls into java until it is unpinned, see Jvm::pin
+    pub fn pin(&mut self, o: Rp<Object>) {
+        self.thread.heap.pin(o);
+    }
+
+    pub fn unpin(&mut self, o: Rp<Object>) {
+        self.thread.heap.unpin(o);
+    }
+
+    // throwable of class to return as error
+    pub fn exception(&mut self, class: &str, msg: &str) -> JavaException {
+        JavaException(self.thread.new_throwable(class, Some(msg)))
+    }
+
+    // call static method of class, the class is initialized first
+    pub fn call_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        desc: &str,
+        args: &[JValue],
+    ) -> Result<JValue, JavaException> {
+        let c = match self.thread.heap.loader.try_load(class) {
+            Some(c) => c,
+            None => return Err(self.exception("java/lang/NoClassDefFoundError", class)),
+        };
+        let m = c.lookup_method(name, desc);
+        if m.is_null() || !m.access_flags.is_static() {
+            let msg = format!("{}.{}{}", class.replace('/', "."), name, desc);
+            return Err(self.exception("java/lang/NoSuchMethodError", &msg));
+        }
+        self.thread.call(m, args)
+    }
+
+    // call method of the class of o, args don't include o
+    pub fn call_method(
+        &mut self,
+        o: Rp<Object>,
+        name: &str,
+        desc: &str,
+        args: &[JValue],
+    ) -> Result<JValue, JavaException> {
+        if o.is_null() {
+            let ex = self
+                .thread
+                .new_throwable("java/lang/NullPointerException", None);
+            return Err(JavaException(ex));
+        }
+
+        let m = o.class.lookup_method(name, desc);
+        if m.is_null() || m.access_flags.is_static() {
+            let msg = format!("{}.{}{}", o.class.name.replace('/', "."), name, desc);
+            return Err(self.exception("java/lang/NoSuchMethodError", &msg));
+        }
+
+        let mut full = vec![JValue::Object(o)];
+        full.extend_from_slice(args);
+        self.thread.call(m, &full)
+    }
+}
+
+pub struct ClosureNative {
+    pub class: String,
+    pub name: String,
+    pub desc: String,
+    pub f: Box<NativeFn>,
+}
+
+impl NativeMethod for ClosureNative {
+    fn class_name(&self) -> &str {
+        &self.class
+    }
+
+    fn method_name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    // decode arguments from local slots and push the value returned
+    fn exec(&self, th: &mut JThread, f: &mut JFrame) {
+        let m = f.method;
+        let slots = f.local_vars();
+        let mut args = Vec::with_capacity(m.m_desc.params.len() + 1);
+        let mut i = 0;
+
+        if !m.access_flags.is_static() {
+            args.push(JValue::Object(f.this()));
+            i = 1;
+        }
+        for t in m.m_desc.params.iter() {
+            args.push(JValue::from_type(t, slots[i]));
+            i += match t {
+                JType::DJ(_) => 2,
+                _ => 1,
+            };
+        }
+
+        let r = (self.f)(&mut NativeEnv { thread: th }, &args);
+        match r {
+            Ok(v) if v.is_type(&m.m_desc.ret) => match m.m_desc.ret {
+                JType::V => {}
+                JType::DJ(_) => f.push_u64(v.slot()),
+                _ => f.push_slot(v.slot()),
+            },
+            Ok(v) => {
+                let msg = format!(
+                    "native {}.{}{} returned {:?}",
+                    &self.class, &self.name, &self.desc, v
+                );
+                th.throw_new("java/lang/InternalError", Some(&msg));
+            }
+            Err(e) => th.throw(e.throwable()),
+        }
+    }
+}
@@ -0,0 +1,806 @@
+use crate::heap::class::{Class, ClassMember, Object};
+use crate::heap::desc::{DescriptorParser, JType, MethodDescriptor};
+use crate::heap::invoke::{method_type_desc, mn_flags};
+use crate::heap::lambda::box_of;
+use crate::heap::misc::ref_kind;
+use crate::runtime::frame::JFrame;
+use crate::runtime::value::JValue;
+use crate::runtime::vm::JThread;
+use rp::Rp;
+
+fn arg(f: &JFrame, i: usize) -> Rp<Object> {
+    (f.local_vars()[i] as usize).into()
+}
+
+fn is_static(kind: u8) -> bool {
+    matches!(
+        kind,
+        ref_kind::GET_STATIC | ref_kind::PUT_STATIC | ref_kind::INVOKE_STATIC
+    )
+}
+
+fn is_field(kind: u8) -> bool {
+    kind <= ref_kind::PUT_STATIC
+}
+
+// Lookup.findXXX(refc, name, type), push a direct method handle or throw
+fn find(th: &mut JThread, f: &mut JFrame, kind: u8, name: Rp<Object>, desc: &str) {
+    let refc = arg(f, 1);
+
+    if refc.is_null() || name.is_null() {
+        th.throw_npe();
+        return;
+    }
+
+    let class = refc.extra_class();
+    let name = name.jstring();
+    let m = f.heap.link_handle_member(kind, class, &name, desc);
+
+    let found = !m.is_null()
+        && m.access_flags.is_static() == is_static(kind)
+        && (kind != ref_kind::NEW_INVOKE_SPECIAL || m.class.id == class.id);
+
+    if !found {
+        let (e, what) = if is_field(kind) {
+            ("java/lang/NoSuchFieldException", "field")
+        } else {
+            ("java/lang/NoSuchMethodException", "method")
+        };
+        let msg = format!(
+            "no such {}: {}.{}{}",
+            what,
+            class.name.replace('/', "."),
+            name,
+            desc
+        );
+        th.throw_new(e, Some(&msg));
+        return;
+    }
+
+    let mh = f.heap.new_method_handle(kind, class, m);
+    f.push_obj(mh);
+}
+
+// findVirtual, findStatic, findSpecial: (refc, name, MethodType)
+fn find_method(th: &mut JThread, f: &mut JFrame, kind: u8) {
+    let mt = arg(f, 3);
+    if mt.is_null() {
+        th.throw_npe();
+        return;
+    }
+    let desc = method_type_desc(&mt);
+    find(th, f, kind, arg(f, 2), &desc)
+}
+
+// findGetter, findSetter, findStaticGetter, findStaticSetter: (refc, name, Class)
+fn find_field(th: &mut JThread, f: &mut JFrame, kind: u8) {
+    let t = arg(f, 3);
+    if t.is_null() {
+        th.throw_npe();
+        return;
+    }
+    let desc = t.extra_class().desc;
+    find(th, f, kind, arg(f, 2), &desc)
+}
+
+fn push_value(f: &mut JFrame, desc: &str, v: u64) {
+    match desc {
+        "J" | "D" => f.push_u64(v),
+        _ => f.push_slot(v),
+    }
+}
+
+// push a frame of m, or with nested run m here and push its result on the stack of f
+// return true if the result is on the stack of f
+fn call(th: &mut JThread, f: &mut JFrame, m: Rp<ClassMember>, args: &[u64], nested: bool) -> bool {
+    if !nested {
+        let new_frame = th.push_frame(m);
+        new_frame.local_vars()[..args.len()].copy_from_slice(args);
+        return false;
+    }
+
+    let mut vals = Vec::with_capacity(m.m_desc.params.len() + 1);
+    let mut i = 0;
+    if !m.access_flags.is_static() {
+        vals.push(JValue::from_slot(b'L', args[0]));
+        i = 1;
+    }
+    for t in m.m_desc.params.iter() {
+        vals.push(JValue::from_type(t, args[i]));
+        i += t.slots() as usize;
+    }
+
+    match th.call(m, &vals) {
+        Ok(JValue::Void) => true,
+        Ok(v) => {
+            push_value(f, &m.m_desc.ret.descriptor(), v.slot());
+            true
+        }
+        Err(e) => {
+            th.throw(e.throwable());
+            false
+        }
+    }
+}
+
+// invoke member linked to a MemberName, arguments are in slots
+// the result is left on the stack of the invoker frame which returns it to the caller
+// return true if the member completed here and its result is on the stack of f
+fn invoke_member(
+    th: &mut JThread,
+    f: &mut JFrame,
+    mn: Rp<Object>,
+    args: Vec<u64>,
+    nested: bool,
+) -> bool {
+    use mn_flags::*;
+
+    if mn.is_null() {
+        th.throw_new(
+            "java/lang/UnsupportedOperationException",
+            Some("method handle is not direct"),
+        );
+        return false;
+    }
+
+    let flags = mn.get_field("flags") as u32;
+    let kind = ((flags >> REFERENCE_KIND_SHIFT) & REFERENCE_KIND_MASK) as u8;
+    let mut m: Rp<ClassMember> = (mn.get_field("vmtarget") as usize).into();
+
+    if (is_static(kind) || kind == ref_kind::NEW_INVOKE_SPECIAL) && !th.init_class(m.class) {
+        return false;
+    }
+
+    let recv: Rp<Object> = if is_static(kind) || kind == ref_kind::NEW_INVOKE_SPECIAL {
+        Rp::null()
+    } else {
+        let r: Rp<Object> = (args[0] as usize).into();
+        if r.is_null() {
+            th.throw_npe();
+            return false;
+        }
+        r
+    };
+
+    match kind {
+        ref_kind::GET_FIELD => push_value(f, &m.desc, recv.load(m.offset, m.field_type())),
+        ref_kind::PUT_FIELD => recv.get_mut().store(m.offset, m.field_type(), args[1]),
+        ref_kind::GET_STATIC => push_value(f, &m.desc, m.class.get_static(m.id)),
+        ref_kind::PUT_STATIC => m.class.get_mut().set_static(m.id, args[0]),
+        ref_kind::NEW_INVOKE_SPECIAL => {
+            let o = Class::new_obj(m.class);
+            f.push_obj(o);
+            let mut all = vec![o.ptr() as u64];
+            all.extend_from_slice(&args);
+            return call(th, f, m, &all, nested);
+        }
+        _ => {
+            if kind == ref_kind::INVOKE_VIRTUAL || kind == ref_kind::INVOKE_INTERFACE {
+                m = match recv.class.select_method(m) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        th.throw_new(e.class, Some(&e.msg));
+                        return false;
+                    }
+                };
+            }
+            return call(th, f, m, &args, nested);
+        }
+    }
+    true
+}
+
+fn method_desc(d: &MethodDescriptor) -> String {
+    let params: String = d.params.iter().map(|t| t.descriptor()).collect();
+    format!("({}){}", params, d.ret.descriptor())
+}
+
+// widening primitive conversion of v to primitive type t, None if not allowed
+fn widen(v: JValue, t: &JType) -> Option<JValue> {
+    let t = match t {
+        JType::FI(c) | JType::DJ(c) => *c as u8,
+        _ => return None,
+    };
+    if v.type_char() == t {
+        return Some(v);
+    }
+
+    match (v, t) {
+        (JValue::Boolean(_), _) | (JValue::Double(_), _) => None,
+        (JValue::Long(l), b'F') => Some(JValue::Float(l as f32)),
+        (JValue::Long(l), b'D') => Some(JValue::Double(l as f64)),
+        (JValue::Float(x), b'D') => Some(JValue::Double(x as f64)),
+        (JValue::Long(_), _) | (JValue::Float(_), _) => None,
+        (JValue::Byte(b), b'S') => Some(JValue::Short(b as i16)),
+        (_, b'I') => v.as_int().map(JValue::Int),
+        (_, b'J') => v.as_int().map(|i| JValue::Long(i as i64)),
+        (_, b'F') => v.as_int().map(|i| JValue::Float(i as f32)),
+        (_, b'D') => v.as_int().map(|i| JValue::Double(i as f64)),
+        _ => None,
+    }
+}
+
+// primitive type boxed by class
+fn unboxed(class: &str) -> Option<char> {
+    ['Z', 'B', 'C', 'S', 'I', 'J', 'F', 'D']
+        .iter()
+        .copied()
+        .find(|&p| box_of(p).0 == class)
+}
+
+// invoke may pass a value of type `from` as type `to`
+// references are cast or unboxed when converted, a mismatch throws ClassCastException then
+fn convertible(from: &JType, to: &JType) -> bool {
+    match (from, to) {
+        _ if from == to => true,
+        (_, JType::V) | (JType::V, _) | (JType::A(_), JType::A(_)) => true,
+        (JType::A(t), _) => match t.as_str() {
+            "java/lang/Object"
+            | "java/io/Serializable"
+            | "java/lang/Comparable"
+            | "java/lang/Number" => true,
+            _ => unboxed(t)
+                .and_then(|p| widen(JValue::from_slot(p as u8, 0), to))
+                .is_some(),
+        },
+        (JType::FI(c) | JType::DJ(c), JType::A(t)) => {
+            let (b, _) = box_of(*c);
+            match t.as_str() {
+                "java/lang/Object" | "java/io/Serializable" | "java/lang/Comparable" => true,
+                "java/lang/Number" => !matches!(c, 'Z' | 'C'),
+                _ => t == b,
+            }
+        }
+        (JType::FI(c) | JType::DJ(c), _) => widen(JValue::from_slot(*c as u8, 0), to).is_some(),
+    }
+}
+
+// value converted to type t as MethodHandle.asType does, None if an exception is thrown
+fn convert(th: &mut JThread, v: JValue, t: &JType) -> Option<JValue> {
+    let o = match (v, t) {
+        (_, JType::V) => return Some(JValue::Void),
+        (JValue::Void, _) => return Some(JValue::from_type(t, 0)),
+        (JValue::Object(o), _) => o,
+        // box with valueOf to share its cache
+        (_, JType::A(_)) => {
+            let c = v.type_char() as char;
+            let (b, _) = box_of(c);
+            let m = th
+                .heap
+                .loader
+                .load(b)
+                .lookup_method("valueOf", format!("({})L{};", c, b));
+            return match th.call(m, &[v]) {
+                Ok(o) => Some(o),
+                Err(e) => {
+                    th.throw(e.throwable());
+                    None
+                }
+            };
+        }
+        _ => return widen(v, t),
+    };
+
+    let to = match t {
+        JType::A(name) if o.is_null() || name == "java/lang/Object" => return Some(v),
+        JType::A(name) => {
+            let c = th.heap.loader.load(name);
+            if o.instance_of(&c) {
+                return Some(v);
+            }
+            name.replace('/', ".")
+        }
+        JType::V => return Some(JValue::Void),
+        // unbox, then widen
+        JType::FI(c) | JType::DJ(c) => {
+            if o.is_null() {
+                th.throw_npe();
+                return None;
+            }
+            let p = unboxed(o.class.name.as_str());
+            if let Some(v) =
+                p.and_then(|p| widen(JValue::from_slot(p as u8, o.get_field("value")), t))
+            {
+                return Some(v);
+            }
+            box_of(*c).0.replace('/', ".")
+        }
+    };
+
+    let msg = format!(
+        "{} cannot be cast to {}",
+        o.class.name.replace('/', "."),
+        to
+    );
+    th.throw_new("java/lang/ClassCastException", Some(&msg));
+    None
+}
+
+// invokeExact, invoke, invokeBasic: the receiver is the method handle
+// invokeExact requires the descriptor of call site to be the type of handle,
+// the others convert arguments and result between them
+fn invoke_handle(th: &mut JThread, f: &mut JFrame, exact: bool) {
+    let mh = f.this();
+
+    if mh.is_null() {
+        th.throw_npe();
+        return;
+    }
+
+    let mt: Rp<Object> = (mh.get_field("type") as usize).into();
+    let mn: Rp<Object> = (mh.get_field("member") as usize).into();
+    let m = f.method;
+    let site = &m.m_desc;
+    let desc = method_type_desc(&mt);
+    let target = DescriptorParser::new(desc.as_bytes()).parse_method();
+    let n = site.arg_slots as usize;
+    let args = f.local_vars()[1..1 + n].to_vec();
+
+    if site.params == target.params && site.ret == target.ret {
+        invoke_member(th, f, mn, args, false);
+        return;
+    }
+
+    let ok = !exact
+        && site.params.len() == target.params.len()
+        && site
+            .params
+            .iter()
+            .zip(target.params.iter())
+            .all(|(a, b)| convertible(a, b))
+        && convertible(&target.ret, &site.ret);
+    if !ok {
+        let msg = format!("expected {} but found {}", desc, method_desc(site));
+        th.throw_new("java/lang/invoke/WrongMethodTypeException", Some(&msg));
+        return;
+    }
+
+    // boxed arguments are only reachable from here until the member gets them
+    let mut boxed = Vec::new();
+    let mut adapted = Vec::with_capacity(target.arg_slots as usize);
+    let mut i = 0;
+    for (from, to) in site.params.iter().zip(target.params.iter()) {
+        let v = match convert(th, JValue::from_type(from, args[i]), to) {
+            Some(v) => v,
+            None => break,
+        };
+        if let Some(o) = v.as_object().filter(|o| !o.is_null()) {
+            th.heap.pin(o);
+            boxed.push(o);
+        }
+        adapted.push(v.slot());
+        if to.slots() == 2 {
+            adapted.push(0);
+        }
+        i += from.slots() as usize;
+    }
+
+    let done = adapted.len() == target.arg_slots as usize
+        && invoke_member(th, f, mn, adapted, site.ret != target.ret);
+    for o in boxed {
+        th.heap.unpin(o);
+    }
+    // the result of the member run here is converted for the call site
+    if !done || site.ret == target.ret {
+        return;
+    }
+    let r = match target.ret {
+        JType::V => JValue::Void,
+        JType::DJ(_) => JValue::from_type(&target.ret, f.pop_u64()),
+        _ => JValue::from_type(&target.ret, f.pop_slot()),
+    };
+    match convert(th, r, &site.ret) {
+        Some(JValue::Void) | None => {}
+        Some(v) => push_value(f, &site.ret.descriptor(), v.slot()),
+    }
+}
+
+// linkToXXX: the trailing argument is the member name
+fn link_to(th: &mut JThread, f: &mut JFrame) {
+    let n = f.method.m_desc.arg_slots as usize;
+    let args = f.local_vars()[..n - 1].to_vec();
+    let mn = arg(f, n - 1);
+    invoke_member(th, f, mn, args, false);
+}
+
+na!(
+    MHInvokeExact,
+    "java/lang/invoke/MethodHandle",
+    "invokeExact",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { invoke_handle(th, f, true) }
+);
+
+na!(
+    MHInvoke,
+    "java/lang/invoke/MethodHandle",
+    "invoke",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { invoke_handle(th, f, false) }
+);
+
+na!(
+    MHInvokeBasic,
+    "java/lang/invoke/MethodHandle",
+    "invokeBasic",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { invoke_handle(th, f, false) }
+);
+
+na!(
+    MHLinkToVirtual,
+    "java/lang/invoke/MethodHandle",
+    "linkToVirtual",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { link_to(th, f) }
+);
+
+na!(
+    MHLinkToStatic,
+    "java/lang/invoke/MethodHandle",
+    "linkToStatic",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { link_to(th, f) }
+);
+
+na!(
+    MHLinkToSpecial,
+    "java/lang/invoke/MethodHandle",
+    "linkToSpecial",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { link_to(th, f) }
+);
+
+na!(
+    MHLinkToInterface,
+    "java/lang/invoke/MethodHandle",
+    "linkToInterface",
+    "([Ljava/lang/Object;)Ljava/lang/Object;",
+    th,
+    f,
+    { link_to(th, f) }
+);
+
+// intrinsics of java methods, see INTRINSICS in loader
+na!(
+    MHLookup,
+    "java/lang/invoke/MethodHandles",
+    "lookup",
+    "()Ljava/lang/invoke/MethodHandles$Lookup;",
+    th,
+    f,
+    {
+        // full power lookup: PUBLIC | PRIVATE | PROTECTED | PACKAGE
+        let caller = th.back_frame(2).class();
+        let c = f.heap.loader.load("java/lang/invoke/MethodHandles$Lookup");
+        let mut o = Class::new_obj(c);
+        o.set_field_ref("lookupClass", caller.j_class);
+        o.set_field("allowedModes", 0x0f);
+        f.push_obj(o)
+    }
+);
+
+na!(
+    LookupFindVirtual,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findVirtual",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    {
+        let refc = arg(f, 1);
+        let kind = if !refc.is_null() && refc.extra_class().access_flags.is_iface() {
+            ref_kind::INVOKE_INTERFACE
+        } else {
+            ref_kind::INVOKE_VIRTUAL
+        };
+        find_method(th, f, kind)
+    }
+);
+
+na!(
+    LookupFindStatic,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findStatic",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_method(th, f, ref_kind::INVOKE_STATIC) }
+);
+
+na!(
+    LookupFindSpecial,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findSpecial",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_method(th, f, ref_kind::INVOKE_SPECIAL) }
+);
+
+na!(
+    LookupFindConstructor,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findConstructor",
+    "(Ljava/lang/Class;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    {
+        let mt = arg(f, 2);
+        if mt.is_null() {
+            th.throw_npe();
+            return;
+        }
+        let desc = method_type_desc(&mt);
+        let name = f.heap.new_jstr("<init>");
+        find(th, f, ref_kind::NEW_INVOKE_SPECIAL, name, &desc)
+    }
+);
+
+na!(
+    LookupFindGetter,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findGetter",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_field(th, f, ref_kind::GET_FIELD) }
+);
+
+na!(
+    LookupFindSetter,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findSetter",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_field(th, f, ref_kind::PUT_FIELD) }
+);
+
+na!(
+    LookupFindStaticGetter,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findStaticGetter",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_field(th, f, ref_kind::GET_STATIC) }
+);
+
+na!(
+    LookupFindStaticSetter,
+    "java/lang/invoke/MethodHandles$Lookup",
+    "findStaticSetter",
+    "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+    th,
+    f,
+    { find_field(th, f, ref_kind::PUT_STATIC) }
+);
+
+na!(
+    MTMakeImpl,
+    "java/lang/invoke/MethodType",
+    "makeImpl",
+    "(Ljava/lang/Class;[Ljava/lang/Class;Z)Ljava/lang/invoke/MethodType;",
+    th,
+    f,
+    {
+        let rtype = arg(f, 0);
+        let ptypes = arg(f, 1);
+
+        if rtype.is_null() || ptypes.is_null() || ptypes.fields().contains(&0) {
+            th.throw_npe();
+            return;
+        }
+
+        // untrusted array may be modified by caller later
+        let trusted = f.local_vars()[2] != 0;
+        let ptypes = if trusted {
+            ptypes
+        } else {
+            let a = f.heap.new_array("java/lang/Class", ptypes.size);
+            a.fields().copy_from_slice(ptypes.fields());
+            a
+        };
+
+        let mt = f.heap.make_method_type(rtype, ptypes);
+        f.push_obj(mt)
+    }
+);
+
+na!(
+    MHNReg,
+    "java/lang/invoke/MethodHandleNatives",
+    "registerNatives",
+    "()V",
+    th,
+    _f,
+    { reg!(th.registry, N0, N1, N2, N3, N4, N5, N6, N7) }
+);
+
+na!(
+    N0,
+    "java/lang/invoke/MethodHandleNatives",
+    "getConstant",
+    "(I)I",
+    _th,
+    f,
+    { f.push_u32(0) }
+);
+
+na!(
+    N1,
+    "java/lang/invoke/MethodHandleNatives",
+    "resolve",
+    "(Ljava/lang/invoke/MemberName;Ljava/lang/Class;)Ljava/lang/invoke/MemberName;",
+    th,
+    f,
+    {
+        use mn_flags::*;
+        let mut mn = arg(f, 0);
+
+        if mn.is_null() {
+            th.throw_npe();
+            return;
+        }
+
+        let flags = mn.get_field("flags") as u32;
+        let kind = ((flags >> REFERENCE_KIND_SHIFT) & REFERENCE_KIND_MASK) as u8;
+        let clazz: Rp<Object> = (mn.get_field("clazz") as usize).into();
+        let name: Rp<Object> = (mn.get_field("name") as usize).into();
+        let t: Rp<Object> = (mn.get_field("type") as usize).into();
+
+        if clazz.is_null() || name.is_null() || t.is_null() {
+            th.throw_new(
+                "java/lang/IllegalArgumentException",
+                Some("nothing to resolve"),
+            );
+            return;
+        }
+
+        // type is a Class for fields, MethodType or descriptor string for methods
+        let desc = if t.class.name == "java/lang/Class" {
+            t.extra_class().desc.to_string()
+        } else if t.class.name == "java/lang/String" {
+            t.jstring()
+        } else {
+            method_type_desc(&t)
+        };
+
+        let name = name.jstring();
+        let m = f
+            .heap
+            .link_handle_member(kind, clazz.extra_class(), &name, &desc);
+
+        if m.is_null() {
+            let e = if flags & IS_FIELD != 0 {
+                "java/lang/NoSuchFieldError"
+            } else {
+                "java/lang/NoSuchMethodError"
+            };
+            th.throw_new(e, Some(&name));
+            return;
+        }
+
+        mn.set_field("flags", (flags | m.access_flags.0 as u32) as u64);
+        mn.set_field("vmtarget", m.ptr() as u64);
+        f.push_obj(mn)
+    }
+);
+
+na!(
+    N2,
+    "java/lang/invoke/MethodHandleNatives",
+    "init",
+    "(Ljava/lang/invoke/MemberName;Ljava/lang/Object;)V",
+    th,
+    f,
+    {
+        use mn_flags::*;
+        let mut mn = arg(f, 0);
+        let r = arg(f, 1);
+
+        if mn.is_null() || r.is_null() {
+            th.throw_npe();
+            return;
+        }
+
+        // only fields carry their member, see getDeclaredFields0
+        if r.class.name != "java/lang/reflect/Field" {
+            th.throw_new("java/lang/InternalError", Some(&r.class.name));
+            return;
+        }
+
+        let m = r.extra_member();
+        let kind = if m.access_flags.is_static() {
+            ref_kind::GET_STATIC
+        } else {
+            ref_kind::GET_FIELD
+        };
+        let flags = m.access_flags.0 as u32 | IS_FIELD | ((kind as u32) << REFERENCE_KIND_SHIFT);
+        let t = f.heap.desc_class(&m.desc).j_class;
+        mn.set_field_ref("clazz", m.class.j_class);
+        mn.set_field_ref("name", f.heap.new_jstr(&m.name));
+        mn.set_field_ref("type", t);
+        mn.set_field("flags", flags as u64);
+        mn.set_field("vmtarget", m.ptr() as u64);
+    }
+);
+
+na!(
+    N3,
+    "java/lang/invoke/MethodHandleNatives",
+    "objectFieldOffset",
+    "(Ljava/lang/invoke/MemberName;)J",
+    _th,
+    f,
+    {
+        let m: Rp<ClassMember> = (arg(f, 0).get_field("vmtarget") as usize).into();
+        f.push_u64(m.offset as u64)
+    }
+);
+
+na!(
+    N4,
+    "java/lang/invoke/MethodHandleNatives",
+    "staticFieldOffset",
+    "(Ljava/lang/invoke/MemberName;)J",
+    _th,
+    f,
+    {
+        use crate::natives::sun::STATIC_OFFSET;
+        let m: Rp<ClassMember> = (arg(f, 0).get_field("vmtarget") as usize).into();
+        f.push_u64(m.id as u64 | STATIC_OFFSET)
+    }
+);
+
+na!(
+    N5,
+    "java/lang/invoke/MethodHandleNatives",
+    "staticFieldBase",
+    "(Ljava/lang/invoke/MemberName;)Ljava/lang/Object;",
+    _th,
+    f,
+    {
+        let m: Rp<ClassMember> = (arg(f, 0).get_field("vmtarget") as usize).into();
+        f.push_obj(m.class.j_class)
+    }
+);
+
+na!(
+    N6,
+    "java/lang/invoke/MethodHandleNatives",
+    "setCallSiteTargetNormal",
+    "(Ljava/lang/invoke/CallSite;Ljava/lang/invoke/MethodHandle;)V",
+    _th,
+    f,
+    {
+        let mut cs = arg(f, 0);
+        cs.set_field_ref("target", arg(f, 1))
+    }
+);
+
+na!(
+    N7,
+    "java/lang/invoke/MethodHandleNatives",
+    "setCallSiteTargetVolatile",
+    "(Ljava/lang/invoke/CallSite;Ljava/lang/invoke/MethodHandle;)V",
+    _th,
+    f,
+    {
+        let mut cs = arg(f, 0);
+        cs.set_field_ref("target", arg(f, 1))
+    }
+);
@@ -35,7 +35,9 @@ macro_rules! reg {
 }
 
 mod class;
+mod closure;
 mod debug;
+mod invoke;
 mod object;
 mod sun;
 mod system;
@@ -45,7 +47,12 @@ use crate::runtime::frame::JFrame;
 use crate::runtime::vm::JThread;
 use std::collections::BTreeMap;
 
+pub use crate::natives::closure::{ClosureNative, NativeEnv};
+
+use crate::heap::class::ClassMember;
 use crate::heap::desc::{DescriptorParser, MethodDescriptor};
+use cp::Symbol;
+use rp::Rp;
 
 pub trait NativeMethod {
     fn class_name(&self) -> &str;
@@ -60,36 +67,73 @@ pub struct NativeMethodW {
 }
 
 pub struct NativeRegistry {
-    data: BTreeMap<String, NativeMethodW>,
+    // replaced natives are kept alive, members bound to them rebind on epoch change
+    // keyed by class, method name and descriptor
+    data: BTreeMap<(Symbol, Symbol, Symbol), Rp<NativeMethodW>>,
+    // bumped by every registration
+    pub epoch: u32,
 }
 
 impl NativeRegistry {
     pub fn new() -> Self {
         let mut r = NativeRegistry {
             data: BTreeMap::new(),
+            epoch: 0,
         };
         use crate::natives::class::ClassReg;
         use crate::natives::debug::DebugReg;
+        use crate::natives::invoke::{
+            LookupFindConstructor, LookupFindGetter, LookupFindSetter, LookupFindSpecial,
+            LookupFindStatic, LookupFindStaticGetter, LookupFindStaticSetter, LookupFindVirtual,
+            MHInvoke, MHInvokeBasic, MHInvokeExact, MHLinkToInterface, MHLinkToSpecial,
+            MHLinkToStatic, MHLinkToVirtual, MHLookup, MHNReg, MTMakeImpl,
+        };
         use crate::natives::object::JLOReg;
-        use crate::natives::sun::UnsafeReg;
-        use crate::natives::system::JLSReg;
+        use crate::natives::sun::{UnsafeReg, CS8};
+        use crate::natives::system::{
+            JLSReg, ShutdownBeforeHalt, ShutdownHalt, ShutdownRunFinalizers,
+        };
         use crate::natives::thread::ThreadReg;
         reg!(
-            r, DebugReg, JLOReg, JLSReg, ClassReg, ThreadReg,
+            r, DebugReg, JLOReg, JLSReg, ClassReg, ThreadReg, CS8,
             // ReflectCallerClass,
             UnsafeReg // ACGetCtx,
                       // ACDopri,
                       // ACDopri2
         );
+
+        // method handles are invoked without registerNatives
+        reg!(
+            r,
+            MHNReg,
+            MHInvokeExact,
+            MHInvoke,
+            MHInvokeBasic,
+            MHLinkToVirtual,
+            MHLinkToStatic,
+            MHLinkToSpecial,
+            MHLinkToInterface,
+            MHLookup,
+            LookupFindVirtual,
+            LookupFindStatic,
+            LookupFindSpecial,
+            LookupFindConstructor,
+            LookupFindGetter,
+            LookupFindSetter,
+            LookupFindStaticGetter,
+            LookupFindStaticSetter,
+            MTMakeImpl
+        );
+        reg!(r, ShutdownBeforeHalt, ShutdownHalt, ShutdownRunFinalizers);
         r
     }
 
-    #[inline]
-    fn hash(&self, class: &str, method: &str, desc: &str) -> String {
-        format!("{}_{}_{}", class, method, desc)
-    }
     pub fn register(&mut self, native: Box<dyn NativeMethod>) {
-        let h = self.hash(&native.class_name(), &native.method_name(), &native.desc());
+        let h = (
+            native.class_name().into(),
+            native.method_name().into(),
+            native.desc().into(),
+        );
         let mut parser = DescriptorParser::new(native.desc().as_bytes());
         let desc = parser.parse_method();
 
@@ -98,16 +142,69 @@ impl NativeRegistry {
             desc: desc,
         };
 
-        self.data.insert(h, o);
+        self.data.insert(h, Rp::new(o));
+        self.epoch += 1;
+    }
+
+    pub fn find(
+        &self,
+        class: impl Into<Symbol>,
+        method: impl Into<Symbol>,
+        desc: impl Into<Symbol>,
+    ) -> Option<Rp<NativeMethodW>> {
+        let k = (class.into(), method.into(), desc.into());
+        self.data.get(&k).copied()
     }
-    pub fn find(&self, class: &str, method: &str, desc: &str) -> &NativeMethodW {
-        let h = self.hash(class, method, desc);
-        let data = self.data.get(&h);
 
-        if data.is_none() {
-            panic!("native method {} not found", h);
+    // native of m, bound on first call and again after natives are registered
+    pub fn bind(&self, m: &mut ClassMember) -> Option<Rp<NativeMethodW>> {
+        if m.native.is_null() || m.native_epoch != self.epoch {
+            m.native = self
+                .find(m.class.name, m.name, m.desc)
+                .unwrap_or(Rp::null());
+            m.native_epoch = self.epoch;
         }
 
-        data.unwrap()
+        if m.native.is_null() {
+            None
+        } else {
+            Some(m.native)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NativeRegistry;
+    use crate::heap::class::{Class, ClassMember};
+    use rp::Rp;
+
+    na!(Value1, "test/NativeTest", "value", "()I", _th, f, {
+        f.push_i32(1)
+    });
+
+    na!(Value2, "test/NativeTest", "value", "()I", _th, f, {
+        f.push_i32(2)
+    });
+
+    #[test]
+    fn bind_test() {
+        let mut r = NativeRegistry::new();
+        let mut c = Class::default();
+        c.name = "test/NativeTest".into();
+        let mut m = ClassMember::default();
+        m.class = Rp::new(c);
+        m.name = "value".into();
+        m.desc = "()I".into();
+
+        assert!(r.bind(&mut m).is_none());
+        reg!(r, Value1);
+        let w = r.bind(&mut m).unwrap();
+        assert_eq!(w.inner.method_name(), "value");
+        assert_eq!(r.bind(&mut m).unwrap().ptr(), w.ptr());
+
+        // registerNatives again rebinds
+        reg!(r, Value2);
+        assert_ne!(r.bind(&mut m).unwrap().ptr(), w.ptr());
     }
 }
@@ -1,4 +1,6 @@
+use crate::heap::class::Object;
 use crate::runtime::misc::Slots;
+use rp::Rp;
 
 na!(
     JLOReg,
@@ -23,7 +25,13 @@ na!(
             N10,
             N11,
             N12,
-            N13
+            N13,
+            N14,
+            N15,
+            N16,
+            N17,
+            N18,
+            N19
         );
     }
 );
@@ -42,8 +50,8 @@ na!(
 );
 
 na!(N1, "java/lang/Object", "hashCode", "()I", th, f, {
-    let ths = f.this();
-    f.push_u32(ths.ptr() as u32)
+    let mut ths = f.this();
+    f.push_u32(ths.identity_hash() as u32)
 });
 
 na!(N2, "java/lang/Float", "floatToRawIntBits", "(F)I", th, f, {
@@ -85,17 +93,40 @@ na!(
     }
 );
 
+// run the action on this thread, checked exceptions of PrivilegedExceptionAction are wrapped
 macro_rules! ac {
-    ($th: ident, $f: ident) => {{
-        let this = $f.this();
-
-        $th.invoke_obj(
-            this.get_mut(),
-            "run",
-            "()Ljava/lang/Object;",
-            &[this.ptr() as u64],
-            false,
-        );
+    ($th: ident, $f: ident, $checked: expr) => {{
+        use crate::runtime::value::JValue;
+        let action = $f.this();
+        if action.is_null() {
+            $th.throw_npe();
+            return;
+        }
+
+        let m = action.class.lookup_method("run", "()Ljava/lang/Object;");
+        match $th.call(m, &[JValue::Object(action)]) {
+            Ok(r) => $f.push_obj(r.as_object().unwrap()),
+            Err(e) => {
+                let ex = e.throwable();
+                let unchecked = ["java/lang/RuntimeException", "java/lang/Error"]
+                    .iter()
+                    .any(|c| ex.instance_of(&$th.heap.loader.load(c)));
+                if !$checked || unchecked {
+                    $th.throw(ex);
+                    return;
+                }
+
+                // new PrivilegedActionException(ex)
+                let c = $th.heap.loader.load("java/security/PrivilegedActionException");
+                let wrapped = crate::heap::class::Class::new_obj(c);
+                let init = c.lookup_method_in_class("<init>", "(Ljava/lang/Exception;)V");
+                let args = [JValue::Object(wrapped), JValue::Object(ex)];
+                match $th.call(init, &args) {
+                    Ok(_) => $th.throw(wrapped),
+                    Err(e) => $th.throw(e.throwable()),
+                }
+            }
+        }
     }};
 }
 na!(
@@ -105,7 +136,7 @@ na!(
     "(Ljava/security/PrivilegedExceptionAction;)Ljava/lang/Object;",
     th,
     f,
-    { ac!(th, f) }
+    { ac!(th, f, true) }
 );
 
 na!(
@@ -115,7 +146,7 @@ na!(
     "(Ljava/security/PrivilegedAction;)Ljava/lang/Object;",
     th,
     f,
-    { ac!(th, f) }
+    { ac!(th, f, false) }
 );
 
 na!(
@@ -137,8 +168,129 @@ na!(
     f,
     {
         let this = f.this();
-        println!("String.intern {} ", this.jstring());
         let o = f.heap.new_jstr(&this.jstring());
         f.push_obj(o)
     }
 );
+
+na!(
+    N14,
+    "java/lang/Throwable",
+    "fillInStackTrace",
+    "(I)Ljava/lang/Throwable;",
+    th,
+    f,
+    {
+        let mut this = f.this();
+        let trace = th.stack_trace(this);
+        this.set_field_ref("backtrace", trace);
+        f.push_obj(this)
+    }
+);
+
+na!(
+    N15,
+    "java/lang/Throwable",
+    "getStackTraceDepth",
+    "()I",
+    _th,
+    f,
+    {
+        let trace: Rp<Object> = (f.this().get_field("backtrace") as usize).into();
+        f.push_u32(if trace.is_null() {
+            0
+        } else {
+            trace.size as u32
+        })
+    }
+);
+
+na!(
+    N16,
+    "java/lang/Throwable",
+    "getStackTraceElement",
+    "(I)Ljava/lang/StackTraceElement;",
+    th,
+    f,
+    {
+        let trace: Rp<Object> = (f.this().get_field("backtrace") as usize).into();
+        let depth = if trace.is_null() { 0 } else { trace.size };
+        let i = f.local_vars().get_i32(1);
+        if i < 0 || i as usize >= depth {
+            th.throw_new("java/lang/IndexOutOfBoundsException", None);
+            return;
+        }
+        f.push_slot(trace.fields()[i as usize])
+    }
+);
+
+na!(N17, "java/lang/Object", "wait", "(J)V", th, f, {
+    let ths = f.this();
+    let ms = f.local_vars().get_i64(1);
+
+    let interrupted = th.interrupted;
+    match th.heap.monitor_wait_done(ths, th.id, interrupted) {
+        // notified, interrupted or timed out
+        Some(true) => {
+            if th.take_interrupt() {
+                th.throw_new("java/lang/InterruptedException", None);
+            }
+            return;
+        }
+        Some(false) => {
+            th.park();
+            return;
+        }
+        None => {}
+    }
+
+    if ms < 0 {
+        th.throw_new(
+            "java/lang/IllegalArgumentException",
+            Some("timeout value is negative"),
+        );
+        return;
+    }
+
+    let timeout = if ms == 0 {
+        None
+    } else {
+        Some(std::time::Duration::from_millis(ms as u64))
+    };
+
+    if !th.heap.holds_lock(ths, th.id) {
+        th.throw_new(
+            "java/lang/IllegalMonitorStateException",
+            Some("current thread is not owner"),
+        );
+        return;
+    }
+
+    if th.take_interrupt() {
+        th.throw_new("java/lang/InterruptedException", None);
+        return;
+    }
+
+    th.heap.monitor_wait(ths, th.id, timeout);
+    th.park();
+});
+
+na!(N18, "java/lang/Object", "notify", "()V", th, f, {
+    let ths = f.this();
+    if !th.heap.monitor_notify(ths, th.id, false) {
+        th.throw_new(
+            "java/lang/IllegalMonitorStateException",
+            Some("current thread is not owner"),
+        );
+    }
+});
+
+na!(N19, "java/lang/Object", "notifyAll", "()V", th, f, {
+    let ths = f.this();
+    if !th.heap.monitor_notify(ths, th.id, true) {
+        th.throw_new(
+            "java/lang/IllegalMonitorStateException",
+            Some("current thread is not owner"),
+        );
+    }
+});
@@ -1,6 +1,15 @@
-use crate::heap::class::Object;
+use crate::heap::class::{Class, Object};
+use crate::runtime::frame::JFrame;
+use crate::runtime::misc::Slots;
+use crate::runtime::vm::JThread;
 use rp::Rp;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
 use std::mem::size_of;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// offsets returned by staticFieldOffset are tagged, the base of a static field is a class mirror
+// which has instance fields as well
+pub const STATIC_OFFSET: u64 = 1 << 62;
 
 na!(
     UnsafeReg,
@@ -10,10 +19,415 @@ na!(
     th,
     f,
     {
-        reg!(th.registry, N0, N1, N2, N3, N4);
+        reg!(
+            th.registry,
+            N0,
+            N1,
+            N2,
+            N3,
+            N4,
+            N5,
+            N6,
+            N7,
+            N8,
+            N9,
+            N10,
+            N11,
+            N12,
+            N13,
+            N14,
+            N15,
+            N16,
+            N17,
+            N18,
+            N19,
+            N20,
+            N21,
+            N22,
+            N23,
+            N24,
+            N25,
+            N26,
+            N27,
+            N28,
+            N29,
+            N30,
+            GetBoolean,
+            PutBoolean,
+            GetBooleanVolatile,
+            PutBooleanVolatile,
+            GetByte,
+            PutByte,
+            GetByteVolatile,
+            PutByteVolatile,
+            GetShort,
+            PutShort,
+            GetShortVolatile,
+            PutShortVolatile,
+            GetChar,
+            PutChar,
+            GetCharVolatile,
+            PutCharVolatile,
+            GetInt,
+            PutInt,
+            GetIntVolatile,
+            PutIntVolatile,
+            GetLong,
+            PutLong,
+            GetLongVolatile,
+            PutLongVolatile,
+            GetFloat,
+            PutFloat,
+            GetFloatVolatile,
+            PutFloatVolatile,
+            GetDouble,
+            PutDouble,
+            GetDoubleVolatile,
+            PutDoubleVolatile,
+            GetObject,
+            PutObject,
+            GetObjectVolatile,
+            PutObjectVolatile,
+            GetByteRaw,
+            PutByteRaw,
+            GetShortRaw,
+            PutShortRaw,
+            GetCharRaw,
+            PutCharRaw,
+            GetIntRaw,
+            PutIntRaw,
+            GetLongRaw,
+            PutLongRaw,
+            GetFloatRaw,
+            PutFloatRaw,
+            GetDoubleRaw,
+            PutDoubleRaw,
+            GetAddress,
+            PutAddress
+        );
     }
 );
 
+// java type accessed by unsafe
+#[derive(Clone, Copy)]
+enum Kind {
+    Boolean,
+    Byte,
+    Short,
+    Char,
+    Int,
+    Float,
+    Long,
+    Double,
+    Object,
+}
+
+impl Kind {
+    fn size(self) -> usize {
+        match self {
+            Kind::Boolean | Kind::Byte => 1,
+            Kind::Short | Kind::Char => 2,
+            Kind::Int | Kind::Float => 4,
+            _ => 8,
+        }
+    }
+
+    // value as kept in a slot from size bytes of memory
+    fn extend(self, v: u64) -> u64 {
+        match self {
+            Kind::Boolean => (v as u8 != 0) as u64,
+            Kind::Byte => v as u8 as i8 as i32 as u32 as u64,
+            Kind::Short => v as u16 as i16 as i32 as u32 as u64,
+            Kind::Char => v as u16 as u64,
+            Kind::Int | Kind::Float => v as u32 as u64,
+            _ => v,
+        }
+    }
+
+    fn arg(self, f: &JFrame, i: usize) -> u64 {
+        match self {
+            Kind::Long | Kind::Double => f.local_vars().get_u64(i),
+            Kind::Object => f.local_vars().get_slot(i),
+            _ => f.local_vars().get_u32(i) as u64,
+        }
+    }
+
+    fn push(self, f: &mut JFrame, v: u64) {
+        match self {
+            Kind::Long | Kind::Double => f.push_u64(v),
+            Kind::Object => f.push_slot(v),
+            _ => f.push_u32(v as u32),
+        }
+    }
+}
+
+// location addressed by a base object and an offset
+enum Place {
+    // off-heap memory or array elements
+    Memory(*mut u8),
+    // field of an object or a class
+    Slot(*mut u64),
+}
+
+impl Place {
+    fn of(o: Rp<Object>, off: u64) -> Place {
+        if o.is_null() {
+            return Place::Memory(off as usize as *mut u8);
+        }
+
+        if off & STATIC_OFFSET != 0 && o.class.name == "java/lang/Class" {
+            let c = o.extra_class();
+            let i = (off & !STATIC_OFFSET) as usize;
+            return Place::Slot(&mut c.get_mut().static_vars[i] as *mut u64);
+        }
+
+        // array elements and instance fields are bytes of object data
+        Place::Memory((o.data + off as usize) as *mut u8)
+    }
+
+    fn ptr(&self) -> *mut u8 {
+        match *self {
+            Place::Memory(p) => p,
+            Place::Slot(p) => p as *mut u8,
+        }
+    }
+
+    // read size bytes, zero extended
+    fn read(&self, size: usize) -> u64 {
+        match *self {
+            Place::Memory(p) => {
+                let mut v = 0u64;
+                unsafe { std::ptr::copy_nonoverlapping(p, &mut v as *mut u64 as *mut u8, size) };
+                v
+            }
+            Place::Slot(p) => {
+                let v = unsafe { *p };
+                if size == 8 {
+                    v
+                } else {
+                    v & ((1u64 << (size * 8)) - 1)
+                }
+            }
+        }
+    }
+
+    // write value as kept in a slot, memory takes its low size bytes
+    fn write(&self, size: usize, v: u64) {
+        match *self {
+            Place::Memory(p) => unsafe {
+                std::ptr::copy_nonoverlapping(&v as *const u64 as *const u8, p, size)
+            },
+            Place::Slot(p) => unsafe { *p = v },
+        }
+    }
+}
+
+fn obj(f: &JFrame, i: usize) -> Rp<Object> {
+    (f.local_vars()[i] as usize).into()
+}
+
+// getX(Object o, long offset)
+fn get(f: &mut JFrame, k: Kind) {
+    let v = Place::of(obj(f, 1), f.local_vars().get_u64(2)).read(k.size());
+    k.push(f, k.extend(v))
+}
+
+// putX(Object o, long offset, X x)
+fn put(f: &mut JFrame, k: Kind) {
+    let v = k.arg(f, 4);
+    Place::of(obj(f, 1), f.local_vars().get_u64(2)).write(k.size(), v)
+}
+
+// getX(long address)
+fn get_raw(f: &mut JFrame, k: Kind) {
+    let v = Place::of(Rp::null(), f.local_vars().get_u64(1)).read(k.size());
+    k.push(f, k.extend(v))
+}
+
+// putX(long address, X x)
+fn put_raw(f: &mut JFrame, k: Kind) {
+    let v = k.arg(f, 3);
+    Place::of(Rp::null(), f.local_vars().get_u64(1)).write(k.size(), v)
+}
+
+// compareAndSwapX(Object o, long offset, X expected, X x)
+fn cas(f: &mut JFrame, k: Kind) {
+    let n = if k.size() == 8 && !matches!(k, Kind::Object) {
+        2
+    } else {
+        1
+    };
+    let expected = k.arg(f, 4);
+    let x = k.arg(f, 4 + n);
+    let p = Place::of(obj(f, 1), f.local_vars().get_u64(2));
+
+    let swapped = p.read(k.size()) == expected;
+    if swapped {
+        p.write(k.size(), x);
+    }
+    f.push_u32(if swapped { 1 } else { 0 })
+}
+
+macro_rules! access {
+    ($g: ident, $p: ident, $gv: ident, $pv: ident, $n: expr, $t: expr, $k: expr) => {
+        na!(
+            $g,
+            "sun/misc/Unsafe",
+            concat!("get", $n),
+            concat!("(Ljava/lang/Object;J)", $t),
+            _th,
+            f,
+            { get(f, $k) }
+        );
+
+        na!(
+            $p,
+            "sun/misc/Unsafe",
+            concat!("put", $n),
+            concat!("(Ljava/lang/Object;J", $t, ")V"),
+            _th,
+            f,
+            { put(f, $k) }
+        );
+
+        // green threads don't reorder memory accesses
+        na!(
+            $gv,
+            "sun/misc/Unsafe",
+            concat!("get", $n, "Volatile"),
+            concat!("(Ljava/lang/Object;J)", $t),
+            _th,
+            f,
+            { get(f, $k) }
+        );
+
+        na!(
+            $pv,
+            "sun/misc/Unsafe",
+            concat!("put", $n, "Volatile"),
+            concat!("(Ljava/lang/Object;J", $t, ")V"),
+            _th,
+            f,
+            { put(f, $k) }
+        );
+    };
+}
+
+macro_rules! raw_access {
+    ($g: ident, $p: ident, $n: expr, $t: expr, $k: expr) => {
+        na!(
+            $g,
+            "sun/misc/Unsafe",
+            concat!("get", $n),
+            concat!("(J)", $t),
+            _th,
+            f,
+            { get_raw(f, $k) }
+        );
+
+        na!(
+            $p,
+            "sun/misc/Unsafe",
+            concat!("put", $n),
+            concat!("(J", $t, ")V"),
+            _th,
+            f,
+            { put_raw(f, $k) }
+        );
+    };
+}
+
+access!(
+    GetBoolean,
+    PutBoolean,
+    GetBooleanVolatile,
+    PutBooleanVolatile,
+    "Boolean",
+    "Z",
+    Kind::Boolean
+);
+access!(
+    GetByte,
+    PutByte,
+    GetByteVolatile,
+    PutByteVolatile,
+    "Byte",
+    "B",
+    Kind::Byte
+);
+access!(
+    GetShort,
+    PutShort,
+    GetShortVolatile,
+    PutShortVolatile,
+    "Short",
+    "S",
+    Kind::Short
+);
+access!(
+    GetChar,
+    PutChar,
+    GetCharVolatile,
+    PutCharVolatile,
+    "Char",
+    "C",
+    Kind::Char
+);
+access!(
+    GetInt,
+    PutInt,
+    GetIntVolatile,
+    PutIntVolatile,
+    "Int",
+    "I",
+    Kind::Int
+);
+access!(
+    GetLong,
+    PutLong,
+    GetLongVolatile,
+    PutLongVolatile,
+    "Long",
+    "J",
+    Kind::Long
+);
+access!(
+    GetFloat,
+    PutFloat,
+    GetFloatVolatile,
+    PutFloatVolatile,
+    "Float",
+    "F",
+    Kind::Float
+);
+access!(
+    GetDouble,
+    PutDouble,
+    GetDoubleVolatile,
+    PutDoubleVolatile,
+    "Double",
+    "D",
+    Kind::Double
+);
+access!(
+    GetObject,
+    PutObject,
+    GetObjectVolatile,
+    PutObjectVolatile,
+    "Object",
+    "Ljava/lang/Object;",
+    Kind::Object
+);
+
+raw_access!(GetByteRaw, PutByteRaw, "Byte", "B", Kind::Byte);
+raw_access!(GetShortRaw, PutShortRaw, "Short", "S", Kind::Short);
+raw_access!(GetCharRaw, PutCharRaw, "Char", "C", Kind::Char);
+raw_access!(GetIntRaw, PutIntRaw, "Int", "I", Kind::Int);
+raw_access!(GetLongRaw, PutLongRaw, "Long", "J", Kind::Long);
+raw_access!(GetFloatRaw, PutFloatRaw, "Float", "F", Kind::Float);
+raw_access!(GetDoubleRaw, PutDoubleRaw, "Double", "D", Kind::Double);
+raw_access!(GetAddress, PutAddress, "Address", "J", Kind::Long);
+
 na!(
     N0,
     "sun/misc/Unsafe",
@@ -24,6 +438,7 @@ na!(
     { f.push_u32(0) }
 );
 
+// array offsets are in bytes
 na!(
     N1,
     "sun/misc/Unsafe",
@@ -31,13 +446,17 @@ na!(
     "(Ljava/lang/Class;)I",
     th,
     f,
-    { f.push_u32(0) }
+    {
+        let c = obj(f, 1).extra_class();
+        f.push_u32(c.elem_size() as u32)
+    }
 );
 
 na!(N2, "sun/misc/Unsafe", "addressSize", "()I", th, f, {
     f.push_u32(size_of::<usize>() as u32)
 });
 
+// byte offset of field in object data, slot of a field is its index in fields of instances
 na!(
     N3,
     "sun/misc/Unsafe",
@@ -47,8 +466,10 @@ na!(
     f,
     {
         let field: Rp<Object> = (f.local_vars()[1] as usize).into();
-        let slot = field.get_field("slot");
-        f.push_u64(slot)
+        let class: Rp<Object> = (field.get_field("clazz") as usize).into();
+        let class = class.extra_class();
+        let slot = field.get_field("slot") as usize;
+        f.push_u64(class.ins_fields[slot].offset as u64)
     }
 );
 
@@ -59,5 +480,424 @@ na!(
     "(Ljava/lang/Object;JLjava/lang/Object;Ljava/lang/Object;)Z",
     th,
     f,
-    { panic!("cas") }
+    { cas(f, Kind::Object) }
+);
+
+na!(
+    N5,
+    "sun/misc/Unsafe",
+    "compareAndSwapInt",
+    "(Ljava/lang/Object;JII)Z",
+    _th,
+    f,
+    { cas(f, Kind::Int) }
+);
+
+na!(
+    N6,
+    "sun/misc/Unsafe",
+    "compareAndSwapLong",
+    "(Ljava/lang/Object;JJJ)Z",
+    _th,
+    f,
+    { cas(f, Kind::Long) }
+);
+
+na!(
+    N7,
+    "sun/misc/Unsafe",
+    "putOrderedObject",
+    "(Ljava/lang/Object;JLjava/lang/Object;)V",
+    _th,
+    f,
+    { put(f, Kind::Object) }
+);
+
+na!(
+    N8,
+    "sun/misc/Unsafe",
+    "putOrderedInt",
+    "(Ljava/lang/Object;JI)V",
+    _th,
+    f,
+    { put(f, Kind::Int) }
+);
+
+na!(
+    N9,
+    "sun/misc/Unsafe",
+    "putOrderedLong",
+    "(Ljava/lang/Object;JJ)V",
+    _th,
+    f,
+    { put(f, Kind::Long) }
+);
+
+na!(
+    N10,
+    "sun/misc/Unsafe",
+    "staticFieldOffset",
+    "(Ljava/lang/reflect/Field;)J",
+    _th,
+    f,
+    {
+        let field: Rp<Object> = (f.local_vars()[1] as usize).into();
+        let slot = field.get_field("slot");
+        f.push_u64(slot | STATIC_OFFSET)
+    }
+);
+
+na!(
+    N11,
+    "sun/misc/Unsafe",
+    "staticFieldBase",
+    "(Ljava/lang/reflect/Field;)Ljava/lang/Object;",
+    _th,
+    f,
+    {
+        let field: Rp<Object> = (f.local_vars()[1] as usize).into();
+        f.push_slot(field.get_field("clazz"))
+    }
+);
+
+// off-heap blocks keep their size in a header before the returned address
+const HEADER: usize = 8;
+
+// None if the block is too large to allocate
+fn layout(bytes: usize) -> Option<Layout> {
+    let size = bytes.checked_add(HEADER)?;
+    Layout::from_size_align(size, 8).ok()
+}
+
+fn allocate(bytes: usize) -> usize {
+    if bytes == 0 {
+        return 0;
+    }
+
+    let l = match layout(bytes) {
+        Some(l) => l,
+        None => return 0,
+    };
+    let p = unsafe { alloc_zeroed(l) };
+    if p.is_null() {
+        return 0;
+    }
+    unsafe { *(p as *mut usize) = bytes };
+    p as usize + HEADER
+}
+
+fn free(addr: usize) {
+    if addr == 0 {
+        return;
+    }
+
+    let p = (addr - HEADER) as *mut u8;
+    let bytes = unsafe { *(p as *mut usize) };
+    if let Some(l) = layout(bytes) {
+        unsafe { dealloc(p, l) };
+    }
+}
+
+// check size of off-heap memory, throw IllegalArgumentException or OutOfMemoryError
+fn check_alloc(th: &mut JThread, bytes: i64, addr: usize) -> bool {
+    if bytes < 0 {
+        th.throw_new("java/lang/IllegalArgumentException", None);
+        return false;
+    }
+
+    if bytes > 0 && addr == 0 {
+        th.throw_new("java/lang/OutOfMemoryError", None);
+        return false;
+    }
+    true
+}
+
+na!(N12, "sun/misc/Unsafe", "allocateMemory", "(J)J", th, f, {
+    let bytes = f.local_vars().get_i64(1);
+    let addr = if bytes > 0 {
+        allocate(bytes as usize)
+    } else {
+        0
+    };
+    if check_alloc(th, bytes, addr) {
+        f.push_u64(addr as u64)
+    }
+});
+
+na!(
+    N13,
+    "sun/misc/Unsafe",
+    "reallocateMemory",
+    "(JJ)J",
+    th,
+    f,
+    {
+        let old = f.local_vars().get_u64(1) as usize;
+        let bytes = f.local_vars().get_i64(3);
+        let addr = if bytes > 0 {
+            allocate(bytes as usize)
+        } else {
+            0
+        };
+        if !check_alloc(th, bytes, addr) {
+            return;
+        }
+
+        // reallocation to 0 bytes only frees the old block
+        if old != 0 {
+            if addr != 0 {
+                let n = unsafe { *((old - HEADER) as *const usize) }.min(bytes as usize);
+                unsafe { std::ptr::copy_nonoverlapping(old as *const u8, addr as *mut u8, n) };
+            }
+            free(old);
+        }
+        f.push_u64(addr as u64)
+    }
+);
+
+na!(N14, "sun/misc/Unsafe", "freeMemory", "(J)V", _th, f, {
+    free(f.local_vars().get_u64(1) as usize)
+});
+
+na!(
+    N15,
+    "sun/misc/Unsafe",
+    "setMemory",
+    "(Ljava/lang/Object;JJB)V",
+    _th,
+    f,
+    {
+        let p = Place::of(obj(f, 1), f.local_vars().get_u64(2));
+        let bytes = f.local_vars().get_u64(4) as usize;
+        let v = f.local_vars().get_u32(6) as u8;
+        unsafe { std::ptr::write_bytes(p.ptr(), v, bytes) }
+    }
+);
+
+na!(
+    N16,
+    "sun/misc/Unsafe",
+    "copyMemory",
+    "(Ljava/lang/Object;JLjava/lang/Object;JJ)V",
+    _th,
+    f,
+    {
+        let src = Place::of(obj(f, 1), f.local_vars().get_u64(2));
+        let dst = Place::of(obj(f, 4), f.local_vars().get_u64(5));
+        let bytes = f.local_vars().get_u64(7) as usize;
+        unsafe { std::ptr::copy(src.ptr(), dst.ptr(), bytes) }
+    }
+);
+
+na!(N17, "sun/misc/Unsafe", "pageSize", "()I", _th, f, {
+    f.push_u32(4096)
+});
+
+na!(
+    N18,
+    "sun/misc/Unsafe",
+    "allocateInstance",
+    "(Ljava/lang/Class;)Ljava/lang/Object;",
+    th,
+    f,
+    {
+        let c = obj(f, 1).extra_class();
+        if c.access_flags.is_abstract() || c.access_flags.is_iface() || c.dim > 0 {
+            let name = c.name.replace('/', ".");
+            th.throw_new("java/lang/InstantiationException", Some(&name));
+            return;
+        }
+
+        if !th.init_class(c) {
+            return;
+        }
+
+        if !th.reserve(Object::bytes(c.ins_words(), 8)) {
+            return;
+        }
+        f.push_obj(Class::new_obj(c))
+    }
+);
+
+na!(
+    N19,
+    "sun/misc/Unsafe",
+    "ensureClassInitialized",
+    "(Ljava/lang/Class;)V",
+    th,
+    f,
+    {
+        let c = obj(f, 1).extra_class();
+        th.init_class(c);
+    }
+);
+
+na!(
+    N20,
+    "sun/misc/Unsafe",
+    "shouldBeInitialized",
+    "(Ljava/lang/Class;)Z",
+    _th,
+    f,
+    {
+        let c = obj(f, 1).extra_class();
+        f.push_u32(if c.is_initialized() { 0 } else { 1 })
+    }
+);
+
+na!(
+    N21,
+    "sun/misc/Unsafe",
+    "throwException",
+    "(Ljava/lang/Throwable;)V",
+    th,
+    f,
+    {
+        let ex = obj(f, 1);
+        if ex.is_null() {
+            th.throw_npe();
+            return;
+        }
+        th.throw(ex)
+    }
+);
+
+na!(
+    N22,
+    "sun/misc/Unsafe",
+    "monitorEnter",
+    "(Ljava/lang/Object;)V",
+    th,
+    f,
+    {
+        let o = obj(f, 1);
+        if o.is_null() {
+            th.throw_npe();
+            return;
+        }
+        if !th.heap.monitor_enter(o, th.id) {
+            th.park();
+        }
+    }
+);
+
+na!(
+    N23,
+    "sun/misc/Unsafe",
+    "monitorExit",
+    "(Ljava/lang/Object;)V",
+    th,
+    f,
+    {
+        let o = obj(f, 1);
+        if o.is_null() {
+            th.throw_npe();
+            return;
+        }
+        if !th.heap.monitor_exit(o, th.id) {
+            th.throw_new("java/lang/IllegalMonitorStateException", None);
+        }
+    }
+);
+
+na!(
+    N24,
+    "sun/misc/Unsafe",
+    "tryMonitorEnter",
+    "(Ljava/lang/Object;)Z",
+    th,
+    f,
+    {
+        let o = obj(f, 1);
+        if o.is_null() {
+            th.throw_npe();
+            return;
+        }
+        let locked = th.heap.monitor_enter(o, th.id);
+        f.push_u32(if locked { 1 } else { 0 })
+    }
+);
+
+// park(boolean isAbsolute, long time)
+// time is nanoseconds relative to now, or milliseconds since the epoch if absolute, 0 means forever
+na!(N25, "sun/misc/Unsafe", "park", "(ZJ)V", th, f, {
+    if th.parking {
+        let timed_out = th.park_until.map(|d| Instant::now() >= d).unwrap_or(false);
+        if th.permit || th.interrupted || timed_out {
+            th.parking = false;
+            th.permit = false;
+            th.park_until = None;
+        } else {
+            th.park();
+        }
+        return;
+    }
+
+    if th.permit {
+        th.permit = false;
+        return;
+    }
+
+    let absolute = f.local_vars()[1] != 0;
+    let time = f.local_vars().get_i64(2);
+    if th.interrupted || time < 0 {
+        return;
+    }
+
+    th.park_until = if absolute {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        if time <= now {
+            return;
+        }
+        Some(Instant::now() + Duration::from_millis((time - now) as u64))
+    } else if time == 0 {
+        None
+    } else {
+        Some(Instant::now() + Duration::from_nanos(time as u64))
+    };
+
+    th.parking = true;
+    th.park();
+});
+
+na!(
+    N26,
+    "sun/misc/Unsafe",
+    "unpark",
+    "(Ljava/lang/Object;)V",
+    _th,
+    f,
+    {
+        let o = obj(f, 1);
+        if o.is_null() {
+            return;
+        }
+
+        let mut t = JThread::of(o);
+        if !t.is_null() {
+            t.permit = true;
+        }
+    }
+);
+
+na!(N27, "sun/misc/Unsafe", "getLoadAverage", "([DI)I", _th, f, {
+    f.push_u32(-1i32 as u32)
+});
+
+na!(N28, "sun/misc/Unsafe", "loadFence", "()V", _th, _f, {});
+na!(N29, "sun/misc/Unsafe", "storeFence", "()V", _th, _f, {});
+na!(N30, "sun/misc/Unsafe", "fullFence", "()V", _th, _f, {});
+
+// AtomicLong uses compareAndSwapLong
+na!(
+    CS8,
+    "java/util/concurrent/atomic/AtomicLong",
+    "VMSupportsCS8",
+    "()Z",
+    _th,
+    f,
+    { f.push_u32(1) }
 );
@@ -23,7 +23,7 @@ na!(
     th,
     f,
     {
-        reg!(th.registry, N0, N1, VM);
+        reg!(th.registry, N0, N1, N2, VM);
     }
 );
 
@@ -35,28 +35,46 @@ na!(
     th,
     f,
     {
-        // call Properties.set()
+        use crate::runtime::value::JValue;
         let this = f.this();
+        let m = this.class.lookup_method(
+            "setProperty",
+            "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/Object;",
+        );
 
-        let props = sys_props();
+        let mut props = sys_props();
+        props.extend(th.heap.props.clone());
         for (k, v) in props.iter() {
             let args = [
-                this.ptr() as u64,
-                th.heap.new_jstr(k).ptr() as u64,
-                th.heap.new_jstr(v).ptr() as u64,
+                JValue::Object(this),
+                JValue::Object(th.heap.new_jstr(k)),
+                JValue::Object(th.heap.new_jstr(v)),
             ];
-            th.invoke_obj(
-                this.get_mut(),
-                "setProperty",
-                "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/Object;",
-                &args,
-                true,
-            )
+            if let Err(e) = th.call(m, &args) {
+                th.throw(e.throwable());
+                return;
+            }
         }
         f.push_obj(this);
     }
 );
 
+na!(
+    N2,
+    "java/lang/System",
+    "identityHashCode",
+    "(Ljava/lang/Object;)I",
+    _th,
+    f,
+    {
+        use crate::heap::class::Object;
+        use rp::Rp;
+        let mut o: Rp<Object> = (f.local_vars()[0] as usize).into();
+        let h = if o.is_null() { 0 } else { o.identity_hash() };
+        f.push_u32(h as u32)
+    }
+);
+
 fn sys_props() -> HashMap<String, String> {
     map! {
         "java.version"=>         "1.8.0",
@@ -82,6 +100,41 @@ fn sys_props() -> HashMap<String, String> {
     }
 }
 
+// java.lang.Shutdown has no registerNatives
+na!(
+    ShutdownBeforeHalt,
+    "java/lang/Shutdown",
+    "beforeHalt",
+    "()V",
+    _th,
+    _f,
+    {}
+);
+
+na!(
+    ShutdownRunFinalizers,
+    "java/lang/Shutdown",
+    "runAllFinalizers",
+    "()V",
+    _th,
+    _f,
+    {}
+);
+
+// Runtime.exit and Runtime.halt end here, the scheduler stops every thread
+na!(
+    ShutdownHalt,
+    "java/lang/Shutdown",
+    "halt0",
+    "(I)V",
+    th,
+    f,
+    {
+        th.sched.exit = Some(f.local_vars()[0] as i32);
+        th.yield_now();
+    }
+);
+
 na!(VM, "sun/misc/VM", "initialize", "()V", th, f, {
     // call initializeSystemClass
     let m = f.heap.loader.load("java/lang/System");
@@ -105,10 +158,6 @@ na!(
         let dest_p = f.local_vars()[3] as usize;
         let len = f.local_vars()[4] as usize;
 
-        println!(
-            "system array copy start {} {} {} {}",
-            src.class.name, src_p, dest_p, len
-        );
         let mut parser = DescriptorParser::new(src.class.name.as_bytes());
         let d = parser.parse_arr();
         let mut sz = match &src.class.element_class.name[0..1] {
@@ -129,7 +178,5 @@ na!(
             let x: u8 = src.get(src_p * sz + i);
             dest.set(dest_p * sz + i, x);
         }
-
-        println!("system array copy end");
     }
 );
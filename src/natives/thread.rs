@@ -1,4 +1,7 @@
-use crate::heap::class::Class;
+use crate::runtime::misc::Slots;
+use crate::runtime::vm::JThread;
+use rp::Rp;
+use std::time::{Duration, Instant};
 
 na!(
     ThreadReg,
@@ -8,7 +11,7 @@ na!(
     th,
     f,
     {
-        reg!(th.registry, N0, N1, N2, N3);
+        reg!(th.registry, N0, N1, N2, N3, N4, N5, N6, N7, N8);
     }
 );
 
@@ -20,21 +23,92 @@ na!(
     th,
     f,
     {
-        let c = th.heap.loader.load("java/lang/Thread");
-        let mut o = Class::new_obj(c);
-        let tgc = th.heap.loader.load("java/lang/ThreadGroup");
-        let to = Class::new_obj(tgc);
-
-        o.set_field_ref("group", to);
-        o.set_field("priority", 1);
-
+        let o = th.thread_obj();
         f.push_obj(o)
     }
 );
 
 na!(N1, "java/lang/Thread", "setPriority0", "(I)V", th, f, {});
 na!(N2, "java/lang/Thread", "isAlive", "()Z", th, f, {
-    f.push_u32(0)
+    let alive = f.this().get_field("eetop") != 0;
+    f.push_u32(if alive { 1 } else { 0 })
 });
 
-na!(N3, "java/lang/Thread", "start0", "()V", th, f, {});
+// new thread runs Thread.run() and is scheduled with other threads
+na!(N3, "java/lang/Thread", "start0", "()V", th, f, {
+    let ths = f.this();
+    let mut t = Rp::new(JThread::new(th.heap, th.registry, th.sched));
+    t.attach(ths);
+
+    let m = ths.class.lookup_method("run", "()V");
+    let nf = t.push_frame(m);
+    nf.local_vars()[0] = ths.ptr() as u64;
+    th.sched.spawn(t);
+});
+
+na!(
+    N4,
+    "java/lang/Thread",
+    "holdsLock",
+    "(Ljava/lang/Object;)Z",
+    th,
+    f,
+    {
+        use crate::heap::class::Object;
+        use rp::Rp;
+        let o: Rp<Object> = (f.local_vars()[0] as usize).into();
+        if o.is_null() {
+            th.throw_npe();
+            return;
+        }
+        let held = th.heap.holds_lock(o, th.id);
+        f.push_u32(if held { 1 } else { 0 })
+    }
+);
+
+na!(N5, "java/lang/Thread", "sleep", "(J)V", th, f, {
+    if th.take_interrupt() {
+        th.sleep_until = None;
+        th.throw_new("java/lang/InterruptedException", Some("sleep interrupted"));
+        return;
+    }
+
+    match th.sleep_until {
+        Some(t) if Instant::now() >= t => th.sleep_until = None,
+        Some(_) => th.park(),
+        None => {
+            let ms = f.local_vars().get_i64(0);
+            if ms < 0 {
+                th.throw_new(
+                    "java/lang/IllegalArgumentException",
+                    Some("timeout value is negative"),
+                );
+                return;
+            }
+            th.sleep_until = Some(Instant::now() + Duration::from_millis(ms as u64));
+            th.park();
+        }
+    }
+});
+
+na!(N6, "java/lang/Thread", "yield", "()V", th, _f, {
+    th.yield_now();
+});
+
+// sleeping and waiting threads check interrupt status each time they are scheduled
+na!(N7, "java/lang/Thread", "interrupt0", "()V", _th, f, {
+    let mut t = JThread::of(f.this());
+    if !t.is_null() {
+        t.interrupted = true;
+    }
+});
+
+na!(N8, "java/lang/Thread", "isInterrupted", "(Z)Z", _th, f, {
+    let clear = f.local_vars()[1] != 0;
+    let mut t = JThread::of(f.this());
+    let r = !t.is_null() && t.interrupted;
+    if r && clear {
+        t.interrupted = false;
+    }
+    f.push_u32(if r { 1 } else { 0 })
+});
@@ -219,6 +219,20 @@ pub enum OpCode {
     breakpoint,
     impdep1,
     impdep2,
+    //Quick, rewritten from resolved instructions, never read from class files
+    ldc_quick,
+    getstatic_quick,
+    putstatic_quick,
+    getfield_quick,
+    putfield_quick,
+    invokevirtual_quick,
+    invokeinterface_quick,
+    // invokestatic or invokespecial with selected method
+    invoke_quick,
+    new_quick,
+    anewarray_quick,
+    checkcast_quick,
+    instanceof_quick,
 }
 
 impl From<u8> for OpCode {
@@ -1,15 +1,22 @@
 use crate::heap::class::{Class, ClassMember, Object};
 use crate::heap::misc::{Heap, SymRef};
+use crate::heap::refmap::RefMap;
 use cp::ConstantPool;
+use err::StringErr;
 use rp::Rp;
 
 const MAX_JSTACK_SIZE: usize = 1024;
+// bytes of locals and operand stacks a thread may use, -Xss
+pub const DEFAULT_STACK_SIZE: usize = 1024 * 1024;
+
 pub struct JStack {
     frames: [JFrame; MAX_JSTACK_SIZE],
     // default stack size = 64k = 64 * 1024
     stack_data: Vec<u64>,
     size: u16,
     cap: u32,
+    // stack size in bytes
+    pub limit: usize,
 }
 
 impl JStack {
@@ -19,6 +26,7 @@ impl JStack {
             size: 0,
             stack_data: vec![0; 64 * 1024 / 8],
             cap: 0,
+            limit: DEFAULT_STACK_SIZE,
         };
 
         for i in 0..r.frames.len() {
@@ -97,10 +105,26 @@ impl JStack {
         unsafe { self.frames.as_ptr().add(self.size as usize - 1).into() }
     }
 
+    // frames or stack data exceed the limits, the top frame hasn't run yet
+    #[inline]
+    pub fn overflow(&self) -> bool {
+        self.size as usize == MAX_JSTACK_SIZE || self.cap as usize * 8 > self.limit
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.size == 0
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    // frames from bottom to top
+    pub fn frames(&self) -> &[JFrame] {
+        &self.frames[..self.size as usize]
+    }
 }
 
 #[derive(Default, Clone, Copy)]
@@ -108,9 +132,16 @@ pub struct JFrame {
     local_base: Rp<u64>,
     pub method: Rp<ClassMember>,
     pub heap: Rp<Heap>,
+    // pc of the instruction being executed in this frame
+    pub pc: u32,
     pub next_pc: u32,
     id: u16,
-    pub no_ret: bool,
+    // pushed by JThread::call, the value returned is kept in the thread
+    pub call: bool,
+    // object locked by a synchronized method
+    pub monitor: Rp<Object>,
+    // class initialized by this <clinit> frame
+    pub init: Rp<Class>,
     stack_base: Rp<u64>,
     stack_size: u16,
 }
@@ -138,6 +169,10 @@ impl JFrame {
 
     pub fn reset(&mut self, local_base: Rp<u64>, method: Rp<ClassMember>) {
         self.method = method;
+        self.monitor = Rp::null();
+        self.init = Rp::null();
+        self.call = false;
+        self.pc = 0;
         self.next_pc = 0;
         self.local_base = local_base;
         self.stack_base = unsafe { self.local_base.raw().add(self.max_locals() as usize).into() };
@@ -164,6 +199,49 @@ impl JFrame {
         self.local_base.as_slice(self.max_locals() as usize)
     }
 
+    // reference map of the instruction being executed, None if there is no map for the pc
+    pub fn ref_map(&self) -> Option<&'static RefMap> {
+        self.method.get_mut().ref_map(self.pc)
+    }
+
+    // references in local variables and operand stack
+    // callers have popped arguments of the invocation in progress, so only the bottom of the mapped stack is live
+    pub fn refs(&self) -> Option<Vec<Rp<Object>>> {
+        let map = self.ref_map()?;
+        let stack = self.operands();
+        let slots = self
+            .local_vars()
+            .iter()
+            .zip(map.locals.iter())
+            .chain(stack.iter().zip(map.stack.iter()));
+
+        Some(
+            slots
+                .filter(|&(&v, &r)| r && v != 0)
+                .map(|(&v, _)| (v as usize).into())
+                .collect(),
+        )
+    }
+
+    // bases of local variables and operand stack for compiled code
+    pub fn slots(&self) -> (Rp<u64>, Rp<u64>) {
+        (self.local_base, self.stack_base)
+    }
+
+    #[inline]
+    pub fn stack_size(&self) -> u16 {
+        self.stack_size
+    }
+
+    pub fn set_stack_size(&mut self, n: u16) {
+        self.stack_size = n;
+    }
+
+    #[inline]
+    pub fn operands(&self) -> &'static [u64] {
+        self.stack_base.as_slice(self.stack_size as usize)
+    }
+
     #[inline]
     pub fn max_locals(&self) -> u16 {
         self.method.max_locals
@@ -194,6 +272,32 @@ impl JFrame {
     xx_ref!(method_ref);
     xx_ref!(iface_ref);
 
+    pub fn call_site_ref(&mut self, i: usize) -> Result<Rp<SymRef>, StringErr> {
+        let mut cur = self.class().get_mut();
+        self.heap.call_site_ref(&mut cur, i)
+    }
+
+    // search exception table for a handler of ex covering current pc
+    pub fn find_handler(&mut self, ex: Rp<Object>) -> Option<u32> {
+        let m = self.method;
+
+        for e in m.exceptions.iter() {
+            if self.pc < e.start_pc as u32 || self.pc >= e.end_pc as u32 {
+                continue;
+            }
+
+            if e.catch_type == 0 {
+                return Some(e.handler_pc as u32);
+            }
+
+            let sym = self.class_ref(e.catch_type as usize);
+            if ex.instance_of(&sym.class) {
+                return Some(e.handler_pc as u32);
+            }
+        }
+        None
+    }
+
     pub fn pass_args(&mut self, other: &mut JFrame, arg_slots: u16) {
         other.local_base.copy_from(
             self.stack_base.add((self.stack_size - arg_slots) as usize),
@@ -298,6 +402,11 @@ impl JFrame {
         f64::from_bits(self.pop_u64())
     }
 
+    #[inline]
+    pub fn clear_stack(&mut self) {
+        self.stack_size = 0;
+    }
+
     #[inline]
     pub fn push_null(&mut self) {
         self.stack_base[self.stack_size as usize] = 0;
@@ -1,3 +1,5 @@
 pub mod frame;
 pub mod misc;
+pub mod sched;
+pub mod value;
 pub mod vm;
@@ -0,0 +1,91 @@
+use crate::runtime::frame::DEFAULT_STACK_SIZE;
+use crate::runtime::vm::JThread;
+use rp::Rp;
+use std::time::Duration;
+
+// instructions a thread executes before the scheduler switches to the next one
+pub const DEFAULT_QUANTUM: u32 = 10_000;
+
+// deterministic round-robin scheduler of green threads
+pub struct Scheduler {
+    // threads[0] is the main thread, owned by jvm
+    threads: Vec<Rp<JThread>>,
+    pub quantum: u32,
+    // stack size of threads started from now on
+    pub stack_size: usize,
+    // status passed to Runtime.halt, every thread stops once it is set
+    pub exit: Option<i32>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            threads: Vec::new(),
+            quantum: DEFAULT_QUANTUM,
+            stack_size: DEFAULT_STACK_SIZE,
+            exit: None,
+        }
+    }
+
+    pub fn threads(&self) -> &[Rp<JThread>] {
+        &self.threads
+    }
+
+    pub fn spawn(&mut self, th: Rp<JThread>) {
+        self.threads.push(th);
+    }
+
+    // one time slice of every thread except the one at address me which waits in a nested call
+    // threads of other nested calls are in progress on the rust stack and are skipped
+    pub fn run_others(&mut self, me: usize) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+        while i < self.threads.len() {
+            let mut th = self.threads[i];
+            if th.ptr() != me && th.is_alive() && !th.in_call() {
+                progress |= th.run(self.quantum);
+            }
+            i += 1;
+        }
+        progress
+    }
+
+    // run threads until every non-daemon thread has terminated or the vm halts
+    pub fn run(&mut self) {
+        while self.exit.is_none() {
+            let mut progress = false;
+            let mut i = 0;
+
+            // threads spawned during this round start in the same round
+            while i < self.threads.len() {
+                let mut th = self.threads[i];
+                if th.is_alive() {
+                    progress |= th.run(self.quantum);
+                }
+                if self.exit.is_some() {
+                    return;
+                }
+
+                if !th.is_alive() {
+                    th.terminate();
+                    // started threads are freed once terminated
+                    if i > 0 {
+                        self.threads.remove(i);
+                        th.drop();
+                        continue;
+                    }
+                }
+                i += 1;
+            }
+
+            if !self.threads.iter().any(|t| t.is_alive() && !t.is_daemon()) {
+                return;
+            }
+
+            // every thread is blocked, sleeping or waiting
+            if !progress {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::heap::class::Object;
+use crate::heap::desc::JType;
+use rp::Rp;
+
+// java value passed to or returned from the vm
+#[derive(Clone, Copy)]
+pub enum JValue {
+    Void,
+    Boolean(bool),
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    // null is Rp::null()
+    Object(Rp<Object>),
+}
+
+impl JValue {
+    // value of a slot holding type t, t is a descriptor char
+    pub fn from_slot(t: u8, v: u64) -> Self {
+        match t {
+            b'Z' => JValue::Boolean(v as u8 != 0),
+            b'B' => JValue::Byte(v as i8),
+            b'C' => JValue::Char(v as u16),
+            b'S' => JValue::Short(v as i16),
+            b'I' => JValue::Int(v as i32),
+            b'J' => JValue::Long(v as i64),
+            b'F' => JValue::Float(f32::from_bits(v as u32)),
+            b'D' => JValue::Double(f64::from_bits(v)),
+            b'V' => JValue::Void,
+            _ => JValue::Object((v as usize).into()),
+        }
+    }
+
+    // value returned by a method of descriptor type t
+    pub fn from_type(t: &JType, v: u64) -> Self {
+        match t {
+            JType::FI(c) | JType::DJ(c) => JValue::from_slot(*c as u8, v),
+            JType::A(_) => JValue::from_slot(b'L', v),
+            JType::V => JValue::Void,
+        }
+    }
+
+    // slot of the value, small ints are extended as kept by the interpreter
+    pub fn slot(&self) -> u64 {
+        match *self {
+            JValue::Void => 0,
+            JValue::Boolean(b) => b as u64,
+            JValue::Byte(b) => b as i32 as u32 as u64,
+            JValue::Char(c) => c as u64,
+            JValue::Short(s) => s as i32 as u32 as u64,
+            JValue::Int(i) => i as u32 as u64,
+            JValue::Long(l) => l as u64,
+            JValue::Float(f) => f.to_bits() as u64,
+            JValue::Double(d) => d.to_bits(),
+            JValue::Object(o) => o.ptr() as u64,
+        }
+    }
+
+    // descriptor char of the value, L for objects and arrays
+    pub fn type_char(&self) -> u8 {
+        match self {
+            JValue::Void => b'V',
+            JValue::Boolean(_) => b'Z',
+            JValue::Byte(_) => b'B',
+            JValue::Char(_) => b'C',
+            JValue::Short(_) => b'S',
+            JValue::Int(_) => b'I',
+            JValue::Long(_) => b'J',
+            JValue::Float(_) => b'F',
+            JValue::Double(_) => b'D',
+            JValue::Object(_) => b'L',
+        }
+    }
+
+    // value fits a parameter or field of descriptor type t
+    pub fn is_type(&self, t: &JType) -> bool {
+        match t {
+            JType::FI(c) | JType::DJ(c) => self.type_char() == *c as u8,
+            JType::A(_) => self.type_char() == b'L',
+            JType::V => self.type_char() == b'V',
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            JValue::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    // boolean, byte, char, short and int widened to int
+    pub fn as_int(&self) -> Option<i32> {
+        match *self {
+            JValue::Boolean(b) => Some(b as i32),
+            JValue::Byte(b) => Some(b as i32),
+            JValue::Char(c) => Some(c as i32),
+            JValue::Short(s) => Some(s as i32),
+            JValue::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        match *self {
+            JValue::Long(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match *self {
+            JValue::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match *self {
+            JValue::Double(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<Rp<Object>> {
+        match *self {
+            JValue::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for JValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JValue::Void => f.write_str("Void"),
+            JValue::Boolean(b) => write!(f, "Boolean({})", b),
+            JValue::Byte(b) => write!(f, "Byte({})", b),
+            JValue::Char(c) => write!(f, "Char({})", c),
+            JValue::Short(s) => write!(f, "Short({})", s),
+            JValue::Int(i) => write!(f, "Int({})", i),
+            JValue::Long(l) => write!(f, "Long({})", l),
+            JValue::Float(v) => write!(f, "Float({})", v),
+            JValue::Double(d) => write!(f, "Double({})", d),
+            JValue::Object(o) if o.is_null() => f.write_str("Object(null)"),
+            JValue::Object(o) => write!(f, "Object({}@{:x})", o.class.name, o.ptr()),
+        }
+    }
+}
+
+// objects are equal if they are the same object
+impl PartialEq for JValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_char() == other.type_char() && self.slot() == other.slot()
+    }
+}
+
+// throwable thrown out of a method called from rust
+pub struct JavaException(pub Rp<Object>);
+
+impl JavaException {
+    pub fn throwable(&self) -> Rp<Object> {
+        self.0
+    }
+
+    // internal name of the throwable class, java/lang/IllegalStateException
+    pub fn class_name(&self) -> String {
+        self.0.class.name.to_string()
+    }
+
+    // detail message of the throwable
+    pub fn message(&self) -> Option<String> {
+        let msg: Rp<Object> = (self.0.get_field("detailMessage") as usize).into();
+        if msg.is_null() {
+            None
+        } else {
+            Some(msg.jstring())
+        }
+    }
+}
+
+// java.lang.Throwable.toString()
+impl std::fmt::Display for JavaException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.class_name().replace('/', ".");
+        match self.message() {
+            Some(m) => write!(f, "{}: {}", name, m),
+            None => f.write_str(&name),
+        }
+    }
+}
+
+impl std::fmt::Debug for JavaException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for JavaException {}
+
+#[cfg(test)]
+mod test {
+    use super::JValue;
+    use crate::heap::desc::JType;
+
+    #[test]
+    fn value_test() {
+        let vs = [
+            JValue::Boolean(true),
+            JValue::Byte(-2),
+            JValue::Char(0xffff),
+            JValue::Short(-300),
+            JValue::Int(-7),
+            JValue::Long(-1 << 40),
+            JValue::Float(1.5),
+            JValue::Double(-0.25),
+        ];
+        for v in vs.iter() {
+            assert_eq!(JValue::from_slot(v.type_char(), v.slot()), *v);
+        }
+
+        // small ints are sign extended to 32 bits only
+        assert_eq!(JValue::Byte(-1).slot(), 0xffff_ffff);
+        assert_eq!(JValue::Short(-1).as_int(), Some(-1));
+        assert!(JValue::Int(1).is_type(&JType::FI('I')));
+        assert!(!JValue::Int(1).is_type(&JType::DJ('J')));
+        assert!(JValue::Object(rp::Rp::null()).is_type(&JType::A("java/lang/String".into())));
+    }
+}
@@ -1,85 +1,475 @@
-use crate::heap::{class::Class, class::ClassMember, class::Object, misc::Heap, misc::SymRef};
-use crate::natives::NativeRegistry;
+use crate::heap::class::{Class, ClassMember, InitState, Object};
+use crate::heap::misc::Heap;
+use crate::natives::{ClosureNative, NativeEnv, NativeRegistry};
 use crate::runtime::frame::{JFrame, JStack};
-use crate::runtime::misc::BytesReader;
+use crate::runtime::sched::Scheduler;
+use crate::runtime::value::{JValue, JavaException};
+use cp::Symbol;
 use err::StringErr;
 use rp::Rp;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+// id of next thread, 0 means no thread in lock words
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(1);
+
+// values of java.lang.Thread.threadStatus
+pub mod thread_status {
+    pub const RUNNABLE: u32 = 0x0005;
+    pub const TERMINATED: u32 = 0x0002;
+}
 
 // jvm runtime representation
 pub struct Jvm {
     heap: Rp<Heap>,
-    thread: JThread,
+    // main thread
+    thread: Rp<JThread>,
     registry: Rp<NativeRegistry>,
+    sched: Rp<Scheduler>,
 }
 
 impl Jvm {
     pub fn new(cp: &str) -> Result<Self, StringErr> {
-        let heap = Heap::new(cp)?;
+        let mut heap = Heap::new(cp)?;
+        heap.props
+            .insert("java.class.path".to_string(), cp.to_string());
         let r = Rp::new(NativeRegistry::new());
+        let mut sched = Rp::new(Scheduler::new());
+        let thread = Rp::new(JThread::new(heap, r, sched));
+        sched.spawn(thread);
+
         Ok(Jvm {
             registry: r,
             heap: heap,
-            thread: JThread::new(heap, r),
+            thread,
+            sched,
         })
     }
 
     pub fn init(&mut self) {
         // 1. init java/lang/System
         // 2. init sun/misc/VM
-        let mut sys = self.heap.loader.load("java/lang/System");
-        sys.clinit(&mut self.thread);
-        self.thread.run();
+        for name in ["java/lang/System", "sun/misc/VM"].iter() {
+            let c = self.heap.loader.load(name);
+            if let Err(e) = self.thread.initialize(c) {
+                self.thread.report_uncaught(e.throwable());
+                return;
+            }
+        }
+    }
+
+    // bytes of locals and operand stacks of each thread, StackOverflowError beyond it
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.sched.stack_size = bytes;
+        self.thread.stack.limit = bytes;
+    }
+
+    // print classes when they are loaded, classes loaded before are printed at once
+    pub fn set_verbose_class(&mut self, verbose: bool) {
+        let mut loader = self.heap.loader;
+        if verbose && !loader.verbose {
+            for c in loader.classes() {
+                if c.dim == 0 && !c.is_primitive() && &c.name != "void" {
+                    println!("[Loaded {}]", c.name.replace('/', "."));
+                }
+            }
+        }
+        loader.verbose = verbose;
+    }
+
+    // system property returned by System.getProperty, overrides the default value
+    pub fn set_property(&mut self, key: &str, value: &str) {
+        self.heap.props.insert(key.to_string(), value.to_string());
+    }
+
+    // instructions a thread executes before switching to another thread
+    pub fn set_quantum(&mut self, n: u32) {
+        self.sched.quantum = n.max(1);
+    }
 
-        let mut sys = self.heap.loader.load("sun/misc/VM");
-        sys.clinit(&mut self.thread);
-        self.thread.run();
+    // invocations and backward branches before a method is compiled
+    pub fn set_jit_threshold(&mut self, invocations: u32, backedges: u32) {
+        self.heap.jit.invoke_threshold = invocations;
+        self.heap.jit.backedge_threshold = backedges;
     }
 
+    // max bytes of java heap
+    pub fn set_max_heap(&mut self, bytes: usize) {
+        self.heap.gc.set_limit(bytes);
+    }
+
+    // uncaught exceptions of main are left in the main thread
     pub fn run_class(&mut self, c: &str) -> Result<(), StringErr> {
+        match self.run_class_with_args(c, &[]) {
+            Err(RunError::Load(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // run main of class c with args as its String[] argument until every non-daemon thread
+    // terminated, return 0 if main returned or the status of System.exit
+    pub fn run_class_with_args(&mut self, c: &str, args: &[String]) -> Result<i32, RunError> {
         // load class
-        let c = self.heap.loader.load(c);
+        let c = match self.heap.loader.try_load(c) {
+            Some(c) => c,
+            None => {
+                let name = c.replace('/', ".");
+                return Err(RunError::Load(StringErr(format!(
+                    "Could not find or load main class {}",
+                    name
+                ))));
+            }
+        };
 
         // get main method
         let main = c.main_method();
 
         if main.is_null() {
-            return err!("class {} has no main method", &c.name);
+            let msg = format!("class {} has no main method", &c.name);
+            return Err(RunError::Load(StringErr(msg)));
+        }
+
+        // the initial class is initialized before main, JVMS 5.2
+        self.thread.exception = Rp::null();
+        match self.thread.initialize(c) {
+            Ok(()) => {
+                let mut arr = self.heap.new_array("java/lang/String", args.len());
+                for (i, a) in args.iter().enumerate() {
+                    arr.set(i, self.heap.create_jstr(a).ptr() as u64);
+                }
+                let f = self.thread.push_frame(main);
+                f.local_vars()[0] = arr.ptr() as u64;
+            }
+            // <clinit> failed, the main thread terminates with its throwable
+            Err(e) if self.sched.exit.is_none() => {
+                self.thread.exception = e.throwable();
+                self.thread.report_uncaught(e.throwable());
+            }
+            Err(_) => {}
+        }
+        self.sched.run();
+        self.thread.terminate();
+
+        match self.sched.exit {
+            Some(status) => Ok(status),
+            None if self.thread.exception.is_null() => Ok(0),
+            None => Err(RunError::Uncaught(self.thread.exception)),
+        }
+    }
+}
+
+// api for rust code embedding the vm, methods run on the main thread
+// objects returned are only reachable from rust and may be collected by the next call unless pinned
+impl Jvm {
+    // call static method of class, the class is initialized first
+    pub fn call_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        desc: &str,
+        args: &[JValue],
+    ) -> Result<JValue, JavaException> {
+        let c = self.load_class(class)?;
+        let m = c.lookup_method(name, desc);
+        if m.is_null() || !m.access_flags.is_static() {
+            let msg = format!("{}.{}{}", class.replace('/', "."), name, desc);
+            return Err(self.exception("java/lang/NoSuchMethodError", &msg));
+        }
+        self.call(m, args)
+    }
+
+    // create an object of class by its constructor of desc
+    pub fn new_object(
+        &mut self,
+        class: &str,
+        desc: &str,
+        args: &[JValue],
+    ) -> Result<Rp<Object>, JavaException> {
+        let c = self.load_class(class)?;
+        if c.access_flags.is_abstract() {
+            return Err(self.exception("java/lang/InstantiationError", &class.replace('/', ".")));
+        }
+
+        let m = c.lookup_method_in_class("<init>", desc);
+        if m.is_null() {
+            let msg = format!("{}.<init>{}", class.replace('/', "."), desc);
+            return Err(self.exception("java/lang/NoSuchMethodError", &msg));
         }
 
-        self.thread.push_frame(main);
-        self.thread.run();
+        self.main_thread()?.new_object(m, args)
+    }
+
+    // instance field of o, fields of a sub class hide fields of its super classes
+    pub fn get_field(&mut self, o: Rp<Object>, name: &str) -> Result<JValue, JavaException> {
+        let f = self.ins_field(o, name)?;
+        let t = f.field_type();
+        Ok(JValue::from_slot(t, o.load(f.offset, t)))
+    }
 
+    pub fn set_field(
+        &mut self,
+        mut o: Rp<Object>,
+        name: &str,
+        v: JValue,
+    ) -> Result<(), JavaException> {
+        let f = self.ins_field(o, name)?;
+        let t = f.field_type();
+        if v.type_char() != t {
+            let msg = format!("{} is not of type {}", name, &f.desc);
+            return Err(self.exception("java/lang/IllegalArgumentException", &msg));
+        }
+        o.store(f.offset, t, v.slot());
         Ok(())
     }
+
+    // java.lang.String of s, it is not interned
+    pub fn new_string(&mut self, s: &str) -> Rp<Object> {
+        self.heap.create_jstr(s)
+    }
+
+    // contents of a java.lang.String, None if o is null or not a string
+    pub fn get_string(&self, o: Rp<Object>) -> Option<String> {
+        self.heap.get_jstr(o)
+    }
+
+    // implement native method of class by f, replaces a native registered before
+    // arguments are decoded by the descriptor, an error returned is thrown to the caller
+    pub fn register_native<F>(&mut self, class: &str, name: &str, desc: &str, f: F)
+    where
+        F: Fn(&mut NativeEnv, &[JValue]) -> Result<JValue, JavaException> + 'static,
+    {
+        self.registry.register(Box::new(ClosureNative {
+            class: class.to_string(),
+            name: name.to_string(),
+            desc: desc.to_string(),
+            f: Box::new(f),
+        }));
+    }
+
+    // keep o alive until it is unpinned
+    pub fn pin(&mut self, o: Rp<Object>) {
+        self.heap.pin(o);
+    }
+
+    pub fn unpin(&mut self, o: Rp<Object>) {
+        self.heap.unpin(o);
+    }
+
+    fn load_class(&mut self, name: &str) -> Result<Rp<Class>, JavaException> {
+        match self.heap.loader.try_load(name) {
+            Some(c) => Ok(c),
+            None => Err(self.exception("java/lang/NoClassDefFoundError", name)),
+        }
+    }
+
+    fn ins_field(&mut self, o: Rp<Object>, name: &str) -> Result<Rp<ClassMember>, JavaException> {
+        if o.is_null() {
+            let ex = self
+                .thread
+                .new_throwable("java/lang/NullPointerException", None);
+            return Err(JavaException(ex));
+        }
+
+        let name: Symbol = name.into();
+        match o.class.ins_fields.iter().rev().find(|f| f.name == name) {
+            Some(f) => Ok(*f),
+            None => Err(self.exception("java/lang/NoSuchFieldError", name.as_str())),
+        }
+    }
+
+    // run m with args until it returns, this is the first argument of instance methods
+    fn call(&mut self, m: Rp<ClassMember>, args: &[JValue]) -> Result<JValue, JavaException> {
+        self.main_thread()?.call(m, args)
+    }
+
+    // main thread running calls of rust code, attached again if main has returned
+    fn main_thread(&mut self) -> Result<Rp<JThread>, JavaException> {
+        if self.sched.exit.is_some() {
+            return Err(self.exception("java/lang/IllegalStateException", "vm has exited"));
+        }
+
+        let mut th = self.thread;
+        // the main thread may keep the throwable of an earlier run
+        th.exception = Rp::null();
+        let o = th.j_thread;
+        if !o.is_null() && o.get_field("eetop") == 0 {
+            th.attach(o);
+        }
+        Ok(th)
+    }
+
+    fn exception(&mut self, class: &str, msg: &str) -> JavaException {
+        JavaException(self.thread.new_throwable(class, Some(msg)))
+    }
+}
+
+// main class failed to run
+pub enum RunError {
+    // main class or its main method not found
+    Load(StringErr),
+    // throwable which terminated the main thread, already reported
+    Uncaught(Rp<Object>),
+}
+
+impl std::fmt::Debug for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Load(e) => f.write_str(e),
+            RunError::Uncaught(ex) => f.write_str(&describe_throwable(*ex)),
+        }
+    }
 }
 
 pub struct JThread {
+    pub id: u32,
     pc: u32,
     stack: JStack,
     next_pc: Option<u32>,
     pub heap: Rp<Heap>,
     pub registry: Rp<NativeRegistry>,
+    pub sched: Rp<Scheduler>,
+    // java.lang.Thread object of this thread
+    pub j_thread: Rp<Object>,
+    // throwable which terminated this thread
+    pub exception: Rp<Object>,
+    // value returned by the last frame pushed by push_call
+    pub result: u64,
+    // frames at or below it belong to the native waiting in call
+    base: usize,
+    // nested calls in progress, see call
+    calls: u32,
+    // interrupt status, java.lang.Thread doesn't keep it
+    pub interrupted: bool,
+    // wake up time of Thread.sleep in progress
+    pub sleep_until: Option<Instant>,
+    // Unsafe.park permit
+    pub permit: bool,
+    // Unsafe.park in progress and its deadline
+    pub parking: bool,
+    pub park_until: Option<Instant>,
+    // current instruction is blocked and will be retried
+    parked: bool,
+    // give up the rest of time slice
+    yielded: bool,
+    // (super, class) whose initialization waits for the <clinit> of super
+    waiting: Vec<(Rp<Class>, Rp<Class>)>,
 }
 
 impl JThread {
     pub fn branch(&mut self, off: i32) {
+        if off < 0 {
+            self.cur_frame().method.get_mut().backedges += 1;
+        }
         self.next_pc = Some((self.pc as i32 + off) as u32);
     }
 
     pub fn revert_pc(&mut self) {
         self.next_pc = Some(self.pc);
     }
+
+    // blocked, switch to another thread and retry the current instruction later
+    pub fn park(&mut self) {
+        self.revert_pc();
+        self.parked = true;
+    }
+
+    // switch to another thread after the current instruction
+    pub fn yield_now(&mut self) {
+        self.yielded = true;
+    }
 }
 
 impl JThread {
-    pub fn new(heap: Rp<Heap>, registry: Rp<NativeRegistry>) -> Self {
+    pub fn new(heap: Rp<Heap>, registry: Rp<NativeRegistry>, sched: Rp<Scheduler>) -> Self {
+        let mut stack = JStack::new(heap);
+        stack.limit = sched.stack_size;
+
         Self {
+            id: NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed),
             heap,
             pc: 0,
-            stack: JStack::new(heap),
+            stack,
             next_pc: None,
             registry,
+            sched,
+            j_thread: Rp::null(),
+            exception: Rp::null(),
+            result: 0,
+            base: 0,
+            calls: 0,
+            interrupted: false,
+            sleep_until: None,
+            permit: false,
+            parking: false,
+            park_until: None,
+            parked: false,
+            yielded: false,
+            waiting: Vec::new(),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    pub fn is_daemon(&self) -> bool {
+        !self.j_thread.is_null() && self.j_thread.get_field("daemon") != 0
+    }
+
+    // bind a java.lang.Thread object to this thread, Thread.isAlive() checks eetop
+    pub fn attach(&mut self, mut o: Rp<Object>) {
+        o.set_field("eetop", self as *mut JThread as u64);
+        o.set_field("threadStatus", thread_status::RUNNABLE as u64);
+        self.j_thread = o;
+    }
+
+    // Thread object of this thread, the main thread isn't started by java so its object is built here
+    pub fn thread_obj(&mut self) -> Rp<Object> {
+        if !self.j_thread.is_null() {
+            return self.j_thread;
         }
+
+        let c = self.heap.loader.load("java/lang/ThreadGroup");
+        let mut g = Class::new_obj(c);
+        g.set_field_ref("name", self.heap.new_jstr("main"));
+        g.set_field("maxPriority", 10);
+
+        let c = self.heap.loader.load("java/lang/Object");
+        let lock = Class::new_obj(c);
+
+        let c = self.heap.loader.load("java/lang/Thread");
+        let mut o = Class::new_obj(c);
+        o.set_field_ref("group", g);
+        o.set_field_ref("name", self.heap.new_char_array("main"));
+        o.set_field_ref("blockerLock", lock);
+        o.set_field("priority", 5);
+        o.set_field("tid", self.id as u64);
+        self.attach(o);
+        o
+    }
+
+    // read and clear interrupt status
+    pub fn take_interrupt(&mut self) -> bool {
+        let r = self.interrupted;
+        self.interrupted = false;
+        r
+    }
+
+    // thread bound to a java.lang.Thread object, null if it's not alive
+    pub fn of(o: Rp<Object>) -> Rp<JThread> {
+        (o.get_field("eetop") as usize).into()
+    }
+
+    // mark Thread object terminated and wake up threads joining it
+    pub fn terminate(&mut self) {
+        let mut o = self.j_thread;
+        if o.is_null() || o.get_field("eetop") == 0 {
+            return;
+        }
+
+        o.set_field("eetop", 0);
+        o.set_field("threadStatus", thread_status::TERMINATED as u64);
+        self.heap.monitor_wake_all(o);
     }
     #[inline]
     pub fn stack(&self) -> &JStack {
@@ -87,7 +477,8 @@ impl JThread {
     }
 
     #[inline]
-    pub fn push_frame(&mut self, m: Rp<ClassMember>) -> Rp<JFrame> {
+    pub fn push_frame(&mut self, mut m: Rp<ClassMember>) -> Rp<JFrame> {
+        m.invocations = m.invocations.saturating_add(1);
         self.stack.push_frame(m)
     }
 
@@ -96,11 +487,93 @@ impl JThread {
         self.stack.back_frame(i)
     }
 
-    #[inline]
+    // pop current frame and release the monitor of its synchronized method
     pub fn pop_frame(&mut self) {
+        let mut f = self.cur_frame();
+        if !f.monitor.is_null() {
+            self.heap.monitor_exit(f.monitor, self.id);
+            f.monitor = Rp::null();
+        }
+
+        // <clinit> returned normally
+        if !f.init.is_null() {
+            f.init.init_state = InitState::Initialized;
+            let c = f.init;
+            self.waiting.retain(|(s, _)| s.ptr() != c.ptr());
+            f.init = Rp::null();
+        }
         self.stack.pop_frame()
     }
 
+    // initialize class c per JVMS 5.5, true if the current instruction can use c
+    // otherwise the instruction is retried after <clinit> has run or another thread finished
+    // initializing c, or an exception is thrown
+    pub fn init_class(&mut self, mut c: Rp<Class>) -> bool {
+        match c.init_state {
+            InitState::Initialized => return true,
+            // recursive request of the initializing thread
+            InitState::BeingInitialized(id) if id == self.id => return true,
+            InitState::BeingInitialized(_) => {
+                self.park();
+                return false;
+            }
+            InitState::Erroneous => {
+                let msg = format!("Could not initialize class {}", c.name.replace('/', "."));
+                self.throw_new("java/lang/NoClassDefFoundError", Some(&msg));
+                return false;
+            }
+            InitState::Uninitialized => {}
+        }
+
+        // super class and super interfaces with default methods first, one at a time
+        if !c.access_flags.is_iface() {
+            let mut supers = c.default_ifaces();
+            if !c.super_class.is_null() {
+                supers.insert(0, c.super_class);
+            }
+            for s in supers {
+                if !self.init_class(s) {
+                    // c fails along with its super, JVMS 5.5 step 7
+                    match s.init_state {
+                        InitState::Erroneous => self.init_failed(c),
+                        InitState::BeingInitialized(id) if id != self.id => {}
+                        _ => self.waiting.push((s, c)),
+                    }
+                    return false;
+                }
+            }
+        }
+        self.waiting.retain(|(_, d)| d.ptr() != c.ptr());
+
+        let init = c.clinit_method();
+        if init.is_null() {
+            c.init_state = InitState::Initialized;
+            return true;
+        }
+
+        c.init_state = InitState::BeingInitialized(self.id);
+        let mut f = self.push_frame(init);
+        f.init = c;
+        self.revert_pc();
+        false
+    }
+
+    // mark c and the classes waiting for it erroneous
+    fn init_failed(&mut self, mut c: Rp<Class>) {
+        c.init_state = InitState::Erroneous;
+        let mut i = 0;
+        while i < self.waiting.len() {
+            let (s, d) = self.waiting[i];
+            if s.ptr() == c.ptr() {
+                self.waiting.swap_remove(i);
+                self.init_failed(d);
+                i = 0;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
     #[inline]
     pub fn prev_frame(&self) -> Rp<JFrame> {
         self.stack.prev_frame()
@@ -128,60 +601,480 @@ impl JThread {
         // }
     }
 
-    pub fn run(&mut self) {
+    // execute at most n instructions, stop early if blocked or yielded
+    // return false if no instruction completed
+    pub fn run(&mut self, n: u32) -> bool {
         use crate::ins::Ins;
-        while !self.stack.is_empty() {
+        let mut progress = false;
+        self.parked = false;
+        self.yielded = false;
+
+        // compiled code takes one unit per backward branch
+        let mut budget = n as i64;
+        while budget > 0 {
+            budget -= 1;
+            if self.stack.len() <= self.base {
+                break;
+            }
+
+            // the frame pushed beyond the stack size is dropped before it runs
+            if self.stack.overflow() {
+                let mut f = self.cur_frame();
+                if !f.init.is_null() {
+                    self.init_failed(f.init);
+                    f.init = Rp::null();
+                }
+                self.stack.pop_frame();
+                self.throw_new("java/lang/StackOverflowError", None);
+                continue;
+            }
             // self.print_stack('=');
             let f = self.cur_frame();
             self.pc = f.next_pc;
 
             let method = f.method;
+            f.get_mut().pc = self.pc;
+
+            // safepoint, every live object is reachable from frames or heap roots here
+            // reference maps of frames are taken at their pc
+            if self.heap.gc.should_collect() {
+                let mut heap = self.heap;
+                heap.collect(self.sched.threads());
+            }
             self.next_pc = None;
-            let mut rd = BytesReader {
-                bytes: &method.code,
-                pc: self.pc,
-            };
 
-            let op: u8 = rd.u8();
+            // synchronized method locks its monitor before the first instruction
+            if self.pc == 0 && f.monitor.is_null() && method.access_flags.is_sync() {
+                let o = if method.access_flags.is_static() {
+                    method.class.j_class
+                } else {
+                    f.this()
+                };
+                if !self.heap.monitor_enter(o, self.id) {
+                    break;
+                }
+                f.get_mut().monitor = o;
+            }
 
-            // wide
-            if op == 0xc4 {
-                let op: u8 = rd.u8();
-                op.step(&mut rd, self, f.get_mut(), true);
-            } else {
-                op.step(&mut rd, self, f.get_mut(), false);
+            let mut heap = self.heap;
+            if heap.jit.is_hot(&method) {
+                heap.jit.compile(method.get_mut());
             }
 
-            f.get_mut().next_pc = rd.pc;
+            // compiled code runs until an instruction it leaves to the interpreter
+            if let Some(code) = method.compiled.as_ref() {
+                if let Some(pc) = code.run(f.get_mut(), self.pc, &mut budget) {
+                    progress |= pc != self.pc;
+                    self.pc = pc;
+                    f.get_mut().pc = pc;
+                }
+            }
+
+            let ins = method.get_mut().insn(self.pc);
+            let next = ins.next;
+            ins.step(self, f.get_mut());
+
+            f.get_mut().next_pc = next;
 
             match self.next_pc {
                 Some(pc) => f.get_mut().next_pc = pc,
                 _ => {}
             };
             // self.print_stack('*');
+
+            if self.parked {
+                break;
+            }
+            progress = true;
+            if self.yielded {
+                break;
+            }
+        }
+        progress
+    }
+
+    // unwind frames until a handler accepts ex, execution continues at the handler pc
+    pub fn throw(&mut self, mut ex: Rp<Object>) {
+        let cur = self.cur_frame();
+
+        while self.stack.len() > self.base {
+            let mut f = self.cur_frame();
+
+            match f.find_handler(ex) {
+                Some(pc) => {
+                    f.clear_stack();
+                    f.push_obj(ex);
+                    f.next_pc = pc;
+                    if f.id() == cur.id() {
+                        self.next_pc = Some(pc);
+                    }
+                    return;
+                }
+                None => {
+                    let c = f.init;
+                    f.init = Rp::null();
+                    self.pop_frame();
+
+                    // <clinit> completed abruptly, exceptions other than errors are wrapped
+                    if !c.is_null() {
+                        self.init_failed(c);
+                        let error = self.heap.loader.load("java/lang/Error");
+                        if !ex.instance_of(&error) {
+                            let mut e =
+                                self.new_throwable("java/lang/ExceptionInInitializerError", None);
+                            e.set_field_ref("exception", ex);
+                            e.set_field_ref("cause", ex);
+                            ex = e;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.exception = ex;
+        // thrown out of a nested call, its caller takes it
+        if self.calls == 0 {
+            self.report_uncaught(ex);
         }
     }
 
-    // create a new thread to invoke object
-    pub fn invoke_obj(
+    // allocate a throwable of class without running its constructor and throw it
+    pub fn throw_new(&mut self, class: &str, msg: Option<&str>) {
+        let ex = self.new_throwable(class, msg);
+        self.throw(ex);
+    }
+
+    // allocate a throwable of class without running its constructor
+    pub fn new_throwable(&mut self, class: &str, msg: Option<&str>) -> Rp<Object> {
+        let c = self.heap.loader.load(class);
+        let mut ex = Class::new_obj(c);
+
+        if let Some(m) = msg {
+            let s = self.heap.new_jstr(m);
+            ex.set_field_ref("detailMessage", s);
+        }
+
+        let this = ex;
+        ex.set_field_ref("cause", this);
+        let trace = self.stack_trace(ex);
+        ex.set_field_ref("backtrace", trace);
+        ex
+    }
+
+    #[inline]
+    pub fn throw_npe(&mut self) {
+        self.throw_new("java/lang/NullPointerException", None)
+    }
+
+    // throw NullPointerException or ArrayIndexOutOfBoundsException if arr[i] is not accessible
+    pub fn check_index(&mut self, arr: Rp<Object>, i: i32) -> bool {
+        if arr.is_null() {
+            self.throw_npe();
+            return false;
+        }
+
+        if i < 0 || i as usize >= arr.size {
+            self.throw_new(
+                "java/lang/ArrayIndexOutOfBoundsException",
+                Some(&i.to_string()),
+            );
+            return false;
+        }
+        true
+    }
+
+    // make room for an allocation of bytes, collect garbage if the heap limit would be exceeded
+    // throw OutOfMemoryError if there is still no room
+    pub fn reserve(&mut self, bytes: usize) -> bool {
+        let mut heap = self.heap;
+        if heap.gc.fits(bytes) {
+            return true;
+        }
+
+        heap.collect(self.sched.threads());
+        if heap.gc.fits(bytes) {
+            return true;
+        }
+
+        self.throw_new("java/lang/OutOfMemoryError", Some("Java heap space"));
+        false
+    }
+
+    // build StackTraceElement[] from current frames
+    // skip frames of fillInStackTrace and constructors of the throwable
+    pub fn stack_trace(&mut self, ex: Rp<Object>) -> Rp<Object> {
+        let mut skip = true;
+        let mut elements: Vec<Rp<Object>> = Vec::new();
+        let c = self.heap.loader.load("java/lang/StackTraceElement");
+
+        for i in 1..=self.stack.len() {
+            let f = self.back_frame(i);
+            let m = f.method;
+
+            if skip
+                && (m.name == "fillInStackTrace"
+                    || (m.name == "<init>" && ex.instance_of(&m.class)))
+            {
+                continue;
+            }
+            skip = false;
+
+            let mut e = Class::new_obj(c);
+            let class_name = m.class.name.replace('/', ".");
+            e.set_field_ref("declaringClass", self.heap.new_jstr(&class_name));
+            e.set_field_ref("methodName", self.heap.new_jstr(&m.name));
+            if !m.class.source_file.is_empty() {
+                e.set_field_ref("fileName", self.heap.new_jstr(&m.class.source_file));
+            }
+            e.set_field("lineNumber", m.line_number(f.pc) as u32 as u64);
+            elements.push(e);
+        }
+
+        let mut arr = self
+            .heap
+            .new_array("java/lang/StackTraceElement", elements.len());
+        for i in 0..elements.len() {
+            arr.set(i, elements[i].ptr() as u64);
+        }
+        arr
+    }
+
+    fn report_uncaught(&self, ex: Rp<Object>) {
+        let name: Rp<Object> = if self.j_thread.is_null() {
+            Rp::null()
+        } else {
+            (self.j_thread.get_field("name") as usize).into()
+        };
+        let name = if name.is_null() {
+            "main".to_string()
+        } else {
+            String::from_utf16_lossy(name.jarray())
+        };
+        eprint!("Exception in thread \"{}\" ", name);
+        let mut cur = ex;
+
+        loop {
+            eprintln!("{}", describe_throwable(cur));
+            print_stack_trace(cur);
+
+            let cause: Rp<Object> = (cur.get_field("cause") as usize).into();
+            if cause.is_null() || cause.ptr() == cur.ptr() {
+                break;
+            }
+            eprint!("Caused by: ");
+            cur = cause;
+        }
+    }
+
+    // push a frame of m with args as its locals, the value it returns is kept in result
+    pub fn push_call(&mut self, m: Rp<ClassMember>, args: &[JValue]) -> Result<(), JavaException> {
+        let this = !m.access_flags.is_static() as usize;
+        let valid = args.len() == m.m_desc.params.len() + this
+            && (this == 0 || args[0].as_object().is_some_and(|o| !o.is_null()))
+            && args[this..]
+                .iter()
+                .zip(m.m_desc.params.iter())
+                .all(|(a, t)| a.is_type(t));
+        if !valid {
+            let msg = format!("arguments of {}.{}{}", &m.class.name, &m.name, &m.desc);
+            let ex = self.new_throwable("java/lang/IllegalArgumentException", Some(&msg));
+            return Err(JavaException(ex));
+        }
+
+        let mut f = self.push_frame(m);
+        f.call = true;
+        let mut i = 0;
+        for a in args.iter() {
+            f.local_vars()[i] = a.slot();
+            i += match a {
+                JValue::Long(_) | JValue::Double(_) => 2,
+                _ => 1,
+            };
+        }
+        Ok(())
+    }
+
+    // invoke m with args and run this thread until it returns, natives call java by it
+    // the class of a static method is initialized first
+    // objects held only by rust code are not roots, keep them in frame slots across calls
+    pub fn call(&mut self, m: Rp<ClassMember>, args: &[JValue]) -> Result<JValue, JavaException> {
+        self.nested(|th| {
+            if m.access_flags.is_static() {
+                th.pinning(args, |th| th.init_nested(m.class))?;
+            }
+            th.push_call(m, args)?;
+            th.run_nested();
+            th.take_exception()?;
+            Ok(JValue::from_type(&m.m_desc.ret, th.result))
+        })
+    }
+
+    // create an object of the class of constructor m and run it with args like call
+    pub fn new_object(
         &mut self,
-        obj: &mut Object,
-        name: &str,
-        desc: &str,
-        args: &[u64],
-        drop: bool,
-    ) {
-        let m = obj.class.lookup_method(name, desc);
-        let mut nf = self.push_frame(m);
-        nf.no_ret = drop;
-        // nf.local_vars().copy_from_slice(args);
+        m: Rp<ClassMember>,
+        args: &[JValue],
+    ) -> Result<Rp<Object>, JavaException> {
+        let c = m.class;
+        self.nested(|th| {
+            th.pinning(args, |th| {
+                th.init_nested(c)?;
+                if !th.reserve(Object::bytes(c.ins_words(), 8)) {
+                    th.take_exception()?;
+                }
+                Ok(())
+            })?;
+
+            // the frame of constructor keeps the object alive
+            let o = Class::new_obj(c);
+            let mut full = vec![JValue::Object(o)];
+            full.extend_from_slice(args);
+            th.push_call(m, &full)?;
+            th.run_nested();
+            th.take_exception()?;
+            Ok(o)
+        })
+    }
+
+    // initialize class c and run its <clinit> until it returns
+    pub fn initialize(&mut self, c: Rp<Class>) -> Result<(), JavaException> {
+        self.nested(|th| th.init_nested(c))
+    }
+
+    // run f above the frames of the instruction in progress, throwables it takes aren't uncaught
+    fn nested<T, F>(&mut self, f: F) -> Result<T, JavaException>
+    where
+        F: FnOnce(&mut JThread) -> Result<T, JavaException>,
+    {
+        // the nested run replaces the state of the instruction in progress
+        let saved = (self.pc, self.next_pc, self.parked, self.yielded, self.base);
+        self.base = self.stack.len();
+        self.parked = false;
+        self.calls += 1;
+        let r = f(self);
+        self.calls -= 1;
+        (self.pc, self.next_pc, self.parked, self.yielded, self.base) = saved;
+        r
+    }
+
+    fn init_nested(&mut self, c: Rp<Class>) -> Result<(), JavaException> {
+        while !self.init_class(c) {
+            self.run_nested();
+            self.take_exception()?;
+        }
+        Ok(())
+    }
+
+    // keep objects of args alive while f runs, the caller holds them only in rust
+    fn pinning<T, F>(&mut self, args: &[JValue], f: F) -> T
+    where
+        F: FnOnce(&mut JThread) -> T,
+    {
+        let objs: Vec<Rp<Object>> = args
+            .iter()
+            .filter_map(|a| a.as_object())
+            .filter(|o| !o.is_null())
+            .collect();
+        for &o in objs.iter() {
+            self.heap.pin(o);
+        }
+        let r = f(self);
+        for &o in objs.iter() {
+            self.heap.unpin(o);
+        }
+        r
+    }
+
+    // run frames above base until they return, other threads run while this one is blocked
+    fn run_nested(&mut self) {
+        let me = self as *const JThread as usize;
+        while self.sched.exit.is_none() {
+            let progress = self.stack.len() > self.base && self.run(self.sched.quantum);
+            if self.stack.len() <= self.base && !self.parked {
+                return;
+            }
+
+            let others = self.sched.run_others(me);
+            if !progress && !others {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            // blocked on a class being initialized by another thread, retry
+            if self.stack.len() <= self.base {
+                return;
+            }
+        }
+
+        // the vm halted, frames above base never return
+        while self.stack.len() > self.base {
+            self.stack.pop_frame();
+        }
+    }
+
+    // throwable thrown out of a nested call, ThreadDeath if the vm halted
+    fn take_exception(&mut self) -> Result<(), JavaException> {
+        if self.sched.exit.is_some() {
+            let ex = self.new_throwable("java/lang/ThreadDeath", None);
+            return Err(JavaException(ex));
+        }
+
+        let ex = self.exception;
+        if ex.is_null() {
+            return Ok(());
+        }
+        self.exception = Rp::null();
+        Err(JavaException(ex))
+    }
+
+    // a nested call of a native is in progress, the thread must not run from the scheduler
+    pub fn in_call(&self) -> bool {
+        self.calls > 0
+    }
+}
+
+// java.lang.Throwable.toString() without calling into java
+fn describe_throwable(ex: Rp<Object>) -> String {
+    let name = ex.class.name.replace('/', ".");
+    let msg: Rp<Object> = (ex.get_field("detailMessage") as usize).into();
+
+    if msg.is_null() {
+        name
+    } else {
+        format!("{}: {}", name, msg.jstring())
+    }
+}
+
+// print StackTraceElement[] filled by Throwable.fillInStackTrace
+fn print_stack_trace(ex: Rp<Object>) {
+    let trace: Rp<Object> = (ex.get_field("backtrace") as usize).into();
+
+    if trace.is_null() {
+        return;
+    }
+
+    for &p in trace.fields().iter() {
+        let e: Rp<Object> = (p as usize).into();
+        let class: Rp<Object> = (e.get_field("declaringClass") as usize).into();
+        let method: Rp<Object> = (e.get_field("methodName") as usize).into();
+        let file: Rp<Object> = (e.get_field("fileName") as usize).into();
+        let line = e.get_field("lineNumber") as u32 as i32;
+
+        let src = if line == -2 {
+            "Native Method".to_string()
+        } else if file.is_null() {
+            "Unknown Source".to_string()
+        } else if line < 0 {
+            file.jstring()
+        } else {
+            format!("{}:{}", file.jstring(), line)
+        };
+
+        eprintln!("\tat {}.{}({})", class.jstring(), method.jstring(), src);
     }
 }
 
-// TODO: limit stack size
 #[cfg(test)]
 mod test {
-    use crate::runtime::vm::Jvm;
+    use crate::runtime::vm::{Jvm, RunError};
 
     #[test]
     fn test_jvm() {
@@ -205,6 +1098,8 @@ mod test {
     fn test_array() {
         let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
         jvm.run_class("test/MultiDimensionalArray").unwrap();
+        assert!(jvm.thread.exception.is_null());
+        assert!(jvm.heap.used_bytes() <= jvm.heap.capacity());
     }
 
     #[test]
@@ -213,6 +1108,285 @@ mod test {
         jvm.run_class("test/Debug").unwrap();
     }
 
+    #[test]
+    fn test_exception() {
+        use crate::runtime::value::JavaException;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        // exceptions caught in main don't leave it, the last one is uncaught
+        match jvm.run_class_with_args("test/ExceptionTest", &[]) {
+            Err(RunError::Uncaught(ex)) => {
+                assert_eq!(&ex.class.name, "java/lang/IllegalStateException");
+                assert_eq!(JavaException(ex).message().as_deref(), Some("uncaught"));
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_runtime_exception() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/RuntimeExceptionTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_lambda() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/LambdaTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_method_handle() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/MethodHandleTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_gc() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.set_max_heap(64 * 1024 * 1024);
+        jvm.run_class("test/GcTest").unwrap();
+        assert!(jvm.heap.used_bytes() <= 64 * 1024 * 1024);
+        assert!(jvm.heap.used_bytes() <= jvm.heap.capacity());
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_identity_hash() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.set_max_heap(64 * 1024 * 1024);
+        jvm.run_class("test/HashTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/SyncTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_method_selection() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/DefaultMethodTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_unsatisfied_link() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/NativeTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_jit() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.set_jit_threshold(10, 100);
+        jvm.run_class("test/JitTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+        assert!(jvm.heap.jit.compiled > 0);
+    }
+
+    #[test]
+    fn test_inline_cache() {
+        use crate::heap::icache::CacheState;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/InlineCacheTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+
+        let stats = jvm.heap.inline_cache_stats();
+        let site = |name: &str| {
+            stats
+                .iter()
+                .find(|s| s.class == "test/InlineCacheTest" && s.method == name)
+                .unwrap()
+                .clone()
+        };
+
+        let s = site("mono");
+        assert_eq!((s.state, s.hits, s.misses), (CacheState::Monomorphic, 9, 1));
+        let s = site("poly");
+        assert_eq!((s.state, s.hits, s.misses), (CacheState::Polymorphic, 8, 2));
+        let s = site("mega");
+        assert_eq!(s.state, CacheState::Megamorphic);
+        assert_eq!(s.hits, 0);
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        // switch often so threads interleave
+        jvm.set_quantum(100);
+        jvm.run_class("test/ThreadTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+        assert_eq!(jvm.sched.threads().len(), 1);
+    }
+
+    #[test]
+    fn test_unsafe() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/UnsafeTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+    }
+
+    #[test]
+    fn test_field_layout() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/FieldLayoutTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+
+        // words: l | b z s i | o | c
+        let c = jvm.heap.loader.load("test/FieldLayoutTest$Sub");
+        assert_eq!((c.ins_fields.len(), c.ins_words()), (7, 4));
+        let offsets: Vec<usize> = c.ins_fields.iter().map(|f| f.offset).collect();
+        assert_eq!(offsets, vec![8, 0, 9, 10, 24, 12, 16]);
+        assert_eq!(c.ins_ref_map, vec![false, false, true, false]);
+    }
+
+    #[test]
+    fn test_class_init() {
+        use crate::heap::class::InitState;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/ClassInitTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+
+        let mut state = |name: &str| jvm.heap.loader.load(name).init_state;
+        assert_eq!(state("test/ClassInitTest$Sub"), InitState::Initialized);
+        assert_eq!(state("test/ClassInitTest$Broken"), InitState::Erroneous);
+        assert_eq!(state("test/ClassInitTest$Failing"), InitState::Erroneous);
+        assert_eq!(state("test/ClassInitTest$Derived"), InitState::Erroneous);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.set_stack_size(64 * 1024);
+        let args = ["a".to_string(), "b c".to_string()];
+        assert_eq!(jvm.run_class_with_args("test/ExitTest", &args).unwrap(), 3);
+        assert!(jvm.thread.exception.is_null());
+        assert!(jvm.run_class("test/NoSuchClass").is_err());
+    }
+
+    #[test]
+    fn test_call_api() {
+        use crate::runtime::value::JValue;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        let c = "test/ApiTest";
+        let r = jvm.call_static(c, "add", "(II)I", &[JValue::Int(2), JValue::Int(-5)]);
+        assert_eq!(r.unwrap(), JValue::Int(-3));
+        let r = jvm.call_static(c, "mul", "(JI)J", &[JValue::Long(1 << 40), JValue::Int(3)]);
+        assert_eq!(r.unwrap(), JValue::Long(3 << 40));
+        let r = jvm.call_static(
+            c,
+            "scale",
+            "(DF)D",
+            &[JValue::Double(1.5), JValue::Float(2.0)],
+        );
+        assert_eq!(r.unwrap(), JValue::Double(3.0));
+
+        let s = JValue::Object(jvm.new_string("rust"));
+        let r = jvm.call_static(c, "greet", "(Ljava/lang/String;)Ljava/lang/String;", &[s]);
+        let greeting = r.unwrap().as_object().unwrap();
+        assert_eq!(jvm.get_string(greeting).as_deref(), Some("hello rust"));
+
+        let o = jvm.new_object(c, "(Ljava/lang/String;I)V", &[s, JValue::Int(7)]);
+        let o = o.unwrap();
+        jvm.pin(o);
+        assert_eq!(jvm.get_field(o, "count").unwrap(), JValue::Int(7));
+        assert_eq!(jvm.get_field(o, "name").unwrap(), s);
+        jvm.set_field(o, "flag", JValue::Byte(-2)).unwrap();
+        assert!(jvm.set_field(o, "flag", JValue::Int(1)).is_err());
+        assert!(jvm.get_field(o, "missing").is_err());
+        let r = jvm.call_static(c, "total", "(Ltest/ApiTest;)I", &[JValue::Object(o)]);
+        assert_eq!(r.unwrap(), JValue::Int(5));
+        jvm.unpin(o);
+
+        let e = jvm
+            .call_static(c, "fail", "(Ljava/lang/String;)V", &[s])
+            .unwrap_err();
+        assert_eq!(e.class_name(), "java/lang/IllegalStateException");
+        assert_eq!(e.message().as_deref(), Some("rust"));
+        // arguments must match the descriptor
+        assert!(jvm
+            .call_static(c, "add", "(II)I", &[JValue::Int(1)])
+            .is_err());
+        assert!(jvm
+            .call_static(c, "add", "(II)I", &[s, JValue::Int(1)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_nested_call() {
+        use crate::runtime::value::JValue;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        jvm.run_class("test/CallTest").unwrap();
+        assert!(jvm.thread.exception.is_null());
+        assert_eq!((jvm.thread.base, jvm.thread.calls), (0, 0));
+
+        // calls of rust code after main returned run on a live main thread
+        let r = jvm.call_static("test/ApiTest", "alive", "()Z", &[]);
+        assert_eq!(r.unwrap(), JValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_register_native() {
+        use crate::runtime::value::JValue;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut jvm = Jvm::new(".:test/rt.jar").unwrap();
+        let c = "test/HostTest";
+        jvm.register_native(c, "add", "(IJ)I", |_, args| {
+            let (a, b) = (args[0].as_int().unwrap(), args[1].as_long().unwrap());
+            Ok(JValue::Int(a + b as i32))
+        });
+        jvm.register_native(c, "half", "(D)D", |_, args| {
+            Ok(JValue::Double(args[0].as_double().unwrap() / 2.0))
+        });
+
+        // natives call back into java
+        let calls = Rc::new(Cell::new(0));
+        let n = calls.clone();
+        jvm.register_native(
+            c,
+            "greet",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            move |env, args| {
+                n.set(n.get() + 1);
+                let this = args[0].as_object().unwrap();
+                let name = this.get_field("name") as usize;
+                let s = env.call_method(name.into(), "toString", "()Ljava/lang/String;", &[])?;
+                let s = s.as_object().unwrap();
+                // s is only held here while java runs
+                env.pin(s);
+                let r = env.call_static(c, "twice", "(I)I", &[JValue::Int(2)]);
+                env.unpin(s);
+                assert_eq!(r?, JValue::Int(4));
+
+                let to = env.get_string(args[1].as_object().unwrap()).unwrap();
+                let msg = format!("{} greets {}", env.get_string(s).unwrap(), to);
+                Ok(JValue::Object(env.new_string(&msg)))
+            },
+        );
+        jvm.register_native(c, "fail", "(Ljava/lang/String;)V", |env, args| {
+            let msg = env.get_string(args[0].as_object().unwrap()).unwrap();
+            Err(env.exception("java/lang/IllegalStateException", &msg))
+        });
+
+        jvm.run_class(c).unwrap();
+        assert!(jvm.thread.exception.is_null());
+        assert_eq!(calls.get(), 1);
+    }
+
     #[test]
     fn test_vec() {
         use rp::Rp;
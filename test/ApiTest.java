@@ -0,0 +1,40 @@
+package test;
+
+public class ApiTest {
+    String name;
+    int count;
+    byte flag;
+
+    public ApiTest(String name, int count) {
+        this.name = name;
+        this.count = count;
+    }
+
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    static long mul(long a, int b) {
+        return a * b;
+    }
+
+    static double scale(double d, float f) {
+        return d * f;
+    }
+
+    static String greet(String name) {
+        return "hello " + name;
+    }
+
+    static int total(ApiTest t) {
+        return t.count + t.flag;
+    }
+
+    static boolean alive() {
+        return Thread.currentThread().isAlive();
+    }
+
+    static void fail(String msg) {
+        throw new IllegalStateException(msg);
+    }
+}
@@ -0,0 +1,58 @@
+package test;
+
+import java.io.IOException;
+import java.security.AccessController;
+import java.security.PrivilegedAction;
+import java.security.PrivilegedActionException;
+import java.security.PrivilegedExceptionAction;
+
+public class CallTest {
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        String s = AccessController.doPrivileged(new PrivilegedAction<String>() {
+            public String run() {
+                return "privileged";
+            }
+        });
+        check(s.equals("privileged"), "result");
+
+        // the action calls back into a native which calls java again
+        String nested = AccessController.doPrivileged(new PrivilegedAction<String>() {
+            public String run() {
+                return AccessController.doPrivileged(new PrivilegedAction<String>() {
+                    public String run() {
+                        return "inner";
+                    }
+                }) + " outer";
+            }
+        });
+        check(nested.equals("inner outer"), "nested");
+
+        try {
+            AccessController.doPrivileged(new PrivilegedExceptionAction<Object>() {
+                public Object run() throws Exception {
+                    throw new IOException("io");
+                }
+            });
+            check(false, "checked exception not thrown");
+        } catch (PrivilegedActionException e) {
+            check(e.getException() instanceof IOException, "checked exception not wrapped");
+        }
+
+        try {
+            AccessController.doPrivileged(new PrivilegedAction<Object>() {
+                public Object run() {
+                    throw new IllegalStateException("state");
+                }
+            });
+            check(false, "unchecked exception not thrown");
+        } catch (IllegalStateException e) {
+            check(e.getMessage().equals("state"), "unchecked exception wrapped");
+        }
+    }
+}
@@ -0,0 +1,128 @@
+package test;
+
+public class ClassInitTest {
+    static String order = "";
+
+    static int log(String s) {
+        order += s + " ";
+        return 0;
+    }
+
+    static int zero() {
+        return 0;
+    }
+
+    static int fail() {
+        throw new AssertionError("fail");
+    }
+
+    static class Base {
+        static int x = log("Base");
+    }
+
+    static class Sub extends Base {
+        static int y = log("Sub");
+    }
+
+    interface I {
+        int A = log("I");
+    }
+
+    interface J {
+        int B = log("J");
+
+        default void m() {
+        }
+    }
+
+    static class Impl implements I, J {
+        static int z = log("Impl");
+    }
+
+    static class A {
+        static int a = B.b + 1;
+    }
+
+    static class B {
+        static int b = A.a + 1;
+    }
+
+    static class Broken {
+        static int x = 1 / zero();
+    }
+
+    static class Failing {
+        static int x = fail();
+    }
+
+    static class BrokenBase {
+        static int x = 1 / zero();
+    }
+
+    static class Derived extends BrokenBase {
+        static int y = log("Derived");
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        // super class first, interfaces only if they declare default methods
+        new Sub();
+        new Impl();
+        check(order.equals("Base Sub J Impl "), "initialization order " + order);
+        check(I.A == 0 && order.endsWith("I "), "interface on field access");
+
+        // recursive request of the initializing thread returns
+        check(A.a == 2 && B.b == 1, "recursive initialization");
+
+        try {
+            int x = Broken.x;
+            check(false, "ExceptionInInitializerError expected");
+        } catch (ExceptionInInitializerError e) {
+            check(e.getCause() instanceof ArithmeticException, "cause of ExceptionInInitializerError");
+        }
+
+        try {
+            new Broken();
+            check(false, "NoClassDefFoundError expected");
+        } catch (NoClassDefFoundError e) {
+            check(e.getMessage().equals("Could not initialize class test.ClassInitTest$Broken"),
+                    "message of NoClassDefFoundError");
+        }
+
+        // errors are thrown as they are
+        try {
+            int x = Failing.x;
+            check(false, "AssertionError expected");
+        } catch (AssertionError e) {
+            check(e.getMessage().equals("fail"), "error thrown by <clinit>");
+        }
+
+        try {
+            int x = Failing.x;
+            check(false, "NoClassDefFoundError expected");
+        } catch (NoClassDefFoundError e) {
+        }
+
+        // a class fails along with its super class
+        try {
+            new Derived();
+            check(false, "ExceptionInInitializerError of super class expected");
+        } catch (ExceptionInInitializerError e) {
+            check(e.getCause() instanceof ArithmeticException, "cause of super class error");
+        }
+
+        try {
+            int y = Derived.y;
+            check(false, "NoClassDefFoundError of sub class expected");
+        } catch (NoClassDefFoundError e) {
+            check(e.getMessage().equals("Could not initialize class test.ClassInitTest$Derived"),
+                    "message of sub class error");
+        }
+        check(!order.contains("Derived"), "<clinit> of failed sub class");
+    }
+}
@@ -0,0 +1,94 @@
+package test;
+
+public class DefaultMethodTest {
+    interface Named {
+        default String name() {
+            return "named";
+        }
+    }
+
+    // more specific than Named
+    interface Titled extends Named {
+        default String name() {
+            return "titled";
+        }
+    }
+
+    interface Other extends Named {
+    }
+
+    static class Doc implements Other, Titled {
+    }
+
+    static class Base implements Named {
+        public String name() {
+            return "base";
+        }
+
+        private String secret() {
+            return "base secret";
+        }
+
+        String callSecret() {
+            // invokespecial of private method, not overridden by Derived.secret
+            return secret();
+        }
+    }
+
+    static class Derived extends Base {
+        public String name() {
+            return "derived";
+        }
+
+        String secret() {
+            return "derived secret";
+        }
+
+        String superName() {
+            return super.name();
+        }
+    }
+
+    static class Mixed implements Titled {
+        public String name() {
+            return "mixed " + Titled.super.name();
+        }
+    }
+
+    static abstract class Shape {
+        abstract int sides();
+
+        int twice() {
+            return sides() * 2;
+        }
+    }
+
+    static class Square extends Shape {
+        int sides() {
+            return 4;
+        }
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        Named d = new Doc();
+        check(d.name().equals("titled"), "maximally-specific default");
+
+        Derived x = new Derived();
+        Named n = x;
+        check(n.name().equals("derived"), "invokeinterface selects class method");
+        check(x.superName().equals("base"), "invokespecial super call");
+        check(x.callSecret().equals("base secret"), "private method not overridden");
+
+        check(new Mixed().name().equals("mixed titled"), "interface super call");
+        check(new Square().twice() == 8, "abstract method selection");
+
+        // public Object methods through interface
+        check(n.hashCode() == System.identityHashCode(x), "Object method via interface");
+    }
+}
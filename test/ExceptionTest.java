@@ -0,0 +1,46 @@
+package test;
+
+import test.Debug;
+
+public class ExceptionTest {
+    static class MyException extends Exception {
+        int code;
+
+        MyException(int code) {
+            super("my exception");
+            this.code = code;
+        }
+    }
+
+    public static void main(String[] args) {
+        try {
+            fail(1);
+        } catch (MyException e) {
+            Debug.println(e.code);
+        }
+
+        try {
+            nested(2);
+        } catch (RuntimeException e) {
+            Debug.println("runtime");
+        } catch (Exception e) {
+            Debug.println(((MyException) e).code);
+        } finally {
+            Debug.println("finally");
+        }
+
+        throw new IllegalStateException("uncaught");
+    }
+
+    static void fail(int code) throws MyException {
+        throw new MyException(code);
+    }
+
+    static void nested(int code) throws MyException {
+        try {
+            fail(code);
+        } finally {
+            Debug.println("nested finally");
+        }
+    }
+}
@@ -0,0 +1,32 @@
+package test;
+
+public class ExitTest {
+    static int depth;
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    static void recurse() {
+        depth++;
+        recurse();
+    }
+
+    public static void main(String[] args) {
+        check(args.length == 2, "args.length");
+        check(args[0].equals("a") && args[1].equals("b c"), "args");
+
+        boolean overflow = false;
+        try {
+            recurse();
+        } catch (StackOverflowError e) {
+            overflow = true;
+        }
+        check(overflow && depth > 100, "stack overflow");
+
+        System.exit(3);
+        throw new RuntimeException("exit returned");
+    }
+}
@@ -0,0 +1,53 @@
+package test;
+
+public class FieldLayoutTest {
+    static class Base {
+        byte b;
+        long l;
+    }
+
+    static class Sub extends Base {
+        boolean z;
+        short s;
+        char c;
+        int i;
+        Object o;
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        Sub x = new Sub();
+        check(x.b == 0 && x.l == 0 && !x.z && x.s == 0 && x.c == 0 && x.i == 0 && x.o == null,
+                "fields start zeroed");
+
+        x.b = -2;
+        x.l = -1L << 40;
+        x.z = true;
+        x.s = -3;
+        x.c = (char) -1;
+        x.i = -4;
+        x.o = "o";
+
+        // neighbours are not overwritten by narrow stores
+        check(x.b == -2, "byte is sign extended");
+        check(x.l == -1L << 40, "long");
+        check(x.z, "boolean");
+        check(x.s == -3, "short is sign extended");
+        check(x.c == 0xffff, "char is zero extended");
+        check(x.i == -4, "int");
+        check(x.o == "o", "reference");
+
+        x.s = (short) 0x12345;
+        x.b++;
+        check(x.s == 0x2345 && x.b == -1 && x.c == 0xffff, "narrowed stores");
+
+        Base base = x;
+        base.b = 7;
+        check(x.b == 7, "super class field");
+    }
+}
@@ -0,0 +1,32 @@
+package test;
+
+import test.Debug;
+
+public class GcTest {
+    public static void main(String[] args) {
+        // garbage is reclaimed, total allocation exceeds the heap limit
+        long sum = 0;
+        for (int i = 0; i < 256; i++) {
+            int[] a = new int[256 * 1024];
+            a[i] = i;
+            sum += a[i];
+        }
+        if (sum != 255 * 256 / 2) {
+            throw new RuntimeException("sum " + sum);
+        }
+
+        // live data can't exceed the heap limit
+        Object[] keep = new Object[1024];
+        try {
+            for (int i = 0; i < keep.length; i++) {
+                keep[i] = new long[128 * 1024];
+            }
+        } catch (OutOfMemoryError e) {
+            keep = null;
+        }
+        if (keep != null) {
+            throw new RuntimeException("no OutOfMemoryError");
+        }
+        Debug.println("ok");
+    }
+}
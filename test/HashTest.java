@@ -0,0 +1,24 @@
+package test;
+
+import test.Debug;
+
+public class HashTest {
+    public static void main(String[] args) {
+        Object o = new Object();
+        int h = o.hashCode();
+
+        // garbage triggers collections, identity hash must not change
+        for (int i = 0; i < 64; i++) {
+            int[] a = new int[256 * 1024];
+            a[0] = i;
+        }
+
+        if (o.hashCode() != h || System.identityHashCode(o) != h) {
+            throw new RuntimeException("identity hash changed");
+        }
+        if (System.identityHashCode(null) != 0) {
+            throw new RuntimeException("identity hash of null");
+        }
+        Debug.println(h);
+    }
+}
@@ -0,0 +1,36 @@
+package test;
+
+public class HostTest {
+    String name = "host";
+
+    static native int add(int a, long b);
+
+    static native double half(double d);
+
+    native String greet(String s);
+
+    static native void fail(String msg);
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    static int twice(int x) {
+        return x * 2;
+    }
+
+    public static void main(String[] args) {
+        check(add(-2, 44L) == 42, "add");
+        check(half(3.0) == 1.5, "half");
+        check(new HostTest().greet("rust").equals("host greets rust"), "greet");
+
+        try {
+            fail("from rust");
+            check(false, "no exception");
+        } catch (IllegalStateException e) {
+            check(e.getMessage().equals("from rust"), "message");
+        }
+    }
+}
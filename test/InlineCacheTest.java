@@ -0,0 +1,83 @@
+package test;
+
+public class InlineCacheTest {
+    interface Shape {
+        int sides();
+    }
+
+    static class Triangle implements Shape {
+        public int sides() {
+            return 3;
+        }
+    }
+
+    static class Square implements Shape {
+        public int sides() {
+            return 4;
+        }
+    }
+
+    static class Pentagon implements Shape {
+        public int sides() {
+            return 5;
+        }
+    }
+
+    static class Hexagon implements Shape {
+        public int sides() {
+            return 6;
+        }
+    }
+
+    static class Heptagon implements Shape {
+        public int sides() {
+            return 7;
+        }
+    }
+
+    static int mono(Shape[] shapes) {
+        int n = 0;
+        for (Shape s : shapes) {
+            n += s.sides();
+        }
+        return n;
+    }
+
+    static int poly(Shape[] shapes) {
+        int n = 0;
+        for (Shape s : shapes) {
+            n += s.sides();
+        }
+        return n;
+    }
+
+    static int mega(Shape[] shapes) {
+        int n = 0;
+        for (Shape s : shapes) {
+            n += s.sides();
+        }
+        return n;
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        Shape[] all = { new Triangle(), new Square(), new Pentagon(), new Hexagon(), new Heptagon() };
+        Shape[] same = new Shape[10];
+        Shape[] two = new Shape[10];
+        for (int i = 0; i < 10; i++) {
+            same[i] = all[0];
+            two[i] = all[i % 2];
+        }
+
+        check(mono(same) == 30, "monomorphic");
+        check(poly(two) == 35, "polymorphic");
+        for (int i = 0; i < 4; i++) {
+            check(mega(all) == 25, "megamorphic");
+        }
+    }
+}
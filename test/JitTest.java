@@ -0,0 +1,92 @@
+package test;
+
+public class JitTest {
+    static long total;
+    int count;
+
+    static int sum(int n) {
+        int s = 0;
+        for (int i = 0; i <= n; i++) {
+            s += i;
+        }
+        return s;
+    }
+
+    static long mix(int[] a, long seed) {
+        long h = seed;
+        for (int i = 0; i < a.length; i++) {
+            h = h * 31 + (a[i] >>> 3) ^ (h >> 7);
+            a[i] = (int) h;
+        }
+        return h;
+    }
+
+    static double norm(double[] v) {
+        double s = 0;
+        for (int i = 0; i < v.length; i++) {
+            s += v[i] * v[i] / 2.0f;
+        }
+        return s > 10 ? s : -s;
+    }
+
+    static int divide(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            return -1;
+        }
+    }
+
+    static int at(int[] a, int i) {
+        try {
+            return a[i];
+        } catch (ArrayIndexOutOfBoundsException e) {
+            return -2;
+        }
+    }
+
+    void count(int n) {
+        for (int i = 0; i < n; i++) {
+            count += i % 3;
+            total += sum(i & 7);
+        }
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        for (int i = 0; i < 50; i++) {
+            check(sum(100) == 5050, "sum");
+        }
+
+        int[] a = new int[64];
+        for (int i = 0; i < a.length; i++) {
+            a[i] = i * 7 - 100;
+        }
+        long h = 0;
+        for (int i = 0; i < 20; i++) {
+            h = mix(a, h);
+        }
+        check(h == -1708759660009871480L, "mix");
+
+        double[] v = { 1.5, -2.25, 3, 0.125 };
+        for (int i = 0; i < 20; i++) {
+            check(norm(v) == -8.1640625, "norm");
+        }
+
+        for (int i = 0; i < 30; i++) {
+            check(divide(7, 2) == 3, "divide");
+            check(divide(Integer.MIN_VALUE, -1) == Integer.MIN_VALUE, "overflow");
+            check(divide(1, 0) == -1, "zero");
+            check(at(a, 3) == a[3] && at(a, 64) == -2 && at(a, -1) == -2, "bounds");
+        }
+
+        JitTest t = new JitTest();
+        t.count(300);
+        check(t.count == 300 && total == 3118, "fields");
+    }
+}
@@ -0,0 +1,48 @@
+package test;
+
+import java.util.function.Function;
+import java.util.function.IntBinaryOperator;
+import java.util.function.Supplier;
+
+import test.Debug;
+
+public class LambdaTest {
+    int base = 10;
+    static int runs = 0;
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) {
+        Runnable r = () -> runs++;
+        r.run();
+        check(runs == 1, "runnable");
+
+        int k = 3;
+        IntBinaryOperator add = (a, b) -> a + b + k;
+        check(add.applyAsInt(1, 2) == 6, "captured local");
+
+        Function<Integer, Integer> inc = x -> x + 1;
+        check(inc.apply(41) == 42, "boxed function");
+
+        Function<String, Integer> parse = Integer::parseInt;
+        check(parse.apply("123") == 123, "static method reference");
+
+        LambdaTest t = new LambdaTest();
+        t.base = 7;
+        Supplier<Integer> s = t::getBase;
+        check(s.get() == 7, "bound method reference");
+
+        Supplier<LambdaTest> ctor = LambdaTest::new;
+        LambdaTest n = ctor.get();
+        check(n != t && n.base == 10, "constructor reference");
+        Debug.println("ok");
+    }
+
+    int getBase() {
+        return base;
+    }
+}
@@ -0,0 +1,96 @@
+package test;
+
+import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+import java.lang.invoke.WrongMethodTypeException;
+
+import test.Debug;
+
+public class MethodHandleTest {
+    static int counter = 5;
+    int base;
+
+    public MethodHandleTest(int base) {
+        this.base = base;
+    }
+
+    int add(int x) {
+        return base + x;
+    }
+
+    static long twice(long x) {
+        return x * 2;
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) throws Throwable {
+        MethodHandles.Lookup lookup = MethodHandles.lookup();
+
+        MethodHandle ctor = lookup.findConstructor(MethodHandleTest.class,
+                MethodType.methodType(void.class, int.class));
+        MethodHandleTest t = (MethodHandleTest) ctor.invokeExact(10);
+        check(t.base == 10, "constructor");
+
+        MethodHandle add = lookup.findVirtual(MethodHandleTest.class, "add",
+                MethodType.methodType(int.class, int.class));
+        int r = (int) add.invokeExact(t, 32);
+        check(r == 42, "virtual");
+
+        MethodHandle twice = lookup.findStatic(MethodHandleTest.class, "twice",
+                MethodType.methodType(long.class, long.class));
+        long l = (long) twice.invokeExact(21L);
+        check(l == 42L, "static");
+
+        MethodHandle getter = lookup.findGetter(MethodHandleTest.class, "base", int.class);
+        MethodHandle setter = lookup.findSetter(MethodHandleTest.class, "base", int.class);
+        setter.invokeExact(t, 7);
+        check(t.base == 7, "setter");
+        check((int) getter.invokeExact(t) == 7, "getter");
+
+        MethodHandle counter = lookup.findStaticGetter(MethodHandleTest.class, "counter", int.class);
+        check((int) counter.invokeExact() == 5, "static getter");
+
+        MethodHandle len = lookup.findVirtual(CharSequence.class, "length",
+                MethodType.methodType(int.class));
+        check((int) len.invokeExact((CharSequence) "hello") == 5, "interface");
+
+        // invoke converts arguments and result to the call site, invokeExact does not
+        Object boxed = add.invoke(t, (short) 3);
+        check(boxed instanceof Integer && (Integer) boxed == 10, "boxed result");
+        check(boxed == Integer.valueOf(10), "boxed result is not cached");
+        long wide = (long) add.invoke(t, 4);
+        check(wide == 11L, "widened result");
+        check((long) twice.invoke(Integer.valueOf(5)) == 10L, "unboxed argument");
+
+        boolean thrown = false;
+        try {
+            Object x = add.invokeExact(t, 1);
+        } catch (WrongMethodTypeException e) {
+            thrown = true;
+        }
+        check(thrown, "invokeExact with wrong type");
+
+        thrown = false;
+        try {
+            add.invoke(t, (Object) "1");
+        } catch (ClassCastException e) {
+            thrown = true;
+        }
+        check(thrown, "invoke with wrong argument");
+
+        thrown = false;
+        try {
+            lookup.findVirtual(MethodHandleTest.class, "missing", MethodType.methodType(void.class));
+        } catch (NoSuchMethodException e) {
+            thrown = true;
+        }
+        check(thrown, "missing method");
+        Debug.println("ok");
+    }
+}
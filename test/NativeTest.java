@@ -0,0 +1,18 @@
+package test;
+
+public class NativeTest {
+    static native int missing(int x);
+
+    public static void main(String[] args) {
+        for (int i = 0; i < 2; i++) {
+            try {
+                missing(i);
+                throw new RuntimeException("linked");
+            } catch (UnsatisfiedLinkError e) {
+                if (!e.getMessage().equals("test.NativeTest.missing(I)I")) {
+                    throw new RuntimeException(e.getMessage());
+                }
+            }
+        }
+    }
+}
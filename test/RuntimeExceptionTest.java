@@ -0,0 +1,91 @@
+package test;
+
+import test.Debug;
+
+public class RuntimeExceptionTest {
+    int x;
+
+    private int secret() {
+        return x;
+    }
+
+    static void check(Throwable e, Class<?> c, String msg) {
+        if (e == null || e.getClass() != c) {
+            throw new RuntimeException(msg + ": " + e);
+        }
+    }
+
+    public static void main(String[] args) {
+        Throwable caught = null;
+        RuntimeExceptionTest t = null;
+        try {
+            Debug.println(t.x);
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, NullPointerException.class, "getfield of null");
+
+        // invokespecial of a private method, quickened by the first receiver
+        RuntimeExceptionTest[] receivers = { new RuntimeExceptionTest(), null };
+        caught = null;
+        int calls = 0;
+        for (RuntimeExceptionTest r : receivers) {
+            try {
+                r.secret();
+                calls++;
+            } catch (RuntimeException e) {
+                caught = e;
+            }
+        }
+        if (calls != 1) {
+            throw new RuntimeException("invokespecial of non-null receiver");
+        }
+        check(caught, NullPointerException.class, "invokespecial of null");
+
+        caught = null;
+        try {
+            Object o = "str";
+            Integer i = (Integer) o;
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, ClassCastException.class, "checkcast");
+
+        caught = null;
+        try {
+            int zero = 0;
+            Debug.println(1 / zero);
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, ArithmeticException.class, "idiv by zero");
+
+        caught = null;
+        try {
+            int[] a = new int[3];
+            a[3] = 1;
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, ArrayIndexOutOfBoundsException.class, "iastore out of bounds");
+
+        caught = null;
+        try {
+            int n = -1;
+            int[] a = new int[n];
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, NegativeArraySizeException.class, "newarray of negative size");
+
+        caught = null;
+        try {
+            Object[] a = new String[1];
+            a[0] = Integer.valueOf(1);
+        } catch (RuntimeException e) {
+            caught = e;
+        }
+        check(caught, ArrayStoreException.class, "aastore of wrong type");
+        Debug.println("ok");
+    }
+}
@@ -0,0 +1,79 @@
+package test;
+
+import test.Debug;
+
+public class SyncTest {
+    private int count;
+
+    synchronized void inc(int n) {
+        if (!Thread.holdsLock(this)) {
+            throw new RuntimeException("synchronized method doesn't hold lock");
+        }
+        count++;
+        // re-entrant
+        if (n > 0) {
+            inc(n - 1);
+        }
+    }
+
+    synchronized void fail() {
+        throw new IllegalStateException("fail");
+    }
+
+    static synchronized boolean holdsClassLock() {
+        return Thread.holdsLock(SyncTest.class);
+    }
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        SyncTest t = new SyncTest();
+        t.inc(10);
+        check(t.count == 11, "count");
+        check(!Thread.holdsLock(t), "lock released after return");
+
+        // lock released during exception unwinding
+        try {
+            t.fail();
+        } catch (IllegalStateException e) {
+            check(!Thread.holdsLock(t), "lock released after throw");
+        }
+
+        check(holdsClassLock(), "static synchronized method locks class");
+        check(!Thread.holdsLock(SyncTest.class), "class lock released");
+
+        Object o = new Object();
+        synchronized (o) {
+            synchronized (o) {
+                check(Thread.holdsLock(o), "nested block");
+            }
+            check(Thread.holdsLock(o), "outer block");
+            o.notify();
+            o.notifyAll();
+            // nobody notifies, wait times out and reacquires the lock
+            o.wait(10);
+            check(Thread.holdsLock(o), "lock reacquired after wait");
+        }
+        check(!Thread.holdsLock(o), "block released");
+
+        try {
+            o.wait();
+            check(false, "wait without lock");
+        } catch (IllegalMonitorStateException e) {
+            Debug.println(e.getMessage());
+        }
+
+        try {
+            o.notify();
+            check(false, "notify without lock");
+        } catch (IllegalMonitorStateException e) {
+            Debug.println(e.getMessage());
+        }
+
+        Debug.println(t.count);
+    }
+}
@@ -0,0 +1,94 @@
+package test;
+
+import test.Debug;
+
+public class ThreadTest {
+    static int counter;
+    static final Object lock = new Object();
+    static Thread self;
+    static boolean interrupted;
+
+    static Object item;
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    static void count() {
+        for (int i = 0; i < 1000; i++) {
+            synchronized (lock) {
+                counter++;
+            }
+            if (i % 100 == 0) {
+                Thread.yield();
+            }
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        Thread main = Thread.currentThread();
+        check(main == Thread.currentThread(), "stable currentThread");
+        check(main.isAlive(), "main is alive");
+
+        // threads interleave and synchronize on a shared lock
+        Thread[] ts = new Thread[4];
+        for (int i = 0; i < ts.length; i++) {
+            ts[i] = new Thread(ThreadTest::count);
+            ts[i].start();
+        }
+        for (Thread t : ts) {
+            t.join();
+            check(!t.isAlive(), "joined thread is terminated");
+        }
+        check(counter == 4000, "counter");
+
+        // currentThread of a started thread is its Thread object
+        Thread t = new Thread(() -> self = Thread.currentThread());
+        t.start();
+        t.join();
+        check(self == t, "currentThread in started thread");
+
+        // producer and consumer with wait and notify
+        Thread consumer = new Thread(() -> {
+            synchronized (lock) {
+                while (item == null) {
+                    try {
+                        lock.wait();
+                    } catch (InterruptedException e) {
+                        return;
+                    }
+                }
+            }
+        });
+        consumer.start();
+        Thread.sleep(5);
+        synchronized (lock) {
+            item = "item";
+            lock.notifyAll();
+        }
+        consumer.join();
+
+        // interrupt a sleeping thread
+        Thread sleeper = new Thread(() -> {
+            try {
+                Thread.sleep(100000);
+            } catch (InterruptedException e) {
+                interrupted = true;
+            }
+        });
+        sleeper.start();
+        Thread.sleep(5);
+        sleeper.interrupt();
+        sleeper.join();
+        check(interrupted, "sleep interrupted");
+
+        // interrupt status of current thread
+        main.interrupt();
+        check(Thread.interrupted(), "interrupted");
+        check(!Thread.interrupted(), "interrupt status cleared");
+
+        Debug.println(counter);
+    }
+}
@@ -0,0 +1,69 @@
+package test;
+
+import java.nio.ByteBuffer;
+import java.util.concurrent.ConcurrentHashMap;
+import java.util.concurrent.atomic.AtomicInteger;
+import java.util.concurrent.atomic.AtomicIntegerArray;
+import java.util.concurrent.atomic.AtomicLong;
+import java.util.concurrent.atomic.AtomicReference;
+import java.util.concurrent.locks.LockSupport;
+
+import test.Debug;
+
+public class UnsafeTest {
+    static volatile boolean unparked;
+
+    static void check(boolean b, String msg) {
+        if (!b) {
+            throw new RuntimeException(msg);
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        AtomicInteger i = new AtomicInteger(1);
+        check(i.incrementAndGet() == 2, "AtomicInteger.incrementAndGet");
+        check(i.compareAndSet(2, 5) && !i.compareAndSet(2, 6), "AtomicInteger.compareAndSet");
+        check(i.getAndAdd(-10) == 5 && i.get() == -5, "AtomicInteger.getAndAdd");
+
+        AtomicLong l = new AtomicLong(1L << 40);
+        check(l.addAndGet(1) == (1L << 40) + 1, "AtomicLong.addAndGet");
+
+        AtomicReference<String> r = new AtomicReference<>("a");
+        check(r.compareAndSet("a", "b") && r.get() == "b", "AtomicReference.compareAndSet");
+
+        AtomicIntegerArray a = new AtomicIntegerArray(8);
+        a.set(7, 3);
+        check(a.incrementAndGet(7) == 4 && a.get(6) == 0, "AtomicIntegerArray");
+
+        ConcurrentHashMap<String, Integer> m = new ConcurrentHashMap<>();
+        for (int k = 0; k < 100; k++) {
+            m.put("k" + k, k);
+        }
+        check(m.size() == 100 && m.get("k42") == 42, "ConcurrentHashMap");
+
+        ByteBuffer b = ByteBuffer.allocateDirect(16);
+        b.putInt(0, 0x12345678);
+        b.putLong(8, -2L);
+        check(b.getInt(0) == 0x12345678 && b.getLong(8) == -2L, "direct ByteBuffer");
+        check(b.get(0) == 0x12, "direct ByteBuffer is big endian");
+
+        // park returns once unparked
+        Thread main = Thread.currentThread();
+        Thread t = new Thread(() -> {
+            unparked = true;
+            LockSupport.unpark(main);
+        });
+        t.start();
+        while (!unparked) {
+            LockSupport.park();
+        }
+        t.join();
+
+        // permit makes the next park return immediately
+        LockSupport.unpark(main);
+        LockSupport.park();
+        LockSupport.parkNanos(1000000);
+
+        Debug.println(i.get());
+    }
+}